@@ -0,0 +1,5 @@
+-- Rollback photo blob outbox
+
+DROP INDEX IF EXISTS idx_photo_blob_outbox_created_at;
+
+DROP TABLE IF EXISTS photo_blob_outbox;
@@ -0,0 +1,13 @@
+-- Outbox of photo blob deletions that still have to be applied to photo storage
+-- Rows are written in the same transaction that removes the photo metadata
+
+CREATE TABLE photo_blob_outbox (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    storage_id VARCHAR(64) NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_photo_blob_outbox_created_at ON photo_blob_outbox(created_at);
@@ -0,0 +1,10 @@
+-- Rollback photo content fingerprints
+
+DROP INDEX IF EXISTS idx_user_photos_needs_review;
+DROP INDEX IF EXISTS idx_user_photos_content_sha256;
+
+ALTER TABLE user_photos
+    DROP COLUMN IF EXISTS duplicate_of,
+    DROP COLUMN IF EXISTS needs_review,
+    DROP COLUMN IF EXISTS perceptual_hash,
+    DROP COLUMN IF EXISTS content_sha256;
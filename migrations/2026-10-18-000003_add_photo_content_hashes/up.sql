@@ -0,0 +1,11 @@
+-- Content fingerprints for photo deduplication and duplicate ID-document detection
+-- perceptual_hash is a 64-bit difference hash; similar images differ in few bits
+
+ALTER TABLE user_photos
+    ADD COLUMN content_sha256 VARCHAR(64),
+    ADD COLUMN perceptual_hash BIGINT,
+    ADD COLUMN needs_review BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN duplicate_of UUID REFERENCES user_photos(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_user_photos_content_sha256 ON user_photos(content_sha256);
+CREATE INDEX idx_user_photos_needs_review ON user_photos(needs_review) WHERE needs_review;
@@ -0,0 +1,13 @@
+-- Rollback photo lifecycle and retention log
+
+DROP INDEX IF EXISTS idx_photo_retention_log_user_id;
+DROP INDEX IF EXISTS idx_photo_retention_log_run_id;
+
+DROP TABLE IF EXISTS photo_retention_log;
+
+DROP INDEX IF EXISTS idx_user_photos_archived_at;
+DROP INDEX IF EXISTS idx_user_photos_user_type_status;
+
+ALTER TABLE user_photos
+    DROP COLUMN IF EXISTS archived_at,
+    DROP COLUMN IF EXISTS status;
@@ -0,0 +1,25 @@
+-- Photo lifecycle for per-type quotas and the retention policy
+-- status: active, superseded (replaced under a quota) or rejected (failed review)
+
+ALTER TABLE user_photos
+    ADD COLUMN status VARCHAR(20) NOT NULL DEFAULT 'active',
+    ADD COLUMN archived_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX idx_user_photos_user_type_status ON user_photos(user_id, photo_type, status);
+CREATE INDEX idx_user_photos_archived_at ON user_photos(archived_at) WHERE archived_at IS NOT NULL;
+
+-- Audit trail of photos removed by the retention policy
+CREATE TABLE photo_retention_log (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    run_id UUID NOT NULL,
+    photo_id UUID NOT NULL,
+    user_id UUID NOT NULL,
+    photo_type VARCHAR(50) NOT NULL,
+    photo_status VARCHAR(20) NOT NULL,
+    photo_url VARCHAR(500) NOT NULL,
+    archived_at TIMESTAMP WITH TIME ZONE,
+    purged_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_photo_retention_log_run_id ON photo_retention_log(run_id);
+CREATE INDEX idx_photo_retention_log_user_id ON photo_retention_log(user_id);
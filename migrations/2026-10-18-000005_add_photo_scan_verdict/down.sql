@@ -0,0 +1,10 @@
+-- Rollback content scanner verdicts
+
+DROP INDEX IF EXISTS idx_user_photos_quarantined;
+
+ALTER TABLE photo_blob_outbox
+    DROP COLUMN IF EXISTS storage_area;
+
+ALTER TABLE user_photos
+    DROP COLUMN IF EXISTS scan_detail,
+    DROP COLUMN IF EXISTS scan_verdict;
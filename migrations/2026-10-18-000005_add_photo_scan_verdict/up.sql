@@ -0,0 +1,10 @@
+-- Content scanner verdicts; photos that are not clean are held in the quarantine storage area
+
+ALTER TABLE user_photos
+    ADD COLUMN scan_verdict VARCHAR(20),
+    ADD COLUMN scan_detail TEXT;
+
+ALTER TABLE photo_blob_outbox
+    ADD COLUMN storage_area VARCHAR(20) NOT NULL DEFAULT 'photos';
+
+CREATE INDEX idx_user_photos_quarantined ON user_photos(created_at) WHERE status = 'quarantined';
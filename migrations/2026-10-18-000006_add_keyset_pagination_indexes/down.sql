@@ -0,0 +1,6 @@
+-- Rollback keyset pagination indexes
+
+DROP INDEX IF EXISTS idx_user_photos_quarantined;
+CREATE INDEX idx_user_photos_quarantined ON user_photos(created_at) WHERE status = 'quarantined';
+
+DROP INDEX IF EXISTS idx_users_created_at_id;
@@ -0,0 +1,6 @@
+-- Indexes backing keyset pagination on (created_at, id)
+
+CREATE INDEX idx_users_created_at_id ON users(created_at, id);
+
+DROP INDEX IF EXISTS idx_user_photos_quarantined;
+CREATE INDEX idx_user_photos_quarantined ON user_photos(created_at, id) WHERE status = 'quarantined';
@@ -0,0 +1,4 @@
+-- Rollback photo duplicate indexes
+
+DROP INDEX IF EXISTS idx_user_photos_type_created_at_id;
+DROP INDEX IF EXISTS idx_user_photos_active_content;
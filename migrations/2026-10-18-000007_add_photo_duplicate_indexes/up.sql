@@ -0,0 +1,23 @@
+-- Exact duplicates: one active photo per user, type and content, so concurrent uploads of the
+-- same file cannot both insert. Older copies that slipped in before are archived first.
+-- The (photo_type, created_at, id) index backs the keyset scan of the possible-duplicates report.
+
+UPDATE user_photos
+SET status = 'superseded', archived_at = NOW(), updated_at = NOW()
+WHERE id IN (
+    SELECT id FROM (
+        SELECT id, ROW_NUMBER() OVER (
+            PARTITION BY user_id, photo_type, content_sha256
+            ORDER BY created_at DESC, id DESC
+        ) AS copy
+        FROM user_photos
+        WHERE status = 'active' AND content_sha256 IS NOT NULL
+    ) copies
+    WHERE copy > 1
+);
+
+CREATE UNIQUE INDEX idx_user_photos_active_content
+    ON user_photos(user_id, photo_type, content_sha256)
+    WHERE status = 'active' AND content_sha256 IS NOT NULL;
+
+CREATE INDEX idx_user_photos_type_created_at_id ON user_photos(photo_type, created_at, id);
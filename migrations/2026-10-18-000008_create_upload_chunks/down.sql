@@ -0,0 +1,14 @@
+-- Rollback staged upload chunks
+
+ALTER TABLE upload_sessions ADD COLUMN data BYTEA NOT NULL DEFAULT ''::bytea;
+
+UPDATE upload_sessions
+SET data = staged.data
+FROM (
+    SELECT session_id, string_agg(data, ''::bytea ORDER BY chunk_offset) AS data
+    FROM upload_chunks
+    GROUP BY session_id
+) staged
+WHERE upload_sessions.id = staged.session_id;
+
+DROP TABLE IF EXISTS upload_chunks;
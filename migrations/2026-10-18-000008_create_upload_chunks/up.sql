@@ -0,0 +1,14 @@
+-- Resumable uploads stage each chunk as its own row instead of appending to one BYTEA,
+-- so a PATCH writes only its own bytes. Chunks are joined in offset order on completion.
+
+CREATE TABLE upload_chunks (
+    session_id UUID NOT NULL REFERENCES upload_sessions(id) ON DELETE CASCADE,
+    chunk_offset BIGINT NOT NULL,
+    data BYTEA NOT NULL,
+    PRIMARY KEY (session_id, chunk_offset)
+);
+
+INSERT INTO upload_chunks (session_id, chunk_offset, data)
+SELECT id, 0, data FROM upload_sessions WHERE length(data) > 0;
+
+ALTER TABLE upload_sessions DROP COLUMN data;
@@ -0,0 +1,33 @@
+#!/usr/bin/env sh
+# Generates a local CA, a server certificate for localhost and a client certificate for
+# testing TLS and gRPC mTLS in development. Never use these certificates in production.
+#
+#   scripts/gen-dev-certs.sh [output-dir] [client-name]
+#
+# Defaults: certs/ and "internal-service". The client name becomes the certificate's CN,
+# which the gRPC server reports as the caller's service identity.
+set -eu
+
+OUT="${1:-certs}"
+CLIENT="${2:-internal-service}"
+DAYS=825
+
+mkdir -p "$OUT"
+cd "$OUT"
+
+openssl req -x509 -newkey rsa:2048 -nodes -days "$DAYS" \
+    -keyout ca.key -out ca.crt -subj "/CN=Stander Dev CA" \
+    -addext "basicConstraints=critical,CA:TRUE" -addext "keyUsage=critical,keyCertSign,cRLSign"
+
+openssl req -newkey rsa:2048 -nodes -keyout server.key -out server.csr -subj "/CN=localhost"
+printf 'subjectAltName=DNS:localhost,IP:127.0.0.1\nextendedKeyUsage=serverAuth\n' > server.ext
+openssl x509 -req -in server.csr -CA ca.crt -CAkey ca.key -CAcreateserial \
+    -days "$DAYS" -extfile server.ext -out server.crt
+
+openssl req -newkey rsa:2048 -nodes -keyout client.key -out client.csr -subj "/CN=$CLIENT"
+printf 'extendedKeyUsage=clientAuth\n' > client.ext
+openssl x509 -req -in client.csr -CA ca.crt -CAkey ca.key -CAcreateserial \
+    -days "$DAYS" -extfile client.ext -out client.crt
+
+rm -f server.csr server.ext client.csr client.ext ca.srl
+echo "Wrote ca.crt, server.crt/key and client.crt/key (CN=$CLIENT) to $OUT"
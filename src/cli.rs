@@ -0,0 +1,137 @@
+//! Command-line interface
+
+use clap::{Parser, Subcommand};
+use uuid::Uuid;
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Monolithic Rust service with gRPC and REST APIs")]
+pub struct Cli {
+    /// Override a configuration key, e.g. `--set server.rest_port=9090`. Repeatable.
+    #[arg(long = "set", global = true, value_name = "KEY=VALUE", value_parser = parse_override)]
+    pub overrides: Vec<(String, String)>,
+    /// Defaults to `serve` when omitted.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Start the REST and gRPC servers
+    Serve {
+        /// Only start the REST server
+        #[arg(long, conflicts_with = "grpc_only")]
+        rest_only: bool,
+        /// Only start the gRPC server
+        #[arg(long)]
+        grpc_only: bool,
+    },
+    /// Manage the embedded PostgreSQL migrations
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+    /// Create a user with the admin role
+    CreateAdmin {
+        #[arg(long)]
+        email: String,
+        /// Read from stdin when omitted, so it stays out of the shell history
+        #[arg(long)]
+        password: Option<String>,
+        #[arg(long)]
+        phone: String,
+        #[arg(long, default_value = "+971")]
+        country_code: String,
+        #[arg(long)]
+        first_name: String,
+        #[arg(long)]
+        last_name: String,
+    },
+    /// Set a user's password without knowing the old one
+    ResetPassword {
+        #[arg(long)]
+        email: String,
+        /// Read from stdin when omitted, so it stays out of the shell history
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Print one page of users as JSON
+    ListUsers {
+        #[arg(long)]
+        role: Option<String>,
+        #[arg(long)]
+        active: Option<bool>,
+        /// Case-insensitive match on name, email or phone
+        #[arg(long)]
+        search: Option<String>,
+        /// created_at, updated_at, email, first_name, last_name or role
+        #[arg(long, default_value = "created_at")]
+        sort_by: String,
+        /// asc or desc
+        #[arg(long, default_value = "desc")]
+        sort_order: String,
+        #[arg(long, default_value_t = 20, value_parser = clap::value_parser!(u32).range(1..=100))]
+        limit: u32,
+        #[arg(long, default_value_t = 1, conflicts_with = "cursor")]
+        page: u32,
+        /// Cursor from a previous page
+        #[arg(long)]
+        cursor: Option<String>,
+        #[arg(long)]
+        include_photos: bool,
+    },
+    /// Mark a user's photo as verified
+    VerifyPhoto {
+        #[arg(long)]
+        user_id: Uuid,
+        #[arg(long)]
+        photo_id: Uuid,
+    },
+    /// Run one blob/metadata reconciliation pass
+    ReconcilePhotos {
+        /// Report what would change without changing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Run the photo retention policy once
+    ApplyRetention {
+        /// Report what would be purged without purging it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Generate new JWT and photo URL signing keys
+    ///
+    /// Deploying them invalidates every issued token and signed photo URL.
+    RotateKeys,
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum MigrateAction {
+    /// Apply all pending migrations
+    Up,
+    /// Revert the latest applied migration
+    Down,
+    /// List every migration and whether it is applied
+    Status,
+    /// Revert and re-apply the latest migration
+    Redo,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigAction {
+    /// Validate the configuration and exit non-zero on problems
+    Check {
+        /// Also try to connect to PostgreSQL and MongoDB
+        #[arg(long)]
+        connect: bool,
+    },
+}
+
+fn parse_override(value: &str) -> Result<(String, String), String> {
+    let (key, value) = value.split_once('=').ok_or_else(|| format!("expected KEY=VALUE, got {:?}", value))?;
+    Ok((key.trim().to_string(), value.to_string()))
+}
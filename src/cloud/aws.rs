@@ -5,7 +5,8 @@ use aws_config::{BehaviorVersion, Region};
 use aws_sdk_s3::Client as S3Client;
 use aws_sdk_dynamodb::Client as DynamoDbClient;
 use aws_sdk_lambda::Client as LambdaClient;
-use std::env;
+use aws_sdk_s3::config::Credentials;
+use tracing::{info, instrument};
 
 #[derive(Clone, Debug)]
 pub struct AwsConfig {
@@ -16,18 +17,25 @@ pub struct AwsConfig {
 }
 
 
-pub async fn initialize_aws_config() -> Result<AwsConfig> {
-    let config = aws_config::defaults(BehaviorVersion::latest())
-        .region(Region::new(
-            env::var("AWS_REGION").unwrap_or_else(|_| "us-east-1".to_string())
-        ))
-        .load()
-        .await;
+/// Static keys from the configuration take precedence over the SDK's default credential chain.
+pub async fn initialize_aws_config(settings: &crate::config::AwsConfig) -> Result<AwsConfig> {
+    let mut loader = aws_config::defaults(BehaviorVersion::latest())
+        .region(Region::new(settings.region.clone()));
+    if let (Some(access_key_id), Some(secret_access_key)) = (&settings.access_key_id, &settings.secret_access_key) {
+        loader = loader.credentials_provider(Credentials::new(
+            access_key_id,
+            secret_access_key.expose(),
+            None,
+            None,
+            "config",
+        ));
+    }
+    let config = loader.load().await;
     let s3_client = S3Client::new(&config);
     let dynamodb_client = DynamoDbClient::new(&config);
     let lambda_client = LambdaClient::new(&config);
     let region = config.region().unwrap_or(&Region::new("us-east-1")).clone();
-    println!("AWS SDK initialized successfully for region: {}", region);
+    info!(%region, "AWS SDK initialized");
     Ok(AwsConfig {
         s3_client,
         dynamodb_client,
@@ -39,6 +47,8 @@ pub async fn initialize_aws_config() -> Result<AwsConfig> {
 pub mod s3 {
     use super::*;
     use aws_sdk_s3::primitives::ByteStream;
+    use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
+    #[instrument(name = "s3.upload_file", skip_all, fields(otel.kind = "client", bucket = %bucket, key = %key))]
     pub async fn upload_file(
         client: &S3Client,
         bucket: &str,
@@ -54,9 +64,10 @@ pub mod s3 {
             .await
             .context("Failed to upload file to S3")?;
 
-        println!("File uploaded to S3: s3://{}/{}", bucket, key);
+        info!(bucket, key, "File uploaded to S3");
         Ok(())
     }
+    #[instrument(name = "s3.download_file", skip_all, fields(otel.kind = "client", bucket = %bucket, key = %key))]
     pub async fn download_file(
         client: &S3Client,
         bucket: &str,
@@ -72,11 +83,202 @@ pub mod s3 {
 
         Ok(response.body)
     }
+    #[instrument(name = "s3.put_object", skip_all, fields(otel.kind = "client", bucket = %bucket, key = %key))]
+    pub async fn put_object(
+        client: &S3Client,
+        bucket: &str,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+    ) -> Result<()> {
+        client
+            .put_object()
+            .bucket(bucket)
+            .key(key)
+            .content_type(content_type)
+            .body(ByteStream::from(data))
+            .send()
+            .await
+            .context("Failed to upload object to S3")?;
+        Ok(())
+    }
+    #[instrument(name = "s3.get_object", skip_all, fields(otel.kind = "client", bucket = %bucket, key = %key))]
+    pub async fn get_object(
+        client: &S3Client,
+        bucket: &str,
+        key: &str,
+    ) -> Result<(Vec<u8>, Option<String>)> {
+        let response = client
+            .get_object()
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await
+            .context("Failed to download object from S3")?;
+        let content_type = response.content_type().map(|c| c.to_string());
+        let data = response.body.collect().await
+            .context("Failed to read S3 object body")?
+            .into_bytes()
+            .to_vec();
+        Ok((data, content_type))
+    }
+    #[instrument(name = "s3.delete_object", skip_all, fields(otel.kind = "client", bucket = %bucket, key = %key))]
+    pub async fn delete_object(
+        client: &S3Client,
+        bucket: &str,
+        key: &str,
+    ) -> Result<()> {
+        client
+            .delete_object()
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await
+            .context("Failed to delete object from S3")?;
+        Ok(())
+    }
+    /// Returns `(key, last_modified)` for every object under `prefix`.
+    #[instrument(name = "s3.list_objects", skip_all, fields(otel.kind = "client", bucket = %bucket, prefix = %prefix))]
+    pub async fn list_objects(
+        client: &S3Client,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Option<chrono::DateTime<chrono::Utc>>)>> {
+        let mut objects = Vec::new();
+        let mut pages = client
+            .list_objects_v2()
+            .bucket(bucket)
+            .prefix(prefix)
+            .into_paginator()
+            .send();
+        while let Some(page) = pages.next().await {
+            let page = page.context("Failed to list S3 objects")?;
+            for object in page.contents() {
+                if let Some(key) = object.key() {
+                    let last_modified = object.last_modified()
+                        .and_then(|t| chrono::DateTime::from_timestamp(t.secs(), t.subsec_nanos()));
+                    objects.push((key.to_string(), last_modified));
+                }
+            }
+        }
+        Ok(objects)
+    }
+    #[instrument(name = "s3.object_exists", skip_all, fields(otel.kind = "client", bucket = %bucket, key = %key))]
+    pub async fn object_exists(
+        client: &S3Client,
+        bucket: &str,
+        key: &str,
+    ) -> Result<bool> {
+        match client.head_object().bucket(bucket).key(key).send().await {
+            Ok(_) => Ok(true),
+            Err(e) if e.as_service_error().map(|e| e.is_not_found()).unwrap_or(false) => Ok(false),
+            Err(e) => Err(anyhow::Error::new(e).context("Failed to check S3 object")),
+        }
+    }
+    #[instrument(name = "s3.create_multipart_upload", skip_all, fields(otel.kind = "client", bucket = %bucket, key = %key))]
+    pub async fn create_multipart_upload(
+        client: &S3Client,
+        bucket: &str,
+        key: &str,
+        content_type: &str,
+    ) -> Result<String> {
+        let response = client
+            .create_multipart_upload()
+            .bucket(bucket)
+            .key(key)
+            .content_type(content_type)
+            .send()
+            .await
+            .context("Failed to start S3 multipart upload")?;
+        response.upload_id()
+            .map(|id| id.to_string())
+            .ok_or_else(|| anyhow::anyhow!("S3 did not return a multipart upload ID"))
+    }
+    #[instrument(name = "s3.upload_part", skip_all, fields(otel.kind = "client", bucket = %bucket, key = %key, upload_id = %upload_id))]
+    pub async fn upload_part(
+        client: &S3Client,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        data: Vec<u8>,
+    ) -> Result<CompletedPart> {
+        let response = client
+            .upload_part()
+            .bucket(bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .part_number(part_number)
+            .body(ByteStream::from(data))
+            .send()
+            .await
+            .context("Failed to upload S3 multipart part")?;
+        Ok(CompletedPart::builder()
+            .set_e_tag(response.e_tag().map(|tag| tag.to_string()))
+            .part_number(part_number)
+            .build())
+    }
+    #[instrument(name = "s3.complete_multipart_upload", skip_all, fields(otel.kind = "client", bucket = %bucket, key = %key, upload_id = %upload_id))]
+    pub async fn complete_multipart_upload(
+        client: &S3Client,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        parts: Vec<CompletedPart>,
+    ) -> Result<()> {
+        client
+            .complete_multipart_upload()
+            .bucket(bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .multipart_upload(CompletedMultipartUpload::builder().set_parts(Some(parts)).build())
+            .send()
+            .await
+            .context("Failed to complete S3 multipart upload")?;
+        Ok(())
+    }
+    #[instrument(name = "s3.abort_multipart_upload", skip_all, fields(otel.kind = "client", bucket = %bucket, key = %key, upload_id = %upload_id))]
+    pub async fn abort_multipart_upload(
+        client: &S3Client,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+    ) -> Result<()> {
+        client
+            .abort_multipart_upload()
+            .bucket(bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .send()
+            .await
+            .context("Failed to abort S3 multipart upload")?;
+        Ok(())
+    }
+    #[instrument(name = "s3.presign_get_object", skip_all, fields(otel.kind = "client", bucket = %bucket, key = %key))]
+    pub async fn presign_get_object(
+        client: &S3Client,
+        bucket: &str,
+        key: &str,
+        expires_in: std::time::Duration,
+    ) -> Result<String> {
+        use aws_sdk_s3::presigning::PresigningConfig;
+        let presigning = PresigningConfig::expires_in(expires_in)
+            .context("Invalid presigned URL expiry")?;
+        let request = client
+            .get_object()
+            .bucket(bucket)
+            .key(key)
+            .presigned(presigning)
+            .await
+            .context("Failed to presign S3 download")?;
+        Ok(request.uri().to_string())
+    }
 }
 pub mod dynamodb {
     use super::*;
     use aws_sdk_dynamodb::types::AttributeValue;
     use std::collections::HashMap;
+    #[instrument(name = "dynamodb.put_item", skip_all, fields(otel.kind = "client", table_name = %table_name))]
     pub async fn put_item(
         client: &DynamoDbClient,
         table_name: &str,
@@ -90,9 +292,10 @@ pub mod dynamodb {
             .await
             .context("Failed to put item in DynamoDB")?;
 
-        println!("Item inserted into DynamoDB table: {}", table_name);
+        info!(table_name, "Item inserted into DynamoDB");
         Ok(())
     }
+    #[instrument(name = "dynamodb.get_item", skip_all, fields(otel.kind = "client", table_name = %table_name))]
     pub async fn get_item(
         client: &DynamoDbClient,
         table_name: &str,
@@ -113,6 +316,7 @@ pub mod dynamodb {
 pub mod lambda {
     use super::*;
     use aws_sdk_lambda::primitives::Blob;
+    #[instrument(name = "lambda.invoke_function", skip_all, fields(otel.kind = "client", function_name = %function_name))]
     pub async fn invoke_function(
         client: &LambdaClient,
         function_name: &str,
@@ -125,7 +329,7 @@ pub mod lambda {
             .send()
             .await
             .context("Failed to invoke Lambda function")?;
-        println!("Lambda function invoked: {}", function_name);
+        info!(function_name, "Lambda function invoked");
         Ok(response.payload)
     }
 }
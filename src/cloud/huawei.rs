@@ -4,34 +4,60 @@ use anyhow::{Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use reqwest::Client;
+use tracing::{debug, info, instrument};
 
 
 #[derive(Clone, Debug)]
 pub struct HuaweiConfig {
     pub region: String,
     pub access_key: String,
-    pub secret_key: String,
+    pub secret_key: crate::config::Secret,
     pub project_id: String,
     pub http_client: Client,
     pub base_url: String,
+    /// OBS speaks the S3 API and accepts V4 signatures, so it is reached through an S3 client
+    /// pointed at the OBS endpoint and signing with the access key pair.
+    pub obs_client: aws_sdk_s3::Client,
 }
 
 /// Initialize Huawei Cloud configuration
-pub async fn initialize_huawei_config() -> Result<HuaweiConfig> {
-    let region = std::env::var("HUAWEI_REGION").unwrap_or_else(|_| "cn-north-1".to_string());
-    let access_key = std::env::var("HUAWEI_ACCESS_KEY")?;
-    let secret_key = std::env::var("HUAWEI_SECRET_KEY")?;
-    let project_id = std::env::var("HUAWEI_PROJECT_ID")?;
-    let http_client = Client::new();
-    let base_url = format!("https://{}.myhuaweicloud.com", region);
+pub async fn initialize_huawei_config(settings: &crate::config::HuaweiConfig) -> Result<HuaweiConfig> {
+    for (key, value) in [("access_key", settings.access_key.as_str()), ("secret_key", settings.secret_key.expose()), ("project_id", settings.project_id.as_str())] {
+        if value.is_empty() {
+            anyhow::bail!("cloud.huawei.{} is required when Huawei Cloud services are enabled", key);
+        }
+    }
+    let base_url = if settings.endpoint.is_empty() {
+        format!("https://{}.myhuaweicloud.com", settings.region)
+    } else {
+        settings.endpoint.clone()
+    };
+    let obs_endpoint = if settings.obs_endpoint.is_empty() {
+        format!("https://obs.{}.myhuaweicloud.com", settings.region)
+    } else {
+        settings.obs_endpoint.clone()
+    };
+    let obs_config = aws_sdk_s3::Config::builder()
+        .behavior_version(aws_config::BehaviorVersion::latest())
+        .region(aws_config::Region::new(settings.region.clone()))
+        .endpoint_url(obs_endpoint)
+        .credentials_provider(aws_sdk_s3::config::Credentials::new(
+            &settings.access_key,
+            settings.secret_key.expose(),
+            None,
+            None,
+            "huawei",
+        ))
+        .build();
 
     Ok(HuaweiConfig {
-        region,
-        access_key,
-        secret_key,
-        project_id,
-        http_client,
+        region: settings.region.clone(),
+        access_key: settings.access_key.clone(),
+        secret_key: settings.secret_key.clone(),
+        project_id: settings.project_id.clone(),
+        http_client: Client::new(),
         base_url,
+        obs_client: aws_sdk_s3::Client::from_conf(obs_config),
     })
 }
 
@@ -46,8 +72,9 @@ pub mod ecs {
         pub flavor: String,
         pub image: String,
     }
+    #[instrument(name = "ecs.list_servers", skip_all, fields(otel.kind = "client"))]
     pub async fn list_servers(config: &HuaweiConfig) -> Result<Vec<ServerInfo>> {
-        println!("Listing ECS servers for project: {}", config.project_id);
+        debug!(project_id = %config.project_id, "Listing ECS servers");
         Ok(vec![
             ServerInfo {
                 id: "server-1".to_string(),
@@ -58,13 +85,14 @@ pub mod ecs {
             }
         ])
     }
+    #[instrument(name = "ecs.create_server", skip_all, fields(otel.kind = "client", name = %name, flavor = %flavor))]
     pub async fn create_server(
-        config: &HuaweiConfig,
+        _config: &HuaweiConfig,
         name: &str,
         flavor: &str,
         image: &str,
     ) -> Result<ServerInfo> {
-        println!("Creating ECS server: {} with flavor: {}", name, flavor);
+        info!(name, flavor, "Creating ECS server");
         Ok(ServerInfo {
             id: format!("server-{}", uuid::Uuid::new_v4()),
             name: name.to_string(),
@@ -75,63 +103,82 @@ pub mod ecs {
     }
 }
 
+/// Object Storage Service, through `HuaweiConfig::obs_client`.
 pub mod obs {
     use super::*;
+    use anyhow::Context;
+    use crate::cloud::aws::s3;
 
     #[derive(Debug, Serialize, Deserialize)]
     pub struct BucketInfo {
         pub name: String,
         pub creation_date: String,
-        pub location: String,
     }
     #[derive(Debug, Serialize, Deserialize)]
     pub struct ObjectInfo {
         pub key: String,
-        pub size: u64,
-        pub last_modified: String,
-        pub etag: String,
+        pub last_modified: Option<String>,
     }
+    #[instrument(name = "obs.list_buckets", skip_all, fields(otel.kind = "client"))]
     pub async fn list_buckets(config: &HuaweiConfig) -> Result<Vec<BucketInfo>> {
-        println!("Listing OBS buckets for project: {}", config.project_id);
-        Ok(vec![
-            BucketInfo {
-                name: "example-bucket".to_string(),
-                creation_date: chrono::Utc::now().to_rfc3339(),
-                location: "ap-southeast-1".to_string(),
-            }
-        ])
+        debug!(project_id = %config.project_id, "Listing OBS buckets");
+        let response = config.obs_client.list_buckets().send().await
+            .context("Failed to list OBS buckets")?;
+        Ok(response.buckets().iter()
+            .map(|bucket| BucketInfo {
+                name: bucket.name().unwrap_or_default().to_string(),
+                creation_date: bucket.creation_date().map(|t| t.to_string()).unwrap_or_default(),
+            })
+            .collect())
     }
+    #[instrument(name = "obs.upload_object", skip_all, fields(otel.kind = "client", bucket = %bucket, key = %key))]
     pub async fn upload_object(
         config: &HuaweiConfig,
         bucket: &str,
         key: &str,
         data: &[u8],
     ) -> Result<()> {
-        println!("Uploading object to OBS: {}/{}", bucket, key);
-        Ok(())
+        info!(bucket, key, "Uploading object to OBS");
+        s3::put_object(&config.obs_client, bucket, key, data.to_vec(), "application/octet-stream").await
     }
+    #[instrument(name = "obs.download_object", skip_all, fields(otel.kind = "client", bucket = %bucket, key = %key))]
     pub async fn download_object(
         config: &HuaweiConfig,
         bucket: &str,
         key: &str,
     ) -> Result<Vec<u8>> {
-        println!("Downloading object from OBS: {}/{}", bucket, key);
-        Ok(vec![1, 2, 3, 4, 5])
+        debug!(bucket, key, "Downloading object from OBS");
+        Ok(s3::get_object(&config.obs_client, bucket, key).await?.0)
     }
-
+    #[instrument(name = "obs.delete_object", skip_all, fields(otel.kind = "client", bucket = %bucket, key = %key))]
+    pub async fn delete_object(
+        config: &HuaweiConfig,
+        bucket: &str,
+        key: &str,
+    ) -> Result<()> {
+        info!(bucket, key, "Deleting object from OBS");
+        s3::delete_object(&config.obs_client, bucket, key).await
+    }
+    #[instrument(name = "obs.list_objects", skip_all, fields(otel.kind = "client", bucket = %bucket))]
     pub async fn list_objects(
         config: &HuaweiConfig,
         bucket: &str,
+        prefix: &str,
     ) -> Result<Vec<ObjectInfo>> {
-        println!("Listing objects in OBS bucket: {}", bucket);
-        Ok(vec![
-            ObjectInfo {
-                key: "example-file.txt".to_string(),
-                size: 1024,
-                last_modified: chrono::Utc::now().to_rfc3339(),
-                etag: "\"d41d8cd98f00b204e9800998ecf8427e\"".to_string(),
-            }
-        ])
+        debug!(bucket, "Listing objects in OBS bucket");
+        Ok(s3::list_objects(&config.obs_client, bucket, prefix).await?
+            .into_iter()
+            .map(|(key, last_modified)| ObjectInfo { key, last_modified: last_modified.map(|t| t.to_rfc3339()) })
+            .collect())
+    }
+    /// Query-string authenticated GET URL (signature V4) on the OBS endpoint.
+    pub async fn presign_get_object(
+        config: &HuaweiConfig,
+        bucket: &str,
+        key: &str,
+        expires_in: std::time::Duration,
+    ) -> Result<String> {
+        s3::presign_get_object(&config.obs_client, bucket, key, expires_in).await
     }
 }
 
@@ -139,8 +186,8 @@ pub mod utils {
     use super::*;
     pub fn generate_auth_headers(
         config: &HuaweiConfig,
-        method: &str,
-        uri: &str,
+        _method: &str,
+        _uri: &str,
     ) -> HashMap<String, String> {
         let mut headers = HashMap::new();
         headers.insert("Authorization".to_string(), format!("AWS4-HMAC-SHA256 Credential={}", config.access_key));
@@ -150,3 +197,33 @@ pub mod utils {
         headers
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_obs_presigned_url() {
+        let settings = crate::config::HuaweiConfig {
+            endpoint: String::new(),
+            obs_endpoint: String::new(),
+            region: "ap-southeast-1".to_string(),
+            access_key: "AKEXAMPLE".to_string(),
+            secret_key: "secret".into(),
+            project_id: "project".to_string(),
+        };
+        let config = initialize_huawei_config(&settings).await.unwrap();
+        let url = obs::presign_get_object(&config, "photos-bucket", "photos/abc", std::time::Duration::from_secs(300))
+            .await
+            .unwrap();
+        let url = reqwest::Url::parse(&url).unwrap();
+        assert_eq!(url.host_str(), Some("photos-bucket.obs.ap-southeast-1.myhuaweicloud.com"));
+        assert_eq!(url.path(), "/photos/abc");
+        let query: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        assert_eq!(query["X-Amz-Algorithm"], "AWS4-HMAC-SHA256");
+        assert!(query["X-Amz-Credential"].starts_with("AKEXAMPLE/"));
+        assert!(query["X-Amz-Credential"].contains("/ap-southeast-1/s3/aws4_request"));
+        assert_eq!(query["X-Amz-Expires"], "300");
+        assert!(!query["X-Amz-Signature"].is_empty());
+    }
+}
@@ -12,7 +12,7 @@ use tracing::info;
 pub async fn initialize_enabled_services(config: &crate::config::CloudConfig) -> Result<(Option<aws::AwsConfig>, Option<huawei::HuaweiConfig>)> {
     let aws_config = if config.enable_aws_services {
         info!("Initializing AWS services...");
-        Some(aws::initialize_aws_config().await?)
+        Some(aws::initialize_aws_config(&config.aws).await?)
     } else {
         info!("AWS services disabled");
         None
@@ -20,7 +20,7 @@ pub async fn initialize_enabled_services(config: &crate::config::CloudConfig) ->
 
     let huawei_config = if config.enable_huawei_services {
         info!("Initializing Huawei Cloud services...");
-        Some(huawei::initialize_huawei_config().await?)
+        Some(huawei::initialize_huawei_config(&config.huawei).await?)
     } else {
         info!("Huawei Cloud services disabled");
         None
@@ -0,0 +1,80 @@
+//! Availability of optional backends
+//!
+//! Dependencies not listed in `health.required_dependencies` may be missing at startup or drop
+//! out later. Their availability is tracked here so that features needing one can fail fast
+//! with `DependencyUnavailable`, which the APIs report as 503, while everything else keeps
+//! serving.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+pub const POSTGRES: &str = "postgres";
+pub const MONGODB: &str = "mongodb";
+pub const AWS: &str = "aws";
+pub const HUAWEI: &str = "huawei";
+
+/// Every dependency name accepted in `health.required_dependencies`.
+pub const ALL: [&str; 4] = [POSTGRES, MONGODB, AWS, HUAWEI];
+
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{dependency} is unavailable: {reason}")]
+pub struct DependencyUnavailable {
+    pub dependency: &'static str,
+    pub reason: String,
+}
+
+/// Whether `error` was caused by a missing dependency.
+pub fn unavailable_cause(error: &anyhow::Error) -> Option<&DependencyUnavailable> {
+    error.chain().find_map(|cause| cause.downcast_ref::<DependencyUnavailable>())
+}
+
+/// Dependencies currently unavailable, with the reason. Anything absent is assumed available.
+#[derive(Clone, Debug, Default)]
+pub struct Dependencies {
+    unavailable: Arc<RwLock<BTreeMap<&'static str, String>>>,
+}
+
+impl Dependencies {
+    /// Returns true if `dependency` was unavailable until now.
+    pub fn mark_available(&self, dependency: &'static str) -> bool {
+        self.unavailable.write().unwrap_or_else(|e| e.into_inner()).remove(dependency).is_some()
+    }
+
+    /// Returns true if `dependency` was available until now.
+    pub fn mark_unavailable(&self, dependency: &'static str, reason: impl Into<String>) -> bool {
+        self.unavailable.write().unwrap_or_else(|e| e.into_inner()).insert(dependency, reason.into()).is_none()
+    }
+
+    pub fn check(&self, dependency: &'static str) -> Result<(), DependencyUnavailable> {
+        match self.unavailable.read().unwrap_or_else(|e| e.into_inner()).get(dependency) {
+            Some(reason) => Err(DependencyUnavailable { dependency, reason: reason.clone() }),
+            None => Ok(()),
+        }
+    }
+
+    pub fn unavailable(&self) -> BTreeMap<&'static str, String> {
+        self.unavailable.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_availability_transitions() {
+        let dependencies = Dependencies::default();
+        assert!(dependencies.check(MONGODB).is_ok());
+        assert!(dependencies.mark_unavailable(MONGODB, "connection refused"));
+        assert!(!dependencies.mark_unavailable(MONGODB, "timed out"));
+
+        let error = anyhow::Error::new(dependencies.check(MONGODB).unwrap_err()).context("Failed to store photo");
+        let cause = unavailable_cause(&error).unwrap();
+        assert_eq!(cause.to_string(), "mongodb is unavailable: timed out");
+        assert!(dependencies.check(AWS).is_ok());
+
+        assert!(dependencies.mark_available(MONGODB));
+        assert!(!dependencies.mark_available(MONGODB));
+        assert!(dependencies.unavailable().is_empty());
+    }
+}
@@ -6,6 +6,8 @@ use tokio::sync::RwLock;
 use std::collections::HashMap;
 use tracing::{info, warn, debug};
 
+type CacheMap = HashMap<String, (Vec<u8>, Instant)>;
+
 
 #[derive(Debug, Clone)]
 pub struct MemoryStats {
@@ -40,7 +42,8 @@ impl Default for GcConfig {
 pub struct MemoryManager {
     config: GcConfig,
     stats: Arc<RwLock<MemoryStats>>,
-    cache: Arc<RwLock<HashMap<String, (Vec<u8>, Instant)>>>,
+    cache: Arc<RwLock<CacheMap>>,
+    gc_task: std::sync::Mutex<Option<tokio::task::JoinHandle<()>>>,
 }
 
 impl MemoryManager {
@@ -54,6 +57,7 @@ impl MemoryManager {
                 last_gc_time: None,
             })),
             cache: Arc::new(RwLock::new(HashMap::new())),
+            gc_task: std::sync::Mutex::new(None),
         }
     }
 
@@ -66,7 +70,7 @@ impl MemoryManager {
         let cache = Arc::clone(&self.cache);
         let config = self.config.clone();
 
-        tokio::spawn(async move {
+        let task = tokio::spawn(async move {
             let mut interval = tokio::time::interval(Duration::from_secs(config.gc_interval_seconds));
 
             loop {
@@ -88,6 +92,14 @@ impl MemoryManager {
                 }
             }
         });
+        *self.gc_task.lock().unwrap() = Some(task);
+    }
+    /// Stops the auto-GC loop and releases everything cached.
+    pub async fn shutdown(&self) {
+        if let Some(task) = self.gc_task.lock().unwrap().take() {
+            task.abort();
+        }
+        self.clear_cache().await;
     }
     pub async fn force_gc(&self) {
         info!("Manual GC triggered");
@@ -138,7 +150,7 @@ impl MemoryManager {
     }
     async fn run_gc_internal(
         stats: &Arc<RwLock<MemoryStats>>,
-        cache: &Arc<RwLock<HashMap<String, (Vec<u8>, Instant)>>>,
+        cache: &Arc<RwLock<CacheMap>>,
     ) {
         let start_time = Instant::now();
         let mut freed_bytes = 0;
@@ -196,6 +208,11 @@ pub async fn force_gc() {
         warn!("Memory manager not initialized");
     }
 }
+pub async fn shutdown_memory_manager() {
+    if let Some(manager) = get_memory_manager().await {
+        manager.shutdown().await;
+    }
+}
 pub async fn get_memory_stats() -> Option<MemoryStats> {
     if let Some(manager) = get_memory_manager().await {
         Some(manager.get_stats().await)
@@ -2,6 +2,10 @@
 
 pub mod response;
 pub mod memory;
+pub mod dependencies;
+pub mod request_id;
+pub mod shutdown;
+pub mod slot;
 
 pub use response::*;
 pub use memory::*;
\ No newline at end of file
@@ -0,0 +1,56 @@
+//! Request IDs shared by the REST and gRPC layers
+//!
+//! A request keeps the `x-request-id` its caller sent, if the value is safe to log and echo.
+//! Otherwise it gets the trace ID, so the two line up, or a random UUID when there is no trace.
+//! The ID is available to everything running on the request's task through `current_request_id`.
+
+use std::future::Future;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Uses `incoming` when it is 1 to 128 characters of `[A-Za-z0-9._:-]`, else mints a new ID.
+pub fn accept_or_generate(incoming: Option<&str>) -> String {
+    match incoming {
+        Some(id) if is_valid(id) => id.to_string(),
+        _ => crate::telemetry::current_trace_id().unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+    }
+}
+
+fn is_valid(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b':'))
+}
+
+/// Runs `future` with `id` as the current request ID.
+pub async fn scope<F: Future>(id: String, future: F) -> F::Output {
+    REQUEST_ID.scope(id, future).await
+}
+
+/// The ID of the request being handled on this task, falling back to the trace ID outside one.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok().or_else(crate::telemetry::current_trace_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_request_id_scope() {
+        assert_eq!(accept_or_generate(Some("abc-123")), "abc-123");
+        let generated = accept_or_generate(Some("bad id\r\nx-injected: 1"));
+        assert!(uuid::Uuid::parse_str(&generated).is_ok());
+        assert!(!is_valid(&"a".repeat(129)));
+
+        assert_eq!(current_request_id(), None);
+        let inner = scope("abc-123".to_string(), async { current_request_id() }).await;
+        assert_eq!(inner.as_deref(), Some("abc-123"));
+    }
+}
@@ -5,6 +5,7 @@ use std::collections::HashMap;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use axum::Json;
+use crate::common::request_id::current_request_id;
 
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,7 +36,7 @@ impl<T> ApiResponse<T> {
             error: None,
             message: message.into(),
             timestamp: chrono::Utc::now(),
-            request_id: None,
+            request_id: current_request_id(),
         }
     }
     pub fn success_with_id(data: T, message: impl Into<String>, request_id: String) -> Self {
@@ -63,7 +64,7 @@ impl ApiResponse<()> {
             }),
             message: message_str,
             timestamp: chrono::Utc::now(),
-            request_id: None,
+            request_id: current_request_id(),
         }
     }
 
@@ -83,7 +84,7 @@ impl ApiResponse<()> {
             }),
             message: message_str,
             timestamp: chrono::Utc::now(),
-            request_id: None,
+            request_id: current_request_id(),
         }
     }
 
@@ -123,6 +124,7 @@ where
                 Some("UNAUTHORIZED") => StatusCode::UNAUTHORIZED,
                 Some("FORBIDDEN") => StatusCode::FORBIDDEN,
                 Some("CONFLICT") => StatusCode::CONFLICT,
+                Some("SERVICE_UNAVAILABLE") => StatusCode::SERVICE_UNAVAILABLE,
                 Some("INTERNAL_ERROR") => StatusCode::INTERNAL_SERVER_ERROR,
                 _ => StatusCode::INTERNAL_SERVER_ERROR,
             }
@@ -143,6 +145,7 @@ pub mod error_codes {
     pub const DATABASE_ERROR: &str = "DATABASE_ERROR";
     pub const CLOUD_SERVICE_ERROR: &str = "CLOUD_SERVICE_ERROR";
     pub const NETWORK_ERROR: &str = "NETWORK_ERROR";
+    pub const SERVICE_UNAVAILABLE: &str = "SERVICE_UNAVAILABLE";
 }
 #[macro_export]
 macro_rules! success_response {
@@ -0,0 +1,91 @@
+//! Coordinated shutdown
+//!
+//! One `Shutdown` is shared through `AppState`. Triggering it makes readiness fail at once, so
+//! load balancers stop routing here while the servers are still draining, and tells background
+//! jobs to stop after the pass they are running.
+
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+#[derive(Clone, Debug)]
+pub struct Shutdown {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self { sender: Arc::new(watch::channel(false).0) }
+    }
+
+    /// Starts the shutdown; later calls do nothing.
+    pub fn trigger(&self) {
+        self.sender.send_if_modified(|triggered| !std::mem::replace(triggered, true));
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    /// Resolves once `trigger` has been called, immediately if it already has.
+    pub async fn triggered(&self) {
+        let mut receiver = self.sender.subscribe();
+        // The sender lives in `self`, so the channel cannot close while this waits.
+        let _ = receiver.wait_for(|triggered| *triggered).await;
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Resolves on the first SIGINT (Ctrl-C) or, on Unix, SIGTERM, returning the signal's name.
+pub async fn wait_for_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => tokio::select! {
+                _ = tokio::signal::ctrl_c() => "SIGINT",
+                _ = sigterm.recv() => "SIGTERM",
+            },
+            Err(e) => {
+                tracing::warn!("Cannot listen for SIGTERM, only SIGINT stops the server: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+                "SIGINT"
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        "SIGINT"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_trigger_wakes_waiters() {
+        let shutdown = Shutdown::new();
+        assert!(!shutdown.is_triggered());
+        let waiter = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move { shutdown.triggered().await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!waiter.is_finished());
+
+        shutdown.trigger();
+        shutdown.trigger();
+        assert!(shutdown.is_triggered());
+        tokio::time::timeout(Duration::from_secs(1), waiter).await.unwrap().unwrap();
+        // Waiting after the fact returns straight away.
+        tokio::time::timeout(Duration::from_secs(1), shutdown.triggered()).await.unwrap();
+    }
+}
@@ -0,0 +1,28 @@
+//! Shared values that can be swapped while the service runs
+
+use std::sync::{Arc, RwLock};
+
+/// A value shared by every clone of the slot. `replace` swaps it for all of them, e.g. to
+/// put in a client rebuilt with rotated credentials.
+#[derive(Debug, Default)]
+pub struct Slot<T>(Arc<RwLock<T>>);
+
+impl<T> Clone for Slot<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: Clone> Slot<T> {
+    pub fn new(value: T) -> Self {
+        Self(Arc::new(RwLock::new(value)))
+    }
+
+    pub fn current(&self) -> T {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn replace(&self, value: T) {
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = value;
+    }
+}
@@ -0,0 +1,288 @@
+//! Runtime configuration reload
+//!
+//! Only settings that are read per request or per job can change while serving. Settings
+//! used once at startup (ports, pool size, enabled services, job intervals) keep their
+//! running value and are reported so the operator knows a restart is needed. Secrets are
+//! re-read from the secret provider on every reload, so a rotated `jwt_secret` or URL
+//! signing key applies without a restart. New PostgreSQL connections use the reloaded URL,
+//! and the MongoDB and cloud clients are rebuilt by `crate::rebuild_clients`.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+use super::secrets::{resolve_secrets, with_exposed_secrets};
+use super::{config_file_bases, load_config_with_overrides, Config, SharedConfig};
+
+/// Keys that need a restart to take effect, as dotted paths. A prefix covers every key below it.
+pub const RESTART_ONLY_KEYS: &[&str] = &[
+    "server",
+    "tls",
+    "database.max_connections",
+    "database.run_migrations",
+    "cloud.enable_aws_services",
+    "cloud.enable_huawei_services",
+    "storage.backend",
+    "storage.bucket",
+    "storage.reconcile_interval_seconds",
+    "photo_policy.retention_interval_seconds",
+    "logging.format",
+    "logging.file",
+    "telemetry",
+    "health.grpc_check_interval_seconds",
+    "health.required_dependencies",
+    "health.reconnect_interval_seconds",
+];
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReloadOutcome {
+    /// Keys whose new value is now live.
+    pub applied: Vec<String>,
+    /// Keys that changed on disk since the last reload but keep their running value until restart.
+    pub restart_required: Vec<String>,
+}
+
+/// Takes the reloadable settings from `next` and everything else from `current`.
+pub fn merge_reloadable(current: &Config, next: &Config) -> Result<(Config, ReloadOutcome)> {
+    let current_value = with_exposed_secrets(|| serde_json::to_value(current)).context("Failed to serialize configuration")?;
+    let mut merged = with_exposed_secrets(|| serde_json::to_value(next)).context("Failed to serialize configuration")?;
+    let mut changed = Vec::new();
+    changed_keys(&current_value, &merged, String::new(), &mut changed);
+    let (restart_required, applied): (Vec<_>, Vec<_>) = changed.into_iter()
+        .partition(|key| is_restart_only(key));
+    for key in RESTART_ONLY_KEYS {
+        let pointer = format!("/{}", key.replace('.', "/"));
+        if let (Some(running), Some(slot)) = (current_value.pointer(&pointer), merged.pointer_mut(&pointer)) {
+            *slot = running.clone();
+        }
+    }
+    let merged = serde_json::from_value(merged).context("Failed to rebuild configuration")?;
+    Ok((merged, ReloadOutcome { applied, restart_required }))
+}
+
+/// Drops restart-only keys whose value on disk is unchanged since `last_loaded`, so a pending
+/// restart is reported once rather than on every reload.
+fn retain_new_restarts(outcome: &mut ReloadOutcome, last_loaded: &Config, next: &Config) -> Result<()> {
+    let last_value = with_exposed_secrets(|| serde_json::to_value(last_loaded)).context("Failed to serialize configuration")?;
+    let next_value = with_exposed_secrets(|| serde_json::to_value(next)).context("Failed to serialize configuration")?;
+    let mut changed = Vec::new();
+    changed_keys(&last_value, &next_value, String::new(), &mut changed);
+    outcome.restart_required.retain(|key| changed.contains(key));
+    Ok(())
+}
+
+fn is_restart_only(key: &str) -> bool {
+    RESTART_ONLY_KEYS.iter().any(|prefix| {
+        key == *prefix || key.strip_prefix(prefix).is_some_and(|rest| rest.starts_with('.'))
+    })
+}
+
+/// Dotted paths of the leaves that differ between `old` and `new`.
+fn changed_keys(old: &Value, new: &Value, path: String, out: &mut Vec<String>) {
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            let mut keys: Vec<&String> = old_map.keys().chain(new_map.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let child = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                changed_keys(
+                    old_map.get(key).unwrap_or(&Value::Null),
+                    new_map.get(key).unwrap_or(&Value::Null),
+                    child,
+                    out,
+                );
+            }
+        }
+        _ if old != new => out.push(path),
+        _ => {}
+    }
+}
+
+/// Existing config files with their modification times; any difference triggers a reload.
+fn config_file_fingerprint() -> Vec<(PathBuf, Option<SystemTime>)> {
+    let mut files = Vec::new();
+    for base in config_file_bases() {
+        let (Some(dir), Some(stem)) = (base.parent(), base.file_name()) else {
+            continue;
+        };
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.file_stem() == Some(stem) {
+                let modified = entry.metadata().and_then(|m| m.modified()).ok();
+                files.push((path, modified));
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Reloads `config` when a config file changes, the process receives SIGHUP or the
+/// secret refresh interval (`secrets.refresh_interval_seconds`) elapses.
+/// `overrides` are the command-line overrides, re-applied on every reload.
+/// `on_reload` runs after each successful swap, e.g. to update the log filter.
+pub fn spawn_config_reload_task(
+    config: SharedConfig,
+    overrides: Vec<(String, String)>,
+    mut on_reload: impl FnMut(&Config) + Send + 'static,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        #[cfg(unix)]
+        let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+            Ok(signal) => Some(signal),
+            Err(e) => {
+                warn!("SIGHUP reload unavailable: {}", e);
+                None
+            }
+        };
+        let mut fingerprint = config_file_fingerprint();
+        let mut last_loaded = config.current();
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        let mut refresh_seconds = config.current().secrets.refresh_interval_seconds;
+        let mut secret_refresh = secret_refresh_interval(refresh_seconds);
+        loop {
+            #[cfg(unix)]
+            let trigger = tokio::select! {
+                _ = interval.tick() => None,
+                Some(()) = async { hangup.as_mut()?.recv().await } => Some("SIGHUP"),
+                Some(_) = async { Some(secret_refresh.as_mut()?.tick().await) } => Some("secret refresh"),
+            };
+            #[cfg(not(unix))]
+            let trigger = tokio::select! {
+                _ = interval.tick() => None,
+                Some(_) = async { Some(secret_refresh.as_mut()?.tick().await) } => Some("secret refresh"),
+            };
+            let latest = config_file_fingerprint();
+            let trigger = match trigger {
+                Some(trigger) => trigger,
+                None if latest != fingerprint => "config file change",
+                None => continue,
+            };
+            fingerprint = latest;
+            match reload(&config, &overrides, &mut last_loaded).await {
+                Ok(outcome) if trigger == "secret refresh" && outcome == ReloadOutcome::default() => {
+                    debug!("Secrets refreshed; nothing changed");
+                }
+                Ok(outcome) => {
+                    if !outcome.restart_required.is_empty() {
+                        warn!(
+                            "Configuration reloaded on {}, but these keys need a restart: {}",
+                            trigger,
+                            outcome.restart_required.join(", "),
+                        );
+                    }
+                    if outcome.applied.is_empty() {
+                        info!("Configuration reloaded on {}; no live settings changed", trigger);
+                    } else {
+                        info!("Configuration reloaded on {}; applied {}", trigger, outcome.applied.join(", "));
+                        let current = config.current();
+                        on_reload(&current);
+                        if current.secrets.refresh_interval_seconds != refresh_seconds {
+                            refresh_seconds = current.secrets.refresh_interval_seconds;
+                            secret_refresh = secret_refresh_interval(refresh_seconds);
+                        }
+                    }
+                }
+                Err(e) => error!("Configuration reload on {} failed, keeping the running settings: {:#}", trigger, e),
+            }
+        }
+    })
+}
+
+/// `None` when periodic secret refresh is disabled. The first tick is skipped since the
+/// secrets were just loaded.
+fn secret_refresh_interval(seconds: u64) -> Option<tokio::time::Interval> {
+    let period = Duration::from_secs(seconds);
+    if period.is_zero() {
+        return None;
+    }
+    Some(tokio::time::interval_at(tokio::time::Instant::now() + period, period))
+}
+
+async fn reload(config: &SharedConfig, overrides: &[(String, String)], last_loaded: &mut Arc<Config>) -> Result<ReloadOutcome> {
+    let mut next = load_config_with_overrides(overrides)?;
+    resolve_secrets(&mut next).await?;
+    let (merged, mut outcome) = merge_reloadable(&config.current(), &next)?;
+    retain_new_restarts(&mut outcome, last_loaded, &next)?;
+    if !outcome.applied.is_empty() {
+        config.replace(merged);
+    }
+    *last_loaded = Arc::new(next);
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_reloadable() {
+        let current = Config::default();
+        let mut next = Config::default();
+        next.logging.level = "debug".to_string();
+        next.cors.allowed_origins = vec!["https://app.example.com".to_string()];
+        next.server.rest_port = 9999;
+        next.database.postgres_url = "postgres://elsewhere/db".into();
+        next.database.max_connections = 50;
+        next.storage.backend = "s3".to_string();
+        next.storage.signed_url_ttl_seconds = 60;
+        next.jwt_secret = "rotated".into();
+
+        let (merged, outcome) = merge_reloadable(&current, &next).unwrap();
+        assert_eq!(merged.logging.level, "debug");
+        assert_eq!(merged.cors.allowed_origins, next.cors.allowed_origins);
+        assert_eq!(merged.storage.signed_url_ttl_seconds, 60);
+        assert_eq!(merged.jwt_secret.expose(), "rotated");
+        assert_eq!(merged.server.rest_port, current.server.rest_port);
+        assert_eq!(merged.database.postgres_url, next.database.postgres_url);
+        assert_eq!(merged.database.max_connections, current.database.max_connections);
+        assert_eq!(merged.storage.backend, current.storage.backend);
+        assert_eq!(outcome.applied, vec![
+            "cors.allowed_origins",
+            "database.postgres_url",
+            "jwt_secret",
+            "logging.level",
+            "storage.signed_url_ttl_seconds",
+        ]);
+        assert_eq!(outcome.restart_required, vec![
+            "database.max_connections",
+            "server.rest_port",
+            "storage.backend",
+        ]);
+    }
+
+    #[test]
+    fn test_pending_restart_is_reported_once() {
+        let running = Config::default();
+        let mut next = Config::default();
+        next.server.rest_port = 9999;
+
+        let (_, mut first) = merge_reloadable(&running, &next).unwrap();
+        retain_new_restarts(&mut first, &running, &next).unwrap();
+        assert_eq!(first.restart_required, vec!["server.rest_port"]);
+
+        let mut again = next.clone();
+        again.logging.level = "debug".to_string();
+        let (_, mut second) = merge_reloadable(&running, &again).unwrap();
+        retain_new_restarts(&mut second, &next, &again).unwrap();
+        assert!(second.restart_required.is_empty());
+        assert_eq!(second.applied, vec!["logging.level"]);
+
+        let mut moved = again.clone();
+        moved.server.rest_port = 9998;
+        let (_, mut third) = merge_reloadable(&running, &moved).unwrap();
+        retain_new_restarts(&mut third, &again, &moved).unwrap();
+        assert_eq!(third.restart_required, vec!["server.rest_port"]);
+    }
+}
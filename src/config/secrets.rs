@@ -0,0 +1,314 @@
+//! Secret values and the providers they are loaded from
+//!
+//! Secrets are addressed by provider-neutral names (`SECRET_NAMES`). The env provider reads
+//! the upper-cased name, e.g. `JWT_SECRET`, or the file named by `JWT_SECRET_FILE`. The file
+//! provider reads `{dir}/jwt_secret`, matching Docker and Kubernetes secret mounts. The Vault
+//! provider reads one KV v2 secret whose fields use these names.
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{Config, SecretsConfig};
+
+const REDACTED: &str = "[redacted]";
+
+thread_local! {
+    static EXPOSE_SECRETS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// A sensitive string. `Debug` and `Serialize` print `[redacted]`; use `expose` to read it.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        if EXPOSE_SECRETS.with(Cell::get) {
+            serializer.serialize_str(&self.0)
+        } else {
+            serializer.serialize_str(REDACTED)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self)
+    }
+}
+
+/// Runs `f` with `Secret` serializing its real value on this thread. Only for
+/// round-tripping the configuration internally; never for output.
+pub(crate) fn with_exposed_secrets<T>(f: impl FnOnce() -> T) -> T {
+    let previous = EXPOSE_SECRETS.with(|expose| expose.replace(true));
+    let result = f();
+    EXPOSE_SECRETS.with(|expose| expose.set(previous));
+    result
+}
+
+/// Reads `name`, or the contents of the file named by `{name}_FILE`, which wins when both are set.
+/// A single trailing newline, as left by most editors and `echo`, is dropped.
+pub fn env_or_file(name: &str) -> Result<Option<String>> {
+    if let Ok(path) = std::env::var(format!("{}_FILE", name)) {
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}_FILE ({})", name, path))?;
+        let contents = contents.strip_suffix('\n').unwrap_or(&contents);
+        return Ok(Some(contents.strip_suffix('\r').unwrap_or(contents).to_string()));
+    }
+    Ok(std::env::var(name).ok())
+}
+
+/// Names of the secrets a provider can supply.
+pub const SECRET_NAMES: &[&str] = &[
+    "jwt_secret",
+    "photo_url_signing_key",
+    "database_url",
+    "mongodb_url",
+    "aws_secret_access_key",
+    "huawei_secret_key",
+];
+
+fn assign(config: &mut Config, name: &str, secret: Secret) {
+    match name {
+        "jwt_secret" => config.jwt_secret = secret,
+        "photo_url_signing_key" => config.storage.url_signing_key = secret,
+        "database_url" => config.database.postgres_url = secret,
+        "mongodb_url" => config.database.mongodb_url = secret,
+        "aws_secret_access_key" => config.cloud.aws.secret_access_key = Some(secret),
+        "huawei_secret_key" => config.cloud.huawei.secret_key = secret,
+        _ => {}
+    }
+}
+
+#[tonic::async_trait]
+pub trait SecretProvider: Send + Sync {
+    /// Looks up one secret; `None` when the provider does not have it.
+    async fn get(&self, name: &str) -> Result<Option<Secret>>;
+
+    /// Looks up several secrets. Providers with a bulk API override this.
+    async fn get_many(&self, names: &[&str]) -> Result<HashMap<String, Secret>> {
+        let mut secrets = HashMap::new();
+        for name in names {
+            if let Some(secret) = self.get(name).await? {
+                secrets.insert(name.to_string(), secret);
+            }
+        }
+        Ok(secrets)
+    }
+}
+
+/// Environment variables named after the upper-cased secret name, with `_FILE` indirection.
+pub struct EnvSecretProvider;
+
+#[tonic::async_trait]
+impl SecretProvider for EnvSecretProvider {
+    async fn get(&self, name: &str) -> Result<Option<Secret>> {
+        Ok(env_or_file(&name.to_uppercase())?.map(Secret::from))
+    }
+}
+
+/// One file per secret in a directory, e.g. `/run/secrets/jwt_secret`.
+pub struct FileSecretProvider {
+    dir: PathBuf,
+}
+
+impl FileSecretProvider {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+#[tonic::async_trait]
+impl SecretProvider for FileSecretProvider {
+    async fn get(&self, name: &str) -> Result<Option<Secret>> {
+        let path = self.dir.join(name);
+        match tokio::fs::read_to_string(&path).await {
+            Ok(contents) => Ok(Some(Secret::new(contents.trim_end_matches(['\r', '\n'])))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read secret file {}", path.display())),
+        }
+    }
+}
+
+/// HashiCorp Vault KV version 2: every secret is a field of one Vault secret at `{mount}/data/{path}`.
+pub struct VaultSecretProvider {
+    address: String,
+    token: Secret,
+    mount: String,
+    path: String,
+    client: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct VaultKvResponse {
+    data: VaultKvData,
+}
+
+#[derive(Deserialize)]
+struct VaultKvData {
+    data: HashMap<String, serde_json::Value>,
+}
+
+impl VaultSecretProvider {
+    pub fn new(address: &str, token: Secret, mount: &str, path: &str, timeout: Duration) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .context("Failed to build Vault HTTP client")?;
+        Ok(Self {
+            address: address.trim_end_matches('/').to_string(),
+            token,
+            mount: mount.trim_matches('/').to_string(),
+            path: path.trim_matches('/').to_string(),
+            client,
+        })
+    }
+
+    async fn read_all(&self) -> Result<HashMap<String, Secret>> {
+        let url = format!("{}/v1/{}/data/{}", self.address, self.mount, self.path);
+        let response = self.client.get(&url)
+            .header("X-Vault-Token", self.token.expose())
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach Vault at {}", self.address))?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(HashMap::new());
+        }
+        let response = response.error_for_status()
+            .with_context(|| format!("Vault rejected the read of {}/{}", self.mount, self.path))?;
+        let body: VaultKvResponse = response.json().await.context("Unexpected Vault KV response")?;
+        Ok(body.data.data.into_iter()
+            .filter_map(|(name, value)| match value {
+                serde_json::Value::String(value) => Some((name, Secret::new(value))),
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+#[tonic::async_trait]
+impl SecretProvider for VaultSecretProvider {
+    async fn get(&self, name: &str) -> Result<Option<Secret>> {
+        Ok(self.read_all().await?.remove(name))
+    }
+
+    async fn get_many(&self, names: &[&str]) -> Result<HashMap<String, Secret>> {
+        let mut secrets = self.read_all().await?;
+        secrets.retain(|name, _| names.contains(&name.as_str()));
+        Ok(secrets)
+    }
+}
+
+pub fn provider_from_config(config: &SecretsConfig) -> Result<Box<dyn SecretProvider>> {
+    match config.provider.as_str() {
+        "env" => Ok(Box::new(EnvSecretProvider)),
+        "file" => Ok(Box::new(FileSecretProvider::new(&config.dir))),
+        "vault" => Ok(Box::new(VaultSecretProvider::new(
+            &config.vault.address,
+            config.vault.token.clone(),
+            &config.vault.mount,
+            &config.vault.path,
+            Duration::from_secs(10),
+        )?)),
+        other => Err(anyhow::anyhow!("Unknown secret provider: {}", other)),
+    }
+}
+
+/// Overwrites the secrets in `config` with whatever the configured provider has.
+/// Empty values are ignored so a half-populated store never blanks a working secret.
+pub async fn resolve_secrets(config: &mut Config) -> Result<()> {
+    let provider = provider_from_config(&config.secrets)?;
+    let secrets = provider.get_many(SECRET_NAMES).await
+        .with_context(|| format!("Failed to load secrets from the {} provider", config.secrets.provider))?;
+    for (name, secret) in secrets {
+        if !secret.is_empty() {
+            assign(config, &name, secret);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::Path, http::HeaderMap, routing::get, Json, Router};
+
+    #[test]
+    fn test_secret_is_redacted() {
+        let secret = Secret::new("hunter2");
+        assert_eq!(format!("{:?}", secret), REDACTED);
+        assert_eq!(serde_json::to_string(&secret).unwrap(), "\"[redacted]\"");
+        assert_eq!(with_exposed_secrets(|| serde_json::to_string(&secret).unwrap()), "\"hunter2\"");
+        assert_eq!(serde_json::from_str::<Secret>("\"hunter2\"").unwrap().expose(), "hunter2");
+    }
+
+    #[tokio::test]
+    async fn test_file_secret_provider() {
+        let dir = std::env::temp_dir().join(format!("secrets-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("jwt_secret"), "from-file\n").unwrap();
+        let provider = FileSecretProvider::new(&dir);
+        assert_eq!(provider.get("jwt_secret").await.unwrap(), Some(Secret::new("from-file")));
+        assert_eq!(provider.get("database_url").await.unwrap(), None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Runs against a stand-in for Vault's KV v2 HTTP API. For a real dev server:
+    /// `vault server -dev -dev-root-token-id=root` then `vault kv put secret/stander jwt_secret=...`.
+    #[tokio::test]
+    async fn test_vault_secret_provider() {
+        let app = Router::new().route("/v1/secret/data/*path", get(|Path(path): Path<String>, headers: HeaderMap| async move {
+            assert_eq!(path, "stander");
+            assert_eq!(headers["x-vault-token"], "root");
+            Json(serde_json::json!({
+                "data": { "data": { "jwt_secret": "from-vault", "unrelated": "x" }, "metadata": { "version": 3 } }
+            }))
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let provider = VaultSecretProvider::new(&address, Secret::new("root"), "secret", "stander", Duration::from_secs(5)).unwrap();
+        let secrets = provider.get_many(SECRET_NAMES).await.unwrap();
+        assert_eq!(secrets.len(), 1);
+        assert_eq!(secrets["jwt_secret"].expose(), "from-vault");
+        assert_eq!(provider.get("database_url").await.unwrap(), None);
+    }
+}
@@ -4,10 +4,19 @@ pub mod postgres;
 pub mod mongodb;
 
 use anyhow::Result;
+use tracing::info;
 
+/// Applies pending PostgreSQL migrations when `run_migrations` is set. MongoDB needs no
+/// setup; its connection is checked when the client is created.
 pub async fn initialize_databases(config: &crate::config::DatabaseConfig) -> Result<()> {
-    postgres::run_migrations(&config.postgres_url).await?;
-    mongodb::test_connection(&config.mongodb_url).await?;
+    if config.run_migrations {
+        let applied = postgres::run_migrations(config.postgres_url.expose()).await?;
+        if applied.is_empty() {
+            info!("PostgreSQL schema is up to date");
+        } else {
+            info!("Applied PostgreSQL migrations: {}", applied.join(", "));
+        }
+    }
 
     Ok(())
 }
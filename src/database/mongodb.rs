@@ -1,21 +1,30 @@
 //! MongoDB database connection and operations
 
 use mongodb::{Client, Database, Collection};
+use mongodb::event::command::{CommandEventHandler, CommandFailedEvent, CommandStartedEvent, CommandSucceededEvent};
 use anyhow::{Result, Context};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tracing::{debug, Span};
 
 pub type MongoClient = Client;
 
 
 pub async fn create_client(mongodb_url: &str) -> Result<MongoClient> {
-    let client_options = mongodb::options::ClientOptions::parse(mongodb_url)
-        .await
-        .context("Failed to parse MongoDB connection string")?;
-    let client = Client::with_options(client_options)
-        .context("Failed to create MongoDB client")?;
+    let client = create_lazy_client(mongodb_url).await?;
     test_connection_with_client(&client).await?;
     Ok(client)
 }
+/// Builds the client without contacting the server. The driver connects on first use and
+/// keeps reconnecting in the background, so the client stays usable after an outage.
+pub async fn create_lazy_client(mongodb_url: &str) -> Result<MongoClient> {
+    let mut client_options = mongodb::options::ClientOptions::parse(mongodb_url)
+        .await
+        .context("Failed to parse MongoDB connection string")?;
+    client_options.command_event_handler = Some(Arc::new(CommandTracer::default()));
+    Client::with_options(client_options).context("Failed to create MongoDB client")
+}
 pub async fn test_connection(mongodb_url: &str) -> Result<()> {
     let client = create_client(mongodb_url).await?;
     test_connection_with_client(&client).await
@@ -26,9 +35,53 @@ pub async fn test_connection_with_client(client: &MongoClient) -> Result<()> {
         .run_command(mongodb::bson::doc! {"ping": 1}, None)
         .await
         .context("Failed to ping MongoDB server")?;
-    println!("MongoDB connection test successful");
+    debug!("MongoDB connection test successful");
     Ok(())
 }
+/// Opens a client span per MongoDB command and records its latency. The driver reports commands
+/// from the calling task, so each span is a child of whatever span issued the operation.
+#[derive(Default)]
+struct CommandTracer {
+    /// In-flight commands by connection and request ID; dropping a span ends it.
+    spans: Mutex<HashMap<(u32, i32), Span>>,
+}
+
+impl CommandTracer {
+    fn finish(&self, connection: u32, request_id: i32) -> Option<Span> {
+        self.spans.lock().unwrap_or_else(|e| e.into_inner()).remove(&(connection, request_id))
+    }
+}
+
+impl CommandEventHandler for CommandTracer {
+    fn handle_command_started_event(&self, event: CommandStartedEvent) {
+        let span = tracing::info_span!(
+            "mongodb",
+            otel.name = %format!("{} {}", event.command_name, event.db),
+            otel.kind = "client",
+            otel.status_code = tracing::field::Empty,
+            db.system = "mongodb",
+            db.name = %event.db,
+            db.operation = %event.command_name,
+        );
+        self.spans.lock().unwrap_or_else(|e| e.into_inner())
+            .insert((event.connection.id, event.request_id), span);
+    }
+
+    fn handle_command_succeeded_event(&self, event: CommandSucceededEvent) {
+        crate::metrics::MONGODB_COMMAND_DURATION.with_label_values(&[&event.command_name, "success"])
+            .observe(event.duration.as_secs_f64());
+        self.finish(event.connection.id, event.request_id);
+    }
+
+    fn handle_command_failed_event(&self, event: CommandFailedEvent) {
+        crate::metrics::MONGODB_COMMAND_DURATION.with_label_values(&[&event.command_name, "failure"])
+            .observe(event.duration.as_secs_f64());
+        if let Some(span) = self.finish(event.connection.id, event.request_id) {
+            span.record("otel.status_code", "ERROR");
+        }
+    }
+}
+
 pub fn get_database(client: &MongoClient, db_name: &str) -> Database {
     client.database(db_name)
 }
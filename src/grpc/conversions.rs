@@ -4,6 +4,7 @@ use uuid::Uuid;
 use chrono::{DateTime, Utc};
 use crate::models::user::{User as ModelUser, UserPhoto as ModelUserPhoto};
 use crate::grpc::user_services::{User as ProtoUser, UserPhoto as ProtoUserPhoto};
+use crate::services::photo_service::PHOTO_STATUS_ACTIVE;
 
 
 impl From<ModelUser> for ProtoUser {
@@ -89,6 +90,8 @@ impl TryFrom<ProtoUserPhoto> for ModelUserPhoto {
             is_verified: proto_photo.is_verified,
             created_at,
             updated_at,
+            status: PHOTO_STATUS_ACTIVE.to_string(),
+            scan_verdict: None,
         })
     }
 }
@@ -0,0 +1,52 @@
+//! Service identities of mTLS callers
+
+use std::task::{Context, Poll};
+
+use tonic::codegen::http;
+use tonic::transport::server::{TcpConnectInfo, TlsConnectInfo};
+use tower::{Layer, Service};
+
+use crate::tls::{service_identity, ServiceIdentity};
+
+/// Reads the caller's identity from its verified client certificate, records it on the call
+/// span and adds it to the request extensions, where handlers find it with
+/// `request.extensions().get::<ServiceIdentity>()`. Calls without a certificate pass unchanged.
+#[derive(Clone, Default)]
+pub struct GrpcIdentityLayer;
+
+impl<S> Layer<S> for GrpcIdentityLayer {
+    type Service = GrpcIdentityMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcIdentityMiddleware { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct GrpcIdentityMiddleware<S> {
+    inner: S,
+}
+
+impl<S, ReqBody> Service<http::Request<ReqBody>> for GrpcIdentityMiddleware<S>
+where
+    S: Service<http::Request<ReqBody>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<ReqBody>) -> Self::Future {
+        let identity = request.extensions().get::<TlsConnectInfo<TcpConnectInfo>>()
+            .and_then(|info| info.peer_certs())
+            .and_then(|certs| certs.first().and_then(|cert| service_identity(cert.get_ref())));
+        if let Some(identity) = identity {
+            tracing::Span::current().record("client.identity", identity.0.as_str());
+            request.extensions_mut().insert::<ServiceIdentity>(identity);
+        }
+        self.inner.call(request)
+    }
+}
@@ -0,0 +1,209 @@
+//! gRPC call metrics
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use tonic::body::BoxBody;
+use tonic::codegen::{http, Body, Bytes};
+use tower::{Layer, Service};
+
+use crate::metrics::{GRPC_REQUESTS, GRPC_REQUEST_DURATION};
+
+/// Counts calls and records their latency per method. Calls rejected as `Unimplemented`, which
+/// includes every unknown method, share the `unknown` label so clients cannot create new series.
+///
+/// The status comes from the response headers when tonic answers with headers only, as it does
+/// for unary errors, and otherwise from the trailers at the end of the body, so a stream that
+/// fails midway is counted with its error code. A body dropped before its trailers, e.g. because
+/// the client went away, counts as `Cancelled`. Latency runs until the status is known.
+#[derive(Clone, Default)]
+pub struct GrpcMetricsLayer;
+
+impl<S> Layer<S> for GrpcMetricsLayer {
+    type Service = GrpcMetricsMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcMetricsMiddleware { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct GrpcMetricsMiddleware<S> {
+    inner: S,
+}
+
+impl<S, ReqBody> Service<http::Request<ReqBody>> for GrpcMetricsMiddleware<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<BoxBody>> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = std::pin::Pin<Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<ReqBody>) -> Self::Future {
+        let call = CallMetrics {
+            method: request.uri().path().to_string(),
+            start_time: Instant::now(),
+        };
+
+        let future = self.inner.call(request);
+
+        Box::pin(async move {
+            let response = future.await?;
+            match status_code(response.headers()) {
+                Some(code) => {
+                    call.record(code);
+                    Ok(response)
+                }
+                None => Ok(response.map(|inner| BoxBody::new(MetricsBody { inner, call: Some(call) }))),
+            }
+        })
+    }
+}
+
+fn status_code(headers: &http::HeaderMap) -> Option<tonic::Code> {
+    headers.get("grpc-status")
+        .and_then(|status| status.to_str().ok())
+        .and_then(|status| status.parse::<i32>().ok())
+        .map(tonic::Code::from_i32)
+}
+
+struct CallMetrics {
+    method: String,
+    start_time: Instant,
+}
+
+impl CallMetrics {
+    fn record(self, code: tonic::Code) {
+        let method = if code == tonic::Code::Unimplemented { "unknown" } else { self.method.as_str() };
+        GRPC_REQUEST_DURATION.with_label_values(&[method])
+            .observe(self.start_time.elapsed().as_secs_f64());
+        GRPC_REQUESTS.with_label_values(&[method, &format!("{:?}", code)]).inc();
+    }
+}
+
+/// Response body that records the call once the status arrives in the trailers.
+struct MetricsBody {
+    inner: BoxBody,
+    call: Option<CallMetrics>,
+}
+
+impl MetricsBody {
+    fn record(&mut self, code: tonic::Code) {
+        if let Some(call) = self.call.take() {
+            call.record(code);
+        }
+    }
+}
+
+impl Body for MetricsBody {
+    type Data = Bytes;
+    type Error = tonic::Status;
+
+    fn poll_data(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes, tonic::Status>>> {
+        let polled = Pin::new(&mut self.inner).poll_data(cx);
+        if let Poll::Ready(Some(Err(status))) = &polled {
+            self.record(status.code());
+        }
+        polled
+    }
+
+    fn poll_trailers(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<Option<http::HeaderMap>, tonic::Status>> {
+        let polled = Pin::new(&mut self.inner).poll_trailers(cx);
+        match &polled {
+            Poll::Ready(Ok(trailers)) => {
+                let code = trailers.as_ref().and_then(status_code).unwrap_or(tonic::Code::Unknown);
+                self.record(code);
+            }
+            Poll::Ready(Err(status)) => self.record(status.code()),
+            Poll::Pending => {}
+        }
+        polled
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+}
+
+impl Drop for MetricsBody {
+    fn drop(&mut self) {
+        self.record(tonic::Code::Cancelled);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+
+    /// A streaming response whose status arrives only in the trailers.
+    struct Streaming(Option<tonic::Code>);
+
+    impl Body for Streaming {
+        type Data = Bytes;
+        type Error = tonic::Status;
+
+        fn poll_data(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Result<Bytes, tonic::Status>>> {
+            Poll::Ready(None)
+        }
+
+        fn poll_trailers(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<Option<http::HeaderMap>, tonic::Status>> {
+            let mut trailers = http::HeaderMap::new();
+            if let Some(code) = self.0 {
+                trailers.insert("grpc-status", http::HeaderValue::from(code as i32));
+            }
+            Poll::Ready(Ok(Some(trailers)))
+        }
+    }
+
+    #[derive(Clone)]
+    struct StreamStub(Option<tonic::Code>);
+
+    impl Service<http::Request<()>> for StreamStub {
+        type Response = http::Response<BoxBody>;
+        type Error = Infallible;
+        type Future = std::future::Ready<Result<Self::Response, Infallible>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _request: http::Request<()>) -> Self::Future {
+            std::future::ready(Ok(http::Response::new(BoxBody::new(Streaming(self.0)))))
+        }
+    }
+
+    async fn call(method: &str, code: Option<tonic::Code>) -> BoxBody {
+        let mut service = GrpcMetricsLayer.layer(StreamStub(code));
+        let request = http::Request::post(method).body(()).unwrap();
+        service.call(request).await.unwrap().into_body()
+    }
+
+    fn count(method: &str, code: &str) -> u64 {
+        GRPC_REQUESTS.with_label_values(&[method, code]).get()
+    }
+
+    #[tokio::test]
+    async fn test_stream_status_comes_from_trailers() {
+        let method = "/test.Photos/FailingStream";
+        let mut body = call(method, Some(tonic::Code::Internal)).await;
+        assert_eq!(count(method, "Internal"), 0, "recorded before the stream ended");
+        assert!(body.data().await.is_none());
+        body.trailers().await.unwrap();
+        assert_eq!(count(method, "Internal"), 1);
+        assert_eq!(count(method, "Ok"), 0);
+        drop(body);
+        assert_eq!(count(method, "Cancelled"), 0);
+
+        let method = "/test.Photos/AbandonedStream";
+        drop(call(method, Some(tonic::Code::Ok)).await);
+        assert_eq!(count(method, "Cancelled"), 1);
+    }
+}
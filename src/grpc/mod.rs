@@ -3,28 +3,99 @@
 use anyhow::Result;
 use tracing::info;
 use std::net::SocketAddr;
+use tonic::server::NamedService;
+use tonic::transport::server::Routes;
 use tonic::transport::Server;
 
+use crate::tls::{ClientAuth, ReloadingServerConfig};
+
 
 pub mod services;
 pub mod user_services;
 pub mod conversions;
+pub mod metrics;
+pub mod request_id;
+pub mod identity;
 
 use services::UserServiceImpl;
 use user_services::user_service_server::UserServiceServer;
 
+/// Serves until `shutdown` resolves, then stops accepting connections and returns once every
+/// in-flight call has completed. Serves over TLS, optionally verifying client certificates,
+/// when `tls.enabled` is set.
 pub async fn start_grpc_server(
     addr: SocketAddr,
     app_state: crate::AppState,
+    shutdown: impl std::future::Future<Output = ()> + Send + 'static,
 ) -> Result<()> {
     info!("Starting gRPC server on {}", addr);
-    let user_service = UserServiceImpl::new(app_state.clone());
-    let server = Server::builder()
-        .add_service(UserServiceServer::new(user_service))
-        .serve(addr);
+    let tls_settings = app_state.config.current().tls.clone();
+    let tls = if tls_settings.enabled {
+        let tls = ReloadingServerConfig::load(&tls_settings, ClientAuth::for_grpc(&tls_settings), crate::tls::GRPC_ALPN)?;
+        // Each handshake reads the current configuration, so reloading needs no callback.
+        tls.spawn_reload_task("gRPC", app_state.shutdown.clone(), |_| {});
+        Some(tls)
+    } else {
+        None
+    };
+    let router = Server::builder()
+        .trace_fn(request_span)
+        .layer(tower::layer::layer_fn(with_middleware))
+        .add_routes(create_grpc_routes(app_state));
 
-    info!("gRPC server listening on {}", addr);
-    server.await?;
+    match tls {
+        Some(tls) => {
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            info!(
+                "gRPC server listening on {} with TLS certificate {} (client certificates: {:?})",
+                addr, tls_settings.cert_file, ClientAuth::for_grpc(&tls_settings),
+            );
+            router.serve_with_incoming_shutdown(crate::tls::incoming(listener, tls), shutdown).await?;
+        }
+        None => {
+            info!("gRPC server listening on {}", addr);
+            router.serve_with_shutdown(addr, shutdown).await?;
+        }
+    }
+    info!("gRPC server drained");
 
     Ok(())
 }
+
+/// The gRPC services, plus the task that reports their health status until shutdown.
+pub fn create_grpc_routes(app_state: crate::AppState) -> Routes {
+    let user_service = UserServiceImpl::new(app_state.clone());
+    // Standard grpc.health.v1.Health, following the same readiness report as REST.
+    let (health_reporter, health_service) = tonic_health::server::health_reporter();
+    crate::services::health_service::spawn_grpc_health_task(
+        app_state,
+        health_reporter,
+        vec![UserServiceServer::<UserServiceImpl>::NAME],
+    );
+    Routes::new(health_service).add_service(UserServiceServer::new(user_service))
+}
+
+/// Wraps the gRPC services in the middleware every listener applies, outermost first.
+pub fn with_middleware<S>(
+    routes: S,
+) -> request_id::GrpcRequestIdMiddleware<identity::GrpcIdentityMiddleware<metrics::GrpcMetricsMiddleware<S>>> {
+    use tower::Layer;
+    request_id::GrpcRequestIdLayer.layer(identity::GrpcIdentityLayer.layer(metrics::GrpcMetricsLayer.layer(routes)))
+}
+
+/// Span for one gRPC call, continuing the caller's trace when it sent `traceparent`.
+pub(crate) fn request_span<B>(request: &tonic::codegen::http::Request<B>) -> tracing::Span {
+    let path = request.uri().path();
+    let span = tracing::info_span!(
+        "gRPC request",
+        otel.name = %path.trim_start_matches('/'),
+        otel.kind = "server",
+        rpc.system = "grpc",
+        rpc.method = %path,
+        request_id = tracing::field::Empty,
+        client.identity = tracing::field::Empty,
+    );
+    let header = |name: &str| request.headers().get(name).and_then(|value| value.to_str().ok());
+    crate::telemetry::set_remote_parent(&span, header("traceparent"), header("tracestate"));
+    span
+}
@@ -0,0 +1,60 @@
+//! Request IDs for gRPC calls
+
+use std::task::{Context, Poll};
+
+use tonic::codegen::http;
+use tower::{Layer, Service};
+
+use crate::common::request_id::{self, REQUEST_ID_HEADER};
+
+/// The gRPC counterpart of the REST `RequestIdLayer`: accepts or generates `x-request-id`
+/// metadata, records it on the call span and returns it in the response metadata.
+#[derive(Clone, Default)]
+pub struct GrpcRequestIdLayer;
+
+impl<S> Layer<S> for GrpcRequestIdLayer {
+    type Service = GrpcRequestIdMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcRequestIdMiddleware { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct GrpcRequestIdMiddleware<S> {
+    inner: S,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for GrpcRequestIdMiddleware<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = std::pin::Pin<Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<ReqBody>) -> Self::Future {
+        let incoming = request.headers().get(REQUEST_ID_HEADER).and_then(|value| value.to_str().ok());
+        let id = request_id::accept_or_generate(incoming);
+        tracing::Span::current().record("request_id", id.as_str());
+        // Handlers read it from the request metadata like any other header.
+        if let Ok(value) = http::HeaderValue::from_str(&id) {
+            request.headers_mut().insert(REQUEST_ID_HEADER, value);
+        }
+
+        let future = request_id::scope(id.clone(), self.inner.call(request));
+
+        Box::pin(async move {
+            let mut response = future.await?;
+            if let Ok(value) = http::HeaderValue::from_str(&id) {
+                response.headers_mut().insert(REQUEST_ID_HEADER, value);
+            }
+            Ok(response)
+        })
+    }
+}
@@ -5,7 +5,11 @@ use crate::AppState;
 use crate::grpc::user_services::*;
 use crate::grpc::user_services::user_service_server::UserService;
 use crate::services::{UserService as BusinessUserService, AuthService, PhotoService};
-use crate::models::user::{CreateUser, LoginRequest as ModelLoginRequest};
+use crate::services::photo_service::{rejection_cause, PhotoStreamUpload, PhotoUrlScope};
+use futures::StreamExt;
+use crate::models::user::{CreateUser, LoginRequest as ModelLoginRequest, UserListFilter, UserSortField};
+use crate::models::common::SortOrder;
+use crate::utils::pagination::{offset_for_page, PageCursor, PagePosition};
 use uuid::Uuid;
 
 
@@ -195,7 +199,7 @@ impl UserService for UserServiceImpl {
 
     async fn refresh_user_token(
         &self,
-        request: Request<RefreshTokenRequest>,
+        _request: Request<RefreshTokenRequest>,
     ) -> Result<Response<AuthResponse>, Status> {
         // TODO: Implement token refresh logic
         let response = AuthResponse {
@@ -271,7 +275,7 @@ impl UserService for UserServiceImpl {
 
     async fn update_user_data(
         &self,
-        request: Request<UpdateUserRequest>,
+        _request: Request<UpdateUserRequest>,
     ) -> Result<Response<UserResponse>, Status> {
         // TODO: Implement update user data logic
         let response = UserResponse {
@@ -287,7 +291,7 @@ impl UserService for UserServiceImpl {
 
     async fn delete_user_data(
         &self,
-        request: Request<DeleteUserRequest>,
+        _request: Request<DeleteUserRequest>,
     ) -> Result<Response<StandardResponse>, Status> {
         // TODO: Implement delete user data logic
         let response = StandardResponse {
@@ -302,20 +306,98 @@ impl UserService for UserServiceImpl {
         &self,
         request: Request<ListUsersRequest>,
     ) -> Result<Response<UsersListResponse>, Status> {
-        // TODO: Implement list users data logic
-        let response = UsersListResponse {
-            response: Some(StandardResponse {
-                status_code: 501,
-                message: "Not implemented yet".to_string(),
-                data: None,
-            }),
-            users: vec![],
-            total: 0,
-            page: 0,
-            limit: 0,
-            role: String::new(),
+        let req = request.into_inner();
+        let error_response = |status_code: i32, message: String| {
+            Response::new(UsersListResponse {
+                response: Some(StandardResponse {
+                    status_code,
+                    message,
+                    data: None,
+                }),
+                users: vec![],
+                total: 0,
+                page: 0,
+                limit: 0,
+                role: String::new(),
+                next_cursor: String::new(),
+                prev_cursor: String::new(),
+            })
         };
-        Ok(Response::new(response))
+        let auth_service = AuthService::new(self.app_state.clone());
+        let user = match auth_service.verify_token(&req.token).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return Ok(error_response(401, "Invalid or expired token".to_string())),
+            Err(_) => return Ok(error_response(500, "Token verification failed".to_string())),
+        };
+        if user.role != "admin" {
+            return Ok(error_response(403, "Access denied".to_string()));
+        }
+        let sort_by = match req.sort_by.as_str() {
+            "" => UserSortField::default(),
+            value => match UserSortField::parse(value) {
+                Some(sort_by) => sort_by,
+                None => return Ok(error_response(400, format!("Unsupported sort field: {}", value))),
+            },
+        };
+        let sort_order = match req.sort_order.as_str() {
+            "" => SortOrder::Descending,
+            value => match SortOrder::parse(value) {
+                Some(sort_order) => sort_order,
+                None => return Ok(error_response(400, format!("Unsupported sort order: {}", value))),
+            },
+        };
+        let timestamp = |seconds: i64| (seconds != 0)
+            .then(|| chrono::DateTime::from_timestamp(seconds, 0))
+            .flatten();
+        let filter = UserListFilter {
+            role: Some(req.role.clone()).filter(|role| !role.is_empty()),
+            is_active: req.is_active,
+            email_verified: req.email_verified,
+            phone_verified: req.phone_verified,
+            created_after: timestamp(req.created_after),
+            created_before: timestamp(req.created_before),
+            search: Some(req.search).filter(|search| !search.is_empty()),
+        };
+        let page = req.page.max(1) as u32;
+        let limit = if req.limit <= 0 { 10 } else { req.limit.min(100) as u32 };
+        let position = if req.cursor.is_empty() {
+            match offset_for_page(page, limit) {
+                Some(offset) => PagePosition::Offset(offset),
+                None => return Ok(error_response(400, format!("Page {} is out of range", page))),
+            }
+        } else {
+            if sort_by != UserSortField::CreatedAt {
+                return Ok(error_response(400, "Cursor pagination requires sorting by created_at".to_string()));
+            }
+            match PageCursor::decode(&req.cursor) {
+                Ok(cursor) => PagePosition::Cursor(cursor),
+                Err(e) => return Ok(error_response(400, e.to_string())),
+            }
+        };
+        let include_photos = req.include.as_deref()
+            .map(|include| include.split(',').any(|relation| relation.trim() == "photos"))
+            .unwrap_or(true);
+        let user_service = BusinessUserService::new(self.app_state.clone());
+        match user_service.list_users(&filter, sort_by, sort_order, limit, &position, include_photos).await {
+            Ok(result) => {
+                let response = UsersListResponse {
+                    response: Some(StandardResponse {
+                        status_code: 200,
+                        message: "Users retrieved successfully".to_string(),
+                        data: None,
+                    }),
+                    users: result.users.into_iter().map(Into::into).collect(),
+                    total: result.total as i32,
+                    page: page as i32,
+                    limit: limit as i32,
+                    role: req.role,
+                    next_cursor: result.next_cursor.unwrap_or_default(),
+                    prev_cursor: result.prev_cursor.unwrap_or_default(),
+                };
+                Ok(Response::new(response))
+            }
+            Err(e) => Ok(error_response(500, format!("Failed to list users: {}", e))),
+        }
     }
 
     async fn upload_user_data(
@@ -395,7 +477,7 @@ impl UserService for UserServiceImpl {
             Err(e) => {
                 let response = PhotoResponse {
                     response: Some(StandardResponse {
-                        status_code: 500,
+                        status_code: failure_status(&e),
                         message: format!("Failed to upload photo: {}", e),
                         data: None,
                     }),
@@ -406,11 +488,127 @@ impl UserService for UserServiceImpl {
         }
     }
 
+    async fn upload_photo_stream(
+        &self,
+        request: Request<tonic::Streaming<UploadPhotoChunk>>,
+    ) -> Result<Response<PhotoResponse>, Status> {
+        let error_response = |status_code: i32, message: String| {
+            Response::new(PhotoResponse {
+                response: Some(StandardResponse {
+                    status_code,
+                    message,
+                    data: None,
+                }),
+                photo: None,
+            })
+        };
+        let mut stream = request.into_inner();
+        let metadata = match stream.message().await? {
+            Some(UploadPhotoChunk { payload: Some(upload_photo_chunk::Payload::Metadata(metadata)) }) => metadata,
+            _ => return Ok(error_response(400, "First message must carry upload metadata".to_string())),
+        };
+        let auth_service = AuthService::new(self.app_state.clone());
+        let user = match auth_service.verify_token(&metadata.token).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return Ok(error_response(401, "Invalid or expired token".to_string())),
+            Err(_) => return Ok(error_response(500, "Token verification failed".to_string())),
+        };
+        let user_id = match Uuid::parse_str(&metadata.user_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(error_response(400, "Invalid user ID format".to_string())),
+        };
+        if user.id != user_id && user.role != "admin" {
+            return Ok(error_response(403, "Access denied".to_string()));
+        }
+        let upload = PhotoStreamUpload {
+            user_id,
+            photo_type: metadata.photo_type,
+            file_extension: metadata.file_extension,
+            total_size: metadata.total_size,
+            sha256: metadata.sha256,
+        };
+        let chunks = stream.map(|message| match message {
+            Ok(UploadPhotoChunk { payload: Some(upload_photo_chunk::Payload::Data(data)) }) => Ok(data),
+            Ok(_) => Err(anyhow::anyhow!("Unexpected message after upload metadata")),
+            Err(status) => Err(anyhow::anyhow!("Upload stream failed: {}", status.message())),
+        });
+        let photo_service = PhotoService::new(self.app_state.clone());
+        match photo_service.upload_photo_stream(upload, chunks).await {
+            Ok(user_photo) => Ok(Response::new(PhotoResponse {
+                response: Some(StandardResponse {
+                    status_code: 200,
+                    message: "Photo uploaded successfully".to_string(),
+                    data: None,
+                }),
+                photo: Some(user_photo.into()),
+            })),
+            Err(e) => Ok(error_response(failure_status(&e), format!("Failed to upload photo: {}", e))),
+        }
+    }
+
+    async fn get_photo_url(
+        &self,
+        request: Request<GetPhotoUrlRequest>,
+    ) -> Result<Response<PhotoUrlResponse>, Status> {
+        let req = request.into_inner();
+        let error_response = |status_code: i32, message: String| {
+            Response::new(PhotoUrlResponse {
+                response: Some(StandardResponse {
+                    status_code,
+                    message,
+                    data: None,
+                }),
+                url: String::new(),
+                expires_at: 0,
+            })
+        };
+        let auth_service = AuthService::new(self.app_state.clone());
+        let user = match auth_service.verify_token(&req.token).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return Ok(error_response(401, "Invalid or expired token".to_string())),
+            Err(_) => return Ok(error_response(500, "Token verification failed".to_string())),
+        };
+        let photo_id = match Uuid::parse_str(&req.photo_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(error_response(400, "Invalid photo ID format".to_string())),
+        };
+        let scope = match PhotoUrlScope::from_size(Some(req.size)) {
+            Ok(scope) => scope,
+            Err(e) => return Ok(error_response(400, e.to_string())),
+        };
+        let photo_service = PhotoService::new(self.app_state.clone());
+        let photo = match photo_service.get_photo(photo_id).await {
+            Ok(Some(photo)) => photo,
+            Ok(None) => return Ok(error_response(404, "Photo not found".to_string())),
+            Err(e) => return Ok(error_response(500, format!("Failed to get photo: {}", e))),
+        };
+        if photo.user_id != user.id && user.role != "admin" {
+            return Ok(error_response(403, "Access denied".to_string()));
+        }
+        let ttl = u64::try_from(req.expires_in).ok();
+        match photo_service.issue_photo_url(&photo, ttl, scope).await {
+            Ok(signed_url) => {
+                let response = PhotoUrlResponse {
+                    response: Some(StandardResponse {
+                        status_code: 200,
+                        message: "Photo URL issued successfully".to_string(),
+                        data: None,
+                    }),
+                    url: signed_url.url,
+                    expires_at: signed_url.expires_at.timestamp(),
+                };
+                Ok(Response::new(response))
+            }
+            Err(e) => Ok(error_response(failure_status(&e), format!("Failed to issue photo URL: {}", e))),
+        }
+    }
+
     async fn send_verification_code(
         &self,
-        request: Request<SendVerificationRequest>,
+        _request: Request<SendVerificationRequest>,
     ) -> Result<Response<StandardResponse>, Status> {
         // TODO: Implement send verification code logic
+        crate::metrics::VERIFICATION_CODE_SENDS.with_label_values(&["not_implemented"]).inc();
         let response = StandardResponse {
             status_code: 501,
             message: "Not implemented yet".to_string(),
@@ -420,7 +618,7 @@ impl UserService for UserServiceImpl {
     }
     async fn verify_code(
         &self,
-        request: Request<VerifyCodeRequest>,
+        _request: Request<VerifyCodeRequest>,
     ) -> Result<Response<StandardResponse>, Status> {
         // TODO: Implement verify code logic
         let response = StandardResponse {
@@ -431,3 +629,48 @@ impl UserService for UserServiceImpl {
         Ok(Response::new(response))
     }
 }
+
+/// `status_code` for a failed call: 503 when a backend the call needs is unavailable, 4xx when
+/// the client sent a photo that was refused.
+fn failure_status(error: &anyhow::Error) -> i32 {
+    if let Some(rejected) = rejection_cause(error) {
+        return rejected.status_code() as i32;
+    }
+    if crate::common::dependencies::unavailable_cause(error).is_some() { 503 } else { 500 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+
+    fn declared(data: &[u8]) -> PhotoStreamUpload {
+        use sha2::{Digest, Sha256};
+        PhotoStreamUpload {
+            user_id: Uuid::new_v4(),
+            photo_type: "profile".to_string(),
+            file_extension: "png".to_string(),
+            total_size: data.len() as u64,
+            sha256: format!("{:x}", Sha256::digest(data)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mismatched_stream_is_a_client_error() {
+        let photo_service = PhotoService::new(crate::offline_app_state().await);
+        let tampered = photo_service.upload_photo_stream(
+            declared(b"original"),
+            stream::iter(vec![Ok(b"tampered".to_vec())]),
+        ).await.unwrap_err();
+        assert_eq!(failure_status(&tampered), 400);
+        let truncated = photo_service.upload_photo_stream(
+            declared(b"original"),
+            stream::iter(vec![Ok(b"orig".to_vec())]),
+        ).await.unwrap_err();
+        assert_eq!(failure_status(&truncated), 400);
+        let mut malformed = declared(b"original");
+        malformed.sha256 = "not-a-digest".to_string();
+        let malformed = photo_service.upload_photo_stream(malformed, stream::iter(Vec::new())).await.unwrap_err();
+        assert_eq!(failure_status(&malformed), 400);
+    }
+}
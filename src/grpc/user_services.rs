@@ -151,6 +151,42 @@ pub struct UploadPhotoRequest {
     #[prost(string, tag = "5")]
     pub token: ::prost::alloc::string::String,
 }
+/// First message of an UploadPhotoStream call.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UploadPhotoMetadata {
+    #[prost(string, tag = "1")]
+    pub user_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub photo_type: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub file_extension: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "4")]
+    pub total_size: u64,
+    /// hex-encoded digest of the whole file
+    #[prost(string, tag = "5")]
+    pub sha256: ::prost::alloc::string::String,
+    #[prost(string, tag = "6")]
+    pub token: ::prost::alloc::string::String,
+}
+/// Keep data chunks well under the 4 MB default gRPC message limit (64 KB-1 MB works well).
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UploadPhotoChunk {
+    #[prost(oneof = "upload_photo_chunk::Payload", tags = "1, 2")]
+    pub payload: ::core::option::Option<upload_photo_chunk::Payload>,
+}
+/// Nested message and enum types in `UploadPhotoChunk`.
+pub mod upload_photo_chunk {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Payload {
+        #[prost(message, tag = "1")]
+        Metadata(super::UploadPhotoMetadata),
+        #[prost(bytes, tag = "2")]
+        Data(::prost::alloc::vec::Vec<u8>),
+    }
+}
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct PhotoResponse {
@@ -161,6 +197,30 @@ pub struct PhotoResponse {
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetPhotoUrlRequest {
+    #[prost(string, tag = "1")]
+    pub photo_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub token: ::prost::alloc::string::String,
+    /// seconds, 0 uses the server default
+    #[prost(int64, tag = "3")]
+    pub expires_in: i64,
+    /// 0 for the original, otherwise max width/height of a derivative
+    #[prost(uint32, tag = "4")]
+    pub size: u32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PhotoUrlResponse {
+    #[prost(message, optional, tag = "1")]
+    pub response: ::core::option::Option<StandardResponse>,
+    #[prost(string, tag = "2")]
+    pub url: ::prost::alloc::string::String,
+    #[prost(int64, tag = "3")]
+    pub expires_at: i64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ValidateTokenResponse {
     #[prost(message, optional, tag = "1")]
     pub response: ::core::option::Option<StandardResponse>,
@@ -220,6 +280,32 @@ pub struct ListUsersRequest {
     pub limit: i32,
     #[prost(string, tag = "4")]
     pub role: ::prost::alloc::string::String,
+    #[prost(bool, optional, tag = "5")]
+    pub is_active: ::core::option::Option<bool>,
+    #[prost(bool, optional, tag = "6")]
+    pub email_verified: ::core::option::Option<bool>,
+    #[prost(bool, optional, tag = "7")]
+    pub phone_verified: ::core::option::Option<bool>,
+    /// Unix seconds; 0 leaves the bound open.
+    #[prost(int64, tag = "8")]
+    pub created_after: i64,
+    #[prost(int64, tag = "9")]
+    pub created_before: i64,
+    /// Matches first name, last name, email or phone.
+    #[prost(string, tag = "10")]
+    pub search: ::prost::alloc::string::String,
+    /// created_at (default), updated_at, email, first_name, last_name or role.
+    #[prost(string, tag = "11")]
+    pub sort_by: ::prost::alloc::string::String,
+    /// "asc" or "desc" (default).
+    #[prost(string, tag = "12")]
+    pub sort_order: ::prost::alloc::string::String,
+    /// next_cursor/prev_cursor from an earlier page; takes precedence over page.
+    #[prost(string, tag = "13")]
+    pub cursor: ::prost::alloc::string::String,
+    /// Comma-separated relations to embed; photos are embedded when unset.
+    #[prost(string, optional, tag = "14")]
+    pub include: ::core::option::Option<::prost::alloc::string::String>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -244,6 +330,10 @@ pub struct UsersListResponse {
     pub limit: i32,
     #[prost(string, tag = "6")]
     pub role: ::prost::alloc::string::String,
+    #[prost(string, tag = "7")]
+    pub next_cursor: ::prost::alloc::string::String,
+    #[prost(string, tag = "8")]
+    pub prev_cursor: ::prost::alloc::string::String,
 }
 /// Generated client implementations.
 pub mod user_service_client {
@@ -541,6 +631,55 @@ pub mod user_service_client {
                 .insert(GrpcMethod::new("user_services.UserService", "UploadUserData"));
             self.inner.unary(req, path, codec).await
         }
+        pub async fn upload_photo_stream(
+            &mut self,
+            request: impl tonic::IntoStreamingRequest<Message = super::UploadPhotoChunk>,
+        ) -> std::result::Result<tonic::Response<super::PhotoResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/user_services.UserService/UploadPhotoStream",
+            );
+            let mut req = request.into_streaming_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("user_services.UserService", "UploadPhotoStream"),
+                );
+            self.inner.client_streaming(req, path, codec).await
+        }
+        pub async fn get_photo_url(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetPhotoUrlRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::PhotoUrlResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/user_services.UserService/GetPhotoUrl",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("user_services.UserService", "GetPhotoUrl"));
+            self.inner.unary(req, path, codec).await
+        }
         pub async fn send_verification_code(
             &mut self,
             request: impl tonic::IntoRequest<super::SendVerificationRequest>,
@@ -647,6 +786,17 @@ pub mod user_service_server {
             &self,
             request: tonic::Request<super::UploadPhotoRequest>,
         ) -> std::result::Result<tonic::Response<super::PhotoResponse>, tonic::Status>;
+        async fn upload_photo_stream(
+            &self,
+            request: tonic::Request<tonic::Streaming<super::UploadPhotoChunk>>,
+        ) -> std::result::Result<tonic::Response<super::PhotoResponse>, tonic::Status>;
+        async fn get_photo_url(
+            &self,
+            request: tonic::Request<super::GetPhotoUrlRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::PhotoUrlResponse>,
+            tonic::Status,
+        >;
         async fn send_verification_code(
             &self,
             request: tonic::Request<super::SendVerificationRequest>,
@@ -1155,6 +1305,101 @@ pub mod user_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/user_services.UserService/UploadPhotoStream" => {
+                    #[allow(non_camel_case_types)]
+                    struct UploadPhotoStreamSvc<T: UserService>(pub Arc<T>);
+                    impl<
+                        T: UserService,
+                    > tonic::server::ClientStreamingService<super::UploadPhotoChunk>
+                    for UploadPhotoStreamSvc<T> {
+                        type Response = super::PhotoResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<
+                                tonic::Streaming<super::UploadPhotoChunk>,
+                            >,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as UserService>::upload_photo_stream(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = UploadPhotoStreamSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.client_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user_services.UserService/GetPhotoUrl" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetPhotoUrlSvc<T: UserService>(pub Arc<T>);
+                    impl<
+                        T: UserService,
+                    > tonic::server::UnaryService<super::GetPhotoUrlRequest>
+                    for GetPhotoUrlSvc<T> {
+                        type Response = super::PhotoUrlResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetPhotoUrlRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as UserService>::get_photo_url(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetPhotoUrlSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/user_services.UserService/SendVerificationCode" => {
                     #[allow(non_camel_case_types)]
                     struct SendVerificationCodeSvc<T: UserService>(pub Arc<T>);
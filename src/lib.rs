@@ -8,6 +8,8 @@
 
 pub mod config;
 pub mod database;
+pub mod logging;
+pub mod metrics;
 pub mod grpc;
 pub mod rest;
 pub mod cloud;
@@ -16,37 +18,75 @@ pub mod models;
 pub mod services;
 pub mod utils;
 pub mod schema;
+pub mod telemetry;
+pub mod tls;
+pub mod multiplex;
 
 use anyhow::Result;
-use tracing::{info, instrument};
+use common::dependencies::{AWS, HUAWEI, MONGODB};
+use tracing::{info, instrument, warn};
 
 
 #[derive(Clone, Debug)]
 pub struct AppState {
     pub postgres_pool: database::postgres::PgPool,
-    pub mongodb_client: database::mongodb::MongoClient,
-    pub aws_config: Option<cloud::aws::AwsConfig>,
-    pub huawei_config: Option<cloud::huawei::HuaweiConfig>,
-    pub config: config::Config,
+    /// Clients are swapped when a reload changes their settings; see `rebuild_clients`.
+    pub mongodb_client: common::slot::Slot<database::mongodb::MongoClient>,
+    pub aws_config: common::slot::Slot<Option<cloud::aws::AwsConfig>>,
+    pub huawei_config: common::slot::Slot<Option<cloud::huawei::HuaweiConfig>>,
+    /// Live configuration; take a snapshot with `config.current()`.
+    pub config: config::SharedConfig,
+    /// Optional dependencies that are currently unavailable.
+    pub dependencies: common::dependencies::Dependencies,
+    /// Latest readiness report, refreshed by `services::health_service::spawn_readiness_task`.
+    pub readiness: services::health_service::Readiness,
+    /// Triggered when the process starts shutting down.
+    pub shutdown: common::shutdown::Shutdown,
 }
 
 
-#[instrument]
-pub async fn initialize_app() -> Result<AppState> {
+#[instrument(skip(config))]
+pub async fn initialize_app(config: config::Config) -> Result<AppState> {
     info!("Initializing monolithic service...");
-    let config = config::load_config()?;
-    let postgres_pool = database::postgres::create_pool(&config.database.postgres_url).await?;
-    let mongodb_client = database::mongodb::create_client(&config.database.mongodb_url).await?;
+    database::initialize_databases(&config.database).await?;
+    let shared_config = config::SharedConfig::new(config.clone());
+    let postgres_pool = database::postgres::create_pool(shared_config.clone()).await?;
+    let dependencies = common::dependencies::Dependencies::default();
+    let mongodb_client = database::mongodb::create_lazy_client(config.database.mongodb_url.expose()).await?;
+    let ping = database::mongodb::test_connection_with_client(&mongodb_client);
+    let ping = if config.health.is_required(MONGODB) {
+        ping.await
+    } else {
+        // An optional MongoDB should not hold up startup for the driver's full server selection timeout.
+        let timeout = std::time::Duration::from_millis(config.health.probe_timeout_ms);
+        tokio::time::timeout(timeout, ping).await
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("No response from MongoDB within {}ms", timeout.as_millis())))
+    };
+    if let Err(e) = ping {
+        start_without(&config, &dependencies, MONGODB, e)?;
+    }
     let aws_config = if config.cloud.enable_aws_services {
         info!("Initializing AWS services...");
-        Some(cloud::aws::initialize_aws_config().await?)
+        match cloud::aws::initialize_aws_config(&config.cloud.aws).await {
+            Ok(aws_config) => Some(aws_config),
+            Err(e) => {
+                start_without(&config, &dependencies, AWS, e)?;
+                None
+            }
+        }
     } else {
         info!("AWS services disabled in configuration");
         None
     };
     let huawei_config = if config.cloud.enable_huawei_services {
         info!("Initializing Huawei Cloud services...");
-        Some(cloud::huawei::initialize_huawei_config().await?)
+        match cloud::huawei::initialize_huawei_config(&config.cloud.huawei).await {
+            Ok(huawei_config) => Some(huawei_config),
+            Err(e) => {
+                start_without(&config, &dependencies, HUAWEI, e)?;
+                None
+            }
+        }
     } else {
         info!("Huawei Cloud services disabled in configuration");
         None
@@ -54,9 +94,87 @@ pub async fn initialize_app() -> Result<AppState> {
     info!("Application initialized successfully");
     Ok(AppState {
         postgres_pool,
-        mongodb_client,
-        aws_config,
-        huawei_config,
-        config,
+        mongodb_client: common::slot::Slot::new(mongodb_client),
+        aws_config: common::slot::Slot::new(aws_config),
+        huawei_config: common::slot::Slot::new(huawei_config),
+        config: shared_config,
+        dependencies,
+        readiness: services::health_service::Readiness::default(),
+        shutdown: common::shutdown::Shutdown::new(),
     })
 }
+
+/// App state whose backends point at closed ports, for tests that must not reach a database.
+#[cfg(test)]
+pub(crate) async fn offline_app_state() -> AppState {
+    let mut offline = config::Config::default();
+    offline.database.postgres_url = "postgres://127.0.0.1:1/offline".into();
+    let config = config::SharedConfig::new(offline);
+    let manager = database::postgres::LiveConnectionManager::new(config.clone());
+    let postgres_pool = diesel::r2d2::Pool::builder().max_size(1).build_unchecked(manager);
+    let mongodb_client = database::mongodb::create_lazy_client("mongodb://127.0.0.1:1/?serverSelectionTimeoutMS=100")
+        .await
+        .expect("valid MongoDB URL");
+    AppState {
+        postgres_pool,
+        mongodb_client: common::slot::Slot::new(mongodb_client),
+        aws_config: common::slot::Slot::new(None),
+        huawei_config: common::slot::Slot::new(None),
+        config,
+        dependencies: common::dependencies::Dependencies::default(),
+        readiness: services::health_service::Readiness::default(),
+        shutdown: common::shutdown::Shutdown::new(),
+    }
+}
+
+/// Replaces the clients whose settings changed between `previous` and `current`, so rotated
+/// MongoDB and cloud credentials apply without a restart. PostgreSQL needs nothing here since
+/// its pool opens connections with the live URL. A client that cannot be rebuilt keeps its
+/// running settings.
+pub async fn rebuild_clients(app_state: &AppState, previous: &config::Config, current: &config::Config) {
+    if current.database.mongodb_url != previous.database.mongodb_url {
+        match database::mongodb::create_lazy_client(current.database.mongodb_url.expose()).await {
+            Ok(client) => {
+                app_state.mongodb_client.replace(client);
+                info!("MongoDB client rebuilt with the reloaded URL");
+            }
+            Err(e) => warn!("Keeping the running MongoDB client, the reloaded URL is unusable: {:#}", e),
+        }
+    }
+    if current.cloud.enable_aws_services && current.cloud.aws != previous.cloud.aws {
+        match cloud::aws::initialize_aws_config(&current.cloud.aws).await {
+            Ok(aws_config) => {
+                app_state.aws_config.replace(Some(aws_config));
+                app_state.dependencies.mark_available(AWS);
+                info!("AWS clients rebuilt with the reloaded settings");
+            }
+            Err(e) => warn!("Keeping the running AWS clients, the reloaded settings are unusable: {:#}", e),
+        }
+    }
+    if current.cloud.enable_huawei_services && current.cloud.huawei != previous.cloud.huawei {
+        match cloud::huawei::initialize_huawei_config(&current.cloud.huawei).await {
+            Ok(huawei_config) => {
+                app_state.huawei_config.replace(Some(huawei_config));
+                app_state.dependencies.mark_available(HUAWEI);
+                info!("Huawei Cloud client rebuilt with the reloaded settings");
+            }
+            Err(e) => warn!("Keeping the running Huawei Cloud client, the reloaded settings are unusable: {:#}", e),
+        }
+    }
+}
+
+/// Fails startup when `dependency` is required; otherwise records it as unavailable so that
+/// the service comes up without it.
+fn start_without(
+    config: &config::Config,
+    dependencies: &common::dependencies::Dependencies,
+    dependency: &'static str,
+    error: anyhow::Error,
+) -> Result<()> {
+    if config.health.is_required(dependency) {
+        return Err(error.context(format!("{} is a required dependency", dependency)));
+    }
+    warn!("Starting without {}, features that need it will answer 503: {:#}", dependency, error);
+    dependencies.mark_unavailable(dependency, format!("{:#}", error));
+    Ok(())
+}
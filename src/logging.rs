@@ -0,0 +1,323 @@
+//! Structured logging
+//!
+//! Builds the tracing subscriber from `LoggingConfig`: JSON or pretty output, `EnvFilter`
+//! directives such as `info,stander_monlothic_rust::services=debug`, and an optional rotating
+//! log file. Fields that carry personal data or credentials (`PII_FIELDS`) are masked by the
+//! formatters, so `info!(email = %user.email, "...")` never writes the full address. Spans and
+//! events are also handed to OpenTelemetry, whose exporter masks the same fields; see `telemetry`.
+
+use std::fmt;
+
+use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields, MakeWriter};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Layer, Registry};
+
+use crate::config::{LoggingConfig, TelemetryConfig};
+
+/// Swaps the active filter when `logging.level` is reloaded.
+pub type LogFilterHandle = reload::Handle<EnvFilter, Registry>;
+
+/// Field names whose values are masked, matched exactly or as a `_`-separated suffix
+/// (`user_email`, `refresh_token`).
+pub const PII_FIELDS: &[&str] = &["email", "phone", "phone_number", "token", "password", "authorization", "secret"];
+
+const REDACTED: &str = "[redacted]";
+
+/// Keeps the file writer flushing and flushes pending spans on drop; drop it only when the process exits.
+pub struct LoggingGuard {
+    _file: Option<WorkerGuard>,
+}
+
+impl Drop for LoggingGuard {
+    fn drop(&mut self) {
+        crate::telemetry::shutdown();
+    }
+}
+
+/// Installs the global subscriber. `RUST_LOG`, when set, takes precedence over `logging.level`.
+/// Must run inside the Tokio runtime, which exports the spans.
+pub fn init(config: &LoggingConfig, telemetry: &TelemetryConfig) -> Result<(LogFilterHandle, LoggingGuard)> {
+    let filter = match std::env::var("RUST_LOG") {
+        Ok(directives) => EnvFilter::try_new(&directives).with_context(|| format!("Invalid RUST_LOG {:?}", directives))?,
+        Err(_) => EnvFilter::try_new(&config.level).with_context(|| format!("Invalid logging.level {:?}", config.level))?,
+    };
+    let (filter, handle) = reload::Layer::new(filter);
+    let json = config.format == "json";
+    let tracer = crate::telemetry::init_tracer(telemetry)?;
+    let mut layers = vec![
+        tracing_opentelemetry::layer().with_tracer(tracer).boxed(),
+        format_layer(json, std::io::stdout, true),
+    ];
+    let mut file_guard = None;
+    if !config.file.directory.is_empty() {
+        let rotation = match config.file.rotation.as_str() {
+            "minutely" => Rotation::MINUTELY,
+            "hourly" => Rotation::HOURLY,
+            "daily" => Rotation::DAILY,
+            _ => Rotation::NEVER,
+        };
+        std::fs::create_dir_all(&config.file.directory)
+            .with_context(|| format!("Failed to create log directory {}", config.file.directory))?;
+        let appender = RollingFileAppender::builder()
+            .rotation(rotation)
+            .filename_prefix(&config.file.prefix)
+            .max_log_files(config.file.max_files.max(1))
+            .build(&config.file.directory)
+            .with_context(|| format!("Failed to open log directory {}", config.file.directory))?;
+        let (writer, guard) = tracing_appender::non_blocking(appender);
+        layers.push(format_layer(json, writer, false));
+        file_guard = Some(guard);
+    }
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(layers)
+        .try_init()
+        .context("Failed to install the tracing subscriber")?;
+    Ok((handle, LoggingGuard { _file: file_guard }))
+}
+
+/// Applies new filter directives; invalid ones keep the running filter.
+pub fn set_log_level(handle: &LogFilterHandle, level: &str) {
+    match EnvFilter::try_new(level) {
+        Ok(filter) => {
+            if let Err(e) = handle.reload(filter) {
+                tracing::warn!("Failed to apply log level {}: {}", level, e);
+            }
+        }
+        Err(e) => tracing::warn!("Ignoring invalid log level {:?}: {}", level, e),
+    }
+}
+
+type BoxedLayer = Box<dyn Layer<tracing_subscriber::layer::Layered<reload::Layer<EnvFilter, Registry>, Registry>> + Send + Sync>;
+
+fn format_layer<W>(json: bool, writer: W, ansi: bool) -> BoxedLayer
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    if json {
+        tracing_subscriber::fmt::layer()
+            .event_format(JsonFormat)
+            .fmt_fields(RedactingFields::Json)
+            .with_writer(writer)
+            .boxed()
+    } else {
+        tracing_subscriber::fmt::layer()
+            .pretty()
+            .fmt_fields(RedactingFields::Text)
+            .with_ansi(ansi)
+            .with_writer(writer)
+            .boxed()
+    }
+}
+
+pub fn is_pii_field(name: &str) -> bool {
+    PII_FIELDS.iter().any(|pii| {
+        name == *pii || name.strip_suffix(pii).is_some_and(|rest| rest.ends_with('_'))
+    })
+}
+
+/// Masks a PII value while keeping enough to correlate log lines: the first character and
+/// domain of an email, the last four digits of a phone number, nothing of a credential.
+pub fn mask(name: &str, value: &str) -> String {
+    if name.ends_with("email") {
+        if let Some((local, domain)) = value.split_once('@') {
+            let first: String = local.chars().take(1).collect();
+            return format!("{}***@{}", first, domain);
+        }
+    } else if name.ends_with("phone") || name.ends_with("phone_number") {
+        let digits: Vec<char> = value.chars().filter(char::is_ascii_digit).collect();
+        if digits.len() > 4 {
+            return format!("***{}", digits[digits.len() - 4..].iter().collect::<String>());
+        }
+    }
+    REDACTED.to_string()
+}
+
+/// Collects an event's or span's fields with PII masked.
+#[derive(Default)]
+struct RedactingVisitor {
+    fields: Map<String, Value>,
+}
+
+impl RedactingVisitor {
+    fn insert(&mut self, field: &Field, value: Value) {
+        let value = match value {
+            Value::String(value) if is_pii_field(field.name()) => Value::String(mask(field.name(), &value)),
+            value if is_pii_field(field.name()) => Value::String(mask(field.name(), &value.to_string())),
+            value => value,
+        };
+        self.fields.insert(field.name().to_string(), value);
+    }
+}
+
+impl Visit for RedactingVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, Value::from(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.insert(field, Value::from(format!("{:?}", value)));
+    }
+}
+
+/// Field formatter for events (pretty output) and span fields (both outputs).
+enum RedactingFields {
+    Text,
+    Json,
+}
+
+impl<'writer> FormatFields<'writer> for RedactingFields {
+    fn format_fields<R: tracing_subscriber::field::RecordFields>(&self, mut writer: Writer<'writer>, fields: R) -> fmt::Result {
+        let mut visitor = RedactingVisitor::default();
+        fields.record(&mut visitor);
+        match self {
+            RedactingFields::Json => write!(writer, "{}", Value::Object(visitor.fields)),
+            RedactingFields::Text => write_text_fields(&mut writer, visitor.fields),
+        }
+    }
+
+    fn add_fields(&self, current: &'writer mut FormattedFields<Self>, fields: &tracing::span::Record<'_>) -> fmt::Result {
+        let mut visitor = RedactingVisitor::default();
+        fields.record(&mut visitor);
+        match self {
+            RedactingFields::Json => {
+                let mut merged: Map<String, Value> = serde_json::from_str(&current.fields).unwrap_or_default();
+                merged.extend(visitor.fields);
+                current.fields = Value::Object(merged).to_string();
+                Ok(())
+            }
+            RedactingFields::Text => {
+                if !current.fields.is_empty() {
+                    current.fields.push(' ');
+                }
+                write_text_fields(&mut current.as_writer(), visitor.fields)
+            }
+        }
+    }
+}
+
+fn write_text_fields(writer: &mut Writer<'_>, mut fields: Map<String, Value>) -> fmt::Result {
+    let mut separator = "";
+    if let Some(Value::String(message)) = fields.remove("message") {
+        write!(writer, "{}", message)?;
+        separator = " ";
+    }
+    for (name, value) in fields {
+        match value {
+            Value::String(value) => write!(writer, "{}{}={}", separator, name, value)?,
+            value => write!(writer, "{}{}={}", separator, name, value)?,
+        }
+        separator = " ";
+    }
+    Ok(())
+}
+
+/// One JSON object per line: timestamp, level, target, fields and the enclosing spans.
+struct JsonFormat;
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        let mut visitor = RedactingVisitor::default();
+        event.record(&mut visitor);
+        let mut line = Map::new();
+        line.insert("timestamp".to_string(), Value::from(chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Micros, true)));
+        line.insert("level".to_string(), Value::from(event.metadata().level().as_str()));
+        line.insert("target".to_string(), Value::from(event.metadata().target()));
+        line.insert("fields".to_string(), Value::Object(visitor.fields));
+        if let Some(scope) = ctx.event_scope() {
+            let spans: Vec<Value> = scope.from_root().map(|span| {
+                let mut object: Map<String, Value> = span.extensions()
+                    .get::<FormattedFields<N>>()
+                    .and_then(|fields| serde_json::from_str(&fields.fields).ok())
+                    .unwrap_or_default();
+                object.insert("name".to_string(), Value::from(span.name()));
+                Value::Object(object)
+            }).collect();
+            line.insert("spans".to_string(), Value::Array(spans));
+        }
+        writeln!(writer, "{}", Value::Object(line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_mask() {
+        assert!(is_pii_field("email"));
+        assert!(is_pii_field("refresh_token"));
+        assert!(!is_pii_field("token_count_total") && !is_pii_field("photo_id"));
+        assert_eq!(mask("email", "jane.doe@example.com"), "j***@example.com");
+        assert_eq!(mask("phone", "+971 50 123 4567"), "***4567");
+        assert_eq!(mask("token", "eyJhbGciOi"), REDACTED);
+    }
+
+    #[test]
+    fn test_json_format_redacts_pii() {
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .event_format(JsonFormat)
+                .fmt_fields(RedactingFields::Json)
+                .with_writer(move || writer.clone()),
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("login", user_email = "jane.doe@example.com");
+            let _entered = span.enter();
+            tracing::info!(token = "eyJhbGciOi", attempts = 2, "Login succeeded");
+        });
+        let output = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+        let line: Value = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["fields"]["message"], "Login succeeded");
+        assert_eq!(line["fields"]["token"], REDACTED);
+        assert_eq!(line["fields"]["attempts"], 2);
+        assert_eq!(line["spans"][0]["name"], "login");
+        assert_eq!(line["spans"][0]["user_email"], "j***@example.com");
+        assert!(!output.contains("jane.doe") && !output.contains("eyJhbGciOi"));
+    }
+}
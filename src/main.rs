@@ -1,13 +1,39 @@
+mod cli;
+
 use anyhow::Result;
 use stander_monlothic_rust::{initialize_app, AppState};
-use tokio::signal;
-use tracing::{info, error};
+use stander_monlothic_rust::config::{load_config_with_overrides, secrets::resolve_secrets, Config, SharedConfig};
+use stander_monlothic_rust::logging::{self, set_log_level, LogFilterHandle};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use stander_monlothic_rust::common::shutdown::wait_for_signal;
+use clap::Parser;
+use cli::{Cli, Command, ConfigAction, MigrateAction};
+use tracing::{info, error, warn};
+use uuid::Uuid;
+use stander_monlothic_rust::models::common::SortOrder;
+use stander_monlothic_rust::models::user::{CreateUser, UserListFilter, UserSortField};
+use stander_monlothic_rust::utils::pagination::{offset_for_page, PageCursor, PagePosition};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
+    let cli = Cli::parse();
+    let command = cli.command.unwrap_or(Command::Serve { rest_only: false, grpc_only: false });
+    if let Command::RotateKeys = command {
+        return rotate_keys();
+    }
+    let mut config = load_config_with_overrides(&cli.overrides)?;
+    let (log_filter_handle, _log_guard) = logging::init(&config.logging, &config.telemetry)?;
+    resolve_secrets(&mut config).await?;
+    // These only need the configuration or Postgres, so they run before the rest of the app is initialised.
+    match command {
+        Command::Migrate { action } => return migrate(&config, action).await,
+        Command::Config { action: ConfigAction::Check { connect } } => return check_config(&config, connect).await,
+        _ => {}
+    }
     info!("Starting Rust Monolithic Application...");
-    let app_state = match initialize_app().await {
+    let app_state = match initialize_app(config).await {
         Ok(state) => {
             info!("Application initialized successfully");
             state
@@ -17,43 +43,318 @@ async fn main() -> Result<()> {
             return Err(e);
         }
     };
-    let rest_server = start_rest_server(app_state.clone());
-    let grpc_server = start_grpc_server(app_state.clone());
+    match command {
+        Command::Serve { rest_only, grpc_only } => {
+            serve(app_state, cli.overrides, log_filter_handle, !grpc_only, !rest_only).await
+        }
+        Command::CreateAdmin { email, password, phone, country_code, first_name, last_name } => {
+            let password = password_or_stdin(password)?;
+            create_admin(app_state, CreateUser {
+                email,
+                password,
+                phone,
+                country_code,
+                first_name,
+                last_name,
+                role: "admin".to_string(),
+            }).await
+        }
+        Command::ResetPassword { email, password } => {
+            let password = password_or_stdin(password)?;
+            reset_password(app_state, &email, &password).await
+        }
+        Command::ListUsers { role, active, search, sort_by, sort_order, limit, page, cursor, include_photos } => {
+            let filter = UserListFilter { role, is_active: active, search, ..Default::default() };
+            let sort_by = UserSortField::parse(&sort_by)
+                .ok_or_else(|| anyhow::anyhow!("Unsupported sort field: {}", sort_by))?;
+            let sort_order = SortOrder::parse(&sort_order)
+                .ok_or_else(|| anyhow::anyhow!("Sort order must be asc or desc"))?;
+            let position = match cursor {
+                Some(cursor) => PagePosition::Cursor(PageCursor::decode(&cursor)?),
+                None => PagePosition::Offset(
+                    offset_for_page(page, limit).ok_or_else(|| anyhow::anyhow!("Page {} is out of range", page))?,
+                ),
+            };
+            list_users(app_state, filter, sort_by, sort_order, limit, position, include_photos).await
+        }
+        Command::VerifyPhoto { user_id, photo_id } => verify_photo(app_state, user_id, photo_id).await,
+        Command::ReconcilePhotos { dry_run } => reconcile_photos(app_state, dry_run).await,
+        Command::ApplyRetention { dry_run } => apply_retention(app_state, dry_run).await,
+        Command::Migrate { .. } | Command::RotateKeys | Command::Config { .. } => unreachable!("handled before initialisation"),
+    }
+}
+
+/// `serve [--rest-only|--grpc-only]`: runs the servers and background jobs until Ctrl+C.
+/// Configuration changes are picked up live; see `config::reload`.
+async fn serve(
+    app_state: AppState,
+    overrides: Vec<(String, String)>,
+    log_filter_handle: LogFilterHandle,
+    rest: bool,
+    grpc: bool,
+) -> Result<()> {
+    let mut log_level = app_state.config.current().logging.level.clone();
+    // Client rebuilds run one at a time in reload order, so a slow rebuild cannot finish after a newer one.
+    let (rebuild_tx, mut rebuild_rx) = tokio::sync::mpsc::unbounded_channel::<Arc<Config>>();
+    let rebuild_state = app_state.clone();
+    let mut running = app_state.config.current();
+    let rebuild_task = tokio::spawn(async move {
+        while let Some(current) = rebuild_rx.recv().await {
+            let previous = std::mem::replace(&mut running, current);
+            stander_monlothic_rust::rebuild_clients(&rebuild_state, &previous, &running).await;
+        }
+    });
+    let reload_task = stander_monlothic_rust::config::reload::spawn_config_reload_task(app_state.config.clone(), overrides, move |config| {
+        if config.logging.level != log_level {
+            log_level = config.logging.level.clone();
+            set_log_level(&log_filter_handle, &log_level);
+        }
+        let _ = rebuild_tx.send(Arc::new(config.clone()));
+    });
+    let mut jobs = vec![
+        stander_monlothic_rust::services::health_service::spawn_readiness_task(app_state.clone()),
+        stander_monlothic_rust::services::upload_service::spawn_upload_expiry_task(app_state.clone()),
+    ];
+    jobs.extend(stander_monlothic_rust::services::photo_reconciler::spawn_photo_reconcile_task(app_state.clone()));
+    jobs.extend(stander_monlothic_rust::services::retention_service::spawn_retention_task(app_state.clone()));
+    jobs.extend(stander_monlothic_rust::services::health_service::spawn_dependency_monitor_task(app_state.clone()));
+
+    let shutdown = app_state.shutdown.clone();
+    let server_config = app_state.config.current().server.clone();
+    let delay = Duration::from_secs(server_config.shutdown_delay_seconds);
+    let timeout = Duration::from_secs(server_config.shutdown_timeout_seconds);
+    // The listeners close `delay` after readiness starts failing, giving load balancers time to notice.
+    let stop_accepting = || {
+        let shutdown = shutdown.clone();
+        async move {
+            shutdown.triggered().await;
+            tokio::time::sleep(delay).await;
+        }
+    };
+    let mut servers = Vec::new();
+    if rest && grpc && server_config.single_port {
+        servers.push(("REST and gRPC", tokio::spawn(start_multiplexed_server(app_state.clone(), stop_accepting()))));
+    } else {
+        if rest {
+            servers.push(("REST", tokio::spawn(start_rest_server(app_state.clone(), stop_accepting()))));
+        }
+        if grpc {
+            servers.push(("gRPC", tokio::spawn(start_grpc_server(app_state.clone(), stop_accepting()))));
+        }
+    }
+
+    // Runs until a signal arrives or a server stops on its own, e.g. because its port is taken.
+    let mut failure = None;
     tokio::select! {
-        result = rest_server => {
-            if let Err(e) = result {
-                error!("REST server error: {}", e);
-            }
+        signal = wait_for_signal() => info!("Received {}, shutting down", signal),
+        (result, index, _) = futures::future::select_all(servers.iter_mut().map(|(_, task)| task)) => {
+            let (name, _) = servers.remove(index);
+            let error = match result {
+                Ok(Ok(())) => anyhow::anyhow!("{} server stopped unexpectedly", name),
+                Ok(Err(e)) => e.context(format!("{} server failed", name)),
+                Err(e) => anyhow::anyhow!("{} server panicked: {}", name, e),
+            };
+            error!("{:#}, shutting down", error);
+            failure = Some(error);
         }
-        result = grpc_server => {
-            if let Err(e) = result {
-                error!("gRPC server error: {}", e);
+    }
+
+    shutdown.trigger();
+    info!("Readiness is now failing; closing listeners in {}s", delay.as_secs());
+    let deadline = tokio::time::Instant::now() + delay + timeout;
+    for (name, mut task) in servers {
+        match tokio::time::timeout_at(deadline, &mut task).await {
+            Ok(Ok(Ok(()))) => {}
+            Ok(Ok(Err(e))) => error!("{} server error: {:#}", name, e),
+            Ok(Err(e)) => error!("{} server panicked: {}", name, e),
+            Err(_) => {
+                warn!("{} server did not drain within {}s, dropping in-flight requests", name, timeout.as_secs());
+                task.abort();
             }
         }
-        _ = signal::ctrl_c() => {
-            info!("Received shutdown signal, stopping servers...");
+    }
+
+    // Background jobs were told to stop when shutdown was triggered; wait for their current pass.
+    for mut job in jobs {
+        if tokio::time::timeout_at(deadline, &mut job).await.is_err() {
+            warn!("Background job still running at the shutdown deadline, cancelling it");
+            job.abort();
         }
     }
+    reload_task.abort();
+    rebuild_task.abort();
+    stander_monlothic_rust::common::memory::shutdown_memory_manager().await;
     info!("Application stopped");
+    failure.map_or(Ok(()), Err)
+}
+
+/// `migrate up|down|status|redo`: manages the embedded PostgreSQL migrations.
+async fn migrate(config: &Config, action: MigrateAction) -> Result<()> {
+    use stander_monlothic_rust::database::postgres;
+    let database_url = config.database.postgres_url.expose();
+    match action {
+        MigrateAction::Up => {
+            let applied = postgres::run_migrations(database_url).await?;
+            println!("{}", serde_json::to_string_pretty(&serde_json::json!({ "applied": applied }))?);
+        }
+        MigrateAction::Down => {
+            let reverted = postgres::revert_last_migration(database_url).await?;
+            println!("{}", serde_json::to_string_pretty(&serde_json::json!({ "reverted": reverted }))?);
+        }
+        MigrateAction::Redo => {
+            let redone = postgres::redo_last_migration(database_url).await?;
+            println!("{}", serde_json::to_string_pretty(&serde_json::json!({ "redone": redone }))?);
+        }
+        MigrateAction::Status => {
+            let status = postgres::migration_status(database_url).await?;
+            println!("{}", serde_json::to_string_pretty(&status)?);
+        }
+    }
+    Ok(())
+}
+
+/// `rotate-keys`: prints fresh signing keys to put into the environment.
+fn rotate_keys() -> Result<()> {
+    use stander_monlothic_rust::utils::encryption::generate_random_string;
+    println!("JWT_SECRET={}", generate_random_string(64));
+    println!("PHOTO_URL_SIGNING_KEY={}", generate_random_string(64));
+    Ok(())
+}
+
+/// `config check [--connect]`: reports configuration problems and fails if there are any.
+/// Settings the service cannot start with already fail while loading, naming the key.
+async fn check_config(config: &Config, connect: bool) -> Result<()> {
+    use stander_monlothic_rust::database;
+    let mut problems = config.check();
+    if connect {
+        if let Err(e) = database::postgres::create_pool(SharedConfig::new(config.clone())).await {
+            problems.push(format!("PostgreSQL: {:#}", e));
+        }
+        if let Err(e) = database::mongodb::create_client(config.database.mongodb_url.expose()).await {
+            problems.push(format!("MongoDB: {:#}", e));
+        }
+    }
+    println!("{}", serde_json::to_string_pretty(&serde_json::json!({
+        "valid": problems.is_empty(),
+        "problems": problems,
+    }))?);
+    if !problems.is_empty() {
+        anyhow::bail!("Configuration has {} problem(s)", problems.len());
+    }
+    Ok(())
+}
+
+/// Reads the password from the first line of stdin unless it was given as an argument.
+fn password_or_stdin(password: Option<String>) -> Result<String> {
+    let password = match password {
+        Some(password) => password,
+        None => {
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line)?;
+            line.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+    if password.is_empty() {
+        anyhow::bail!("Password must not be empty");
+    }
+    Ok(password)
+}
+
+/// `create-admin`: creates a user with the admin role.
+async fn create_admin(app_state: AppState, create_data: CreateUser) -> Result<()> {
+    use stander_monlothic_rust::services::UserService;
+    let user_service = UserService::new(app_state);
+    if user_service.get_user_by_email(&create_data.email).await?.is_some() {
+        anyhow::bail!("A user with email {} already exists", create_data.email);
+    }
+    let user = user_service.create_user(create_data).await?;
+    println!("{}", serde_json::to_string_pretty(&user)?);
+    Ok(())
+}
+
+/// `reset-password`: sets a new password for the user with the given email.
+async fn reset_password(app_state: AppState, email: &str, password: &str) -> Result<()> {
+    use stander_monlothic_rust::services::{AuthService, UserService};
+    let user = UserService::new(app_state.clone()).get_user_by_email(email).await?
+        .ok_or_else(|| anyhow::anyhow!("No user with email {}", email))?;
+    AuthService::new(app_state).reset_password(user.id, password).await?;
+    println!("{}", serde_json::to_string_pretty(&serde_json::json!({ "user_id": user.id, "password_reset": true }))?);
     Ok(())
 }
 
-async fn start_rest_server(app_state: AppState) -> Result<()> {
+/// `list-users`: one page of users printed as JSON.
+async fn list_users(
+    app_state: AppState,
+    filter: UserListFilter,
+    sort_by: UserSortField,
+    sort_order: SortOrder,
+    limit: u32,
+    position: PagePosition,
+    include_photos: bool,
+) -> Result<()> {
+    use stander_monlothic_rust::services::UserService;
+    let page = UserService::new(app_state)
+        .list_users(&filter, sort_by, sort_order, limit, &position, include_photos)
+        .await?;
+    println!("{}", serde_json::to_string_pretty(&serde_json::json!({
+        "users": page.users,
+        "total": page.total,
+        "next_cursor": page.next_cursor,
+        "prev_cursor": page.prev_cursor,
+    }))?);
+    Ok(())
+}
+
+/// `verify-photo`: marks one of a user's photos as verified.
+async fn verify_photo(app_state: AppState, user_id: Uuid, photo_id: Uuid) -> Result<()> {
+    use stander_monlothic_rust::services::PhotoService;
+    let photo = PhotoService::new(app_state).verify_photo(user_id, photo_id).await?;
+    println!("{}", serde_json::to_string_pretty(&photo)?);
+    Ok(())
+}
+
+/// `reconcile-photos [--dry-run]`: one reconciliation pass, report printed as JSON.
+async fn reconcile_photos(app_state: AppState, dry_run: bool) -> Result<()> {
+    use stander_monlothic_rust::services::PhotoReconciler;
+    let report = PhotoReconciler::new(app_state).reconcile(dry_run).await?;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
+
+/// `apply-retention [--dry-run]`: one retention run, report printed as JSON.
+async fn apply_retention(app_state: AppState, dry_run: bool) -> Result<()> {
+    use stander_monlothic_rust::services::RetentionService;
+    let report = RetentionService::new(app_state).run_policy(dry_run).await?;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
+
+async fn start_rest_server(app_state: AppState, shutdown: impl Future<Output = ()> + Send + 'static) -> Result<()> {
     use stander_monlothic_rust::rest::start_rest_server;
     use std::net::SocketAddr;
     let addr: SocketAddr = format!("{}:{}",
-        app_state.config.server.host,
-        app_state.config.server.rest_port
+        app_state.config.current().server.host,
+        app_state.config.current().server.rest_port
     ).parse()?;
-    start_rest_server(addr, app_state).await
+    start_rest_server(addr, app_state, shutdown).await
 }
 
-async fn start_grpc_server(app_state: AppState) -> Result<()> {
+async fn start_grpc_server(app_state: AppState, shutdown: impl Future<Output = ()> + Send + 'static) -> Result<()> {
     use stander_monlothic_rust::grpc::start_grpc_server;
     use std::net::SocketAddr;
     let addr: SocketAddr = format!("{}:{}",
-        app_state.config.server.host,
-        app_state.config.server.grpc_port
+        app_state.config.current().server.host,
+        app_state.config.current().server.grpc_port
+    ).parse()?;
+    start_grpc_server(addr, app_state, shutdown).await
+}
+
+async fn start_multiplexed_server(app_state: AppState, shutdown: impl Future<Output = ()> + Send + 'static) -> Result<()> {
+    use stander_monlothic_rust::multiplex::start_multiplexed_server;
+    use std::net::SocketAddr;
+    let addr: SocketAddr = format!("{}:{}",
+        app_state.config.current().server.host,
+        app_state.config.current().server.rest_port
     ).parse()?;
-    start_grpc_server(addr, app_state).await
+    start_multiplexed_server(addr, app_state, shutdown).await
 }
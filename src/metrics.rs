@@ -0,0 +1,132 @@
+//! Prometheus metrics
+//!
+//! Every metric lives in one registry served as text at `GET /metrics`. Names and labels are
+//! part of the operational interface: dashboards and alerts depend on them, so rename only with
+//! a deprecation period. The full list is documented in the README. Label values are always
+//! drawn from a fixed set (route templates, not raw paths) to keep series counts bounded.
+
+use std::sync::LazyLock;
+
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+
+use crate::AppState;
+
+pub static REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::new);
+
+/// Latency buckets in seconds, from 5ms to 10s.
+const LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+fn register<T: prometheus::core::Collector + Clone + 'static>(metric: T) -> T {
+    REGISTRY.register(Box::new(metric.clone())).expect("metric registered twice");
+    metric
+}
+
+pub static HTTP_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| register(IntCounterVec::new(
+    Opts::new("http_requests_total", "REST requests by method, route template and status code"),
+    &["method", "route", "status"],
+).unwrap()));
+
+pub static HTTP_REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| register(HistogramVec::new(
+    HistogramOpts::new("http_request_duration_seconds", "REST request latency by method and route template")
+        .buckets(LATENCY_BUCKETS.to_vec()),
+    &["method", "route"],
+).unwrap()));
+
+pub static GRPC_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| register(IntCounterVec::new(
+    Opts::new("grpc_requests_total", "gRPC calls by full method name and status code"),
+    &["method", "code"],
+).unwrap()));
+
+pub static GRPC_REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| register(HistogramVec::new(
+    HistogramOpts::new("grpc_request_duration_seconds", "gRPC call latency by full method name")
+        .buckets(LATENCY_BUCKETS.to_vec()),
+    &["method"],
+).unwrap()));
+
+pub static DB_POOL_CONNECTIONS: LazyLock<IntGaugeVec> = LazyLock::new(|| register(IntGaugeVec::new(
+    Opts::new("db_pool_connections", "PostgreSQL pool connections by state (active, idle)"),
+    &["state"],
+).unwrap()));
+
+pub static DB_POOL_MAX_CONNECTIONS: LazyLock<IntGauge> = LazyLock::new(|| register(IntGauge::new(
+    "db_pool_max_connections", "Configured PostgreSQL pool size",
+).unwrap()));
+
+pub static DB_POOL_CHECKOUT_DURATION: LazyLock<Histogram> = LazyLock::new(|| register(Histogram::with_opts(
+    HistogramOpts::new("db_pool_checkout_duration_seconds", "Time spent waiting for a PostgreSQL connection")
+        .buckets(LATENCY_BUCKETS.to_vec()),
+).unwrap()));
+
+pub static MONGODB_COMMAND_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| register(HistogramVec::new(
+    HistogramOpts::new("mongodb_command_duration_seconds", "MongoDB command latency by command name and result (success, failure)")
+        .buckets(LATENCY_BUCKETS.to_vec()),
+    &["command", "result"],
+).unwrap()));
+
+pub static AUTH_LOGINS: LazyLock<IntCounterVec> = LazyLock::new(|| register(IntCounterVec::new(
+    Opts::new("auth_logins_total", "Login attempts by result (success, failure, error)"),
+    &["result"],
+).unwrap()));
+
+pub static PHOTO_UPLOAD_BYTES: LazyLock<HistogramVec> = LazyLock::new(|| register(HistogramVec::new(
+    HistogramOpts::new("photo_upload_bytes", "Size of stored photo uploads by mode (buffered, stream)")
+        .buckets(prometheus::exponential_buckets(16.0 * 1024.0, 2.0, 10).unwrap()),
+    &["mode"],
+).unwrap()));
+
+pub static VERIFICATION_CODE_SENDS: LazyLock<IntCounterVec> = LazyLock::new(|| register(IntCounterVec::new(
+    Opts::new("verification_code_sends_total", "Verification code send requests by result"),
+    &["result"],
+).unwrap()));
+
+pub static MEMORY_CACHE_BYTES: LazyLock<IntGauge> = LazyLock::new(|| register(IntGauge::new(
+    "memory_cache_bytes", "Bytes held by the in-process memory cache",
+).unwrap()));
+
+pub static MEMORY_CACHE_PEAK_BYTES: LazyLock<IntGauge> = LazyLock::new(|| register(IntGauge::new(
+    "memory_cache_peak_bytes", "Largest size the in-process memory cache has reached",
+).unwrap()));
+
+pub static MEMORY_GC_RUNS: LazyLock<IntGauge> = LazyLock::new(|| register(IntGauge::new(
+    "memory_gc_runs", "Garbage collection passes over the in-process memory cache",
+).unwrap()));
+
+/// Registers every metric so a scrape lists them all, including ones that have not fired yet.
+pub fn init() {
+    LazyLock::force(&HTTP_REQUESTS);
+    LazyLock::force(&HTTP_REQUEST_DURATION);
+    LazyLock::force(&GRPC_REQUESTS);
+    LazyLock::force(&GRPC_REQUEST_DURATION);
+    LazyLock::force(&DB_POOL_CONNECTIONS);
+    LazyLock::force(&DB_POOL_MAX_CONNECTIONS);
+    LazyLock::force(&DB_POOL_CHECKOUT_DURATION);
+    LazyLock::force(&MONGODB_COMMAND_DURATION);
+    LazyLock::force(&AUTH_LOGINS);
+    LazyLock::force(&PHOTO_UPLOAD_BYTES);
+    LazyLock::force(&VERIFICATION_CODE_SENDS);
+    LazyLock::force(&MEMORY_CACHE_BYTES);
+    LazyLock::force(&MEMORY_CACHE_PEAK_BYTES);
+    LazyLock::force(&MEMORY_GC_RUNS);
+}
+
+/// Samples the gauges that are read rather than pushed, then encodes the registry in the
+/// Prometheus text format.
+pub async fn render(app_state: &AppState) -> String {
+    init();
+    let pool = app_state.postgres_pool.state();
+    DB_POOL_CONNECTIONS.with_label_values(&["idle"]).set(i64::from(pool.idle_connections));
+    DB_POOL_CONNECTIONS.with_label_values(&["active"]).set(i64::from(pool.connections - pool.idle_connections));
+    DB_POOL_MAX_CONNECTIONS.set(i64::from(app_state.postgres_pool.max_size()));
+    if let Some(stats) = crate::common::memory::get_memory_stats().await {
+        MEMORY_CACHE_BYTES.set(stats.allocated_bytes as i64);
+        MEMORY_CACHE_PEAK_BYTES.set(stats.peak_allocated_bytes as i64);
+        MEMORY_GC_RUNS.set(stats.gc_runs as i64);
+    }
+    let mut buffer = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&REGISTRY.gather(), &mut buffer) {
+        tracing::error!("Failed to encode metrics: {}", e);
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
@@ -1,16 +1,43 @@
 //! Common data models and types
 
 use serde::{Deserialize, Serialize};
+use crate::utils::pagination::{offset_for_page, PageCursor, PagePosition};
 
+/// Paging parameters shared by the listing endpoints.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PaginationQuery {
     pub limit: Option<u32>,
     pub offset: Option<u32>,
+    /// 1-based page number; ignored when `offset` is given.
+    pub page: Option<u32>,
+    /// `next_cursor`/`prev_cursor` from an earlier page; takes precedence over `page` and `offset`.
+    pub cursor: Option<String>,
     pub sort_by: Option<String>,
     pub sort_order: Option<SortOrder>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl PaginationQuery {
+    /// `limit`, or `default` when absent, clamped to `1..=max`.
+    pub fn limit_or(&self, default: u32, max: u32) -> u32 {
+        self.limit.unwrap_or(default).clamp(1, max)
+    }
+
+    pub fn page(&self) -> u32 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    /// Where the requested page starts. `None` when the cursor is malformed or the page
+    /// lies beyond the largest representable offset.
+    pub fn position(&self, limit: u32) -> Option<PagePosition> {
+        match (&self.cursor, self.offset) {
+            (Some(cursor), _) => PageCursor::decode(cursor).ok().map(PagePosition::Cursor),
+            (None, Some(offset)) => Some(PagePosition::Offset(offset)),
+            (None, None) => offset_for_page(self.page(), limit).map(PagePosition::Offset),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SortOrder {
     #[serde(rename = "asc")]
     Ascending,
@@ -18,6 +45,16 @@ pub enum SortOrder {
     Descending,
 }
 
+impl SortOrder {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "asc" => Some(Self::Ascending),
+            "desc" => Some(Self::Descending),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PaginatedResponse<T> {
     pub items: Vec<T>,
@@ -26,6 +63,9 @@ pub struct PaginatedResponse<T> {
     pub offset: u32,
     pub has_next: bool,
     pub has_prev: bool,
+    /// Opaque keyset cursors; pass one back as `cursor` to fetch the neighbouring page.
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
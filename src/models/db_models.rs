@@ -4,10 +4,10 @@ use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
-use crate::schema::{users, user_photos, verification_codes, refresh_tokens};
+use crate::schema::{users, user_photos, verification_codes, refresh_tokens, upload_sessions, upload_chunks, photo_blob_outbox, photo_retention_log};
 
 
-#[derive(Debug, Clone, Queryable, Selectable, Serialize, Deserialize)]
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable, Serialize, Deserialize)]
 #[diesel(table_name = users)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct DbUser {
@@ -57,8 +57,9 @@ pub struct UpdateDbUser {
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Queryable, Selectable, Serialize, Deserialize)]
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable, Associations, Serialize, Deserialize)]
 #[diesel(table_name = user_photos)]
+#[diesel(belongs_to(DbUser, foreign_key = user_id))]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct DbUserPhoto {
     pub id: Uuid,
@@ -68,6 +69,14 @@ pub struct DbUserPhoto {
     pub is_verified: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub content_sha256: Option<String>,
+    pub perceptual_hash: Option<i64>,
+    pub needs_review: bool,
+    pub duplicate_of: Option<Uuid>,
+    pub status: String,
+    pub archived_at: Option<DateTime<Utc>>,
+    pub scan_verdict: Option<String>,
+    pub scan_detail: Option<String>,
 }
 
 
@@ -80,6 +89,14 @@ pub struct NewDbUserPhoto {
     pub is_verified: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub content_sha256: Option<String>,
+    pub perceptual_hash: Option<i64>,
+    pub needs_review: bool,
+    pub duplicate_of: Option<Uuid>,
+    pub status: String,
+    pub archived_at: Option<DateTime<Utc>>,
+    pub scan_verdict: Option<String>,
+    pub scan_detail: Option<String>,
 }
 
 
@@ -131,3 +148,99 @@ pub struct NewDbRefreshToken {
     pub is_revoked: bool,
     pub created_at: DateTime<Utc>,
 }
+
+
+/// Upload session without the buffered `data` column; always load it with `as_select()`.
+#[derive(Debug, Clone, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = upload_sessions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DbUploadSession {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub photo_type: String,
+    pub file_extension: String,
+    pub upload_length: i64,
+    pub upload_offset: i64,
+    pub photo_id: Option<Uuid>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = upload_sessions)]
+pub struct NewDbUploadSession {
+    pub user_id: Uuid,
+    pub photo_type: String,
+    pub file_extension: String,
+    pub upload_length: i64,
+    pub upload_offset: i64,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = upload_chunks)]
+pub struct NewDbUploadChunk {
+    pub session_id: Uuid,
+    pub chunk_offset: i64,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = photo_blob_outbox)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DbPhotoBlobOutbox {
+    pub id: Uuid,
+    pub storage_id: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub storage_area: String,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = photo_blob_outbox)]
+pub struct NewDbPhotoBlobOutbox {
+    pub storage_id: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub storage_area: String,
+}
+
+/// Row of the possible-duplicates self-join on `user_photos`.
+#[derive(Debug, Clone, QueryableByName)]
+pub struct DbPossibleDuplicate {
+    #[diesel(sql_type = diesel::sql_types::Uuid)]
+    pub photo_id: Uuid,
+    #[diesel(sql_type = diesel::sql_types::Uuid)]
+    pub user_id: Uuid,
+    #[diesel(sql_type = diesel::sql_types::Uuid)]
+    pub duplicate_photo_id: Uuid,
+    #[diesel(sql_type = diesel::sql_types::Uuid)]
+    pub duplicate_user_id: Uuid,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub photo_type: String,
+    #[diesel(sql_type = diesel::sql_types::Bool)]
+    pub exact_match: bool,
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::Integer>)]
+    pub distance: Option<i32>,
+    #[diesel(sql_type = diesel::sql_types::Bool)]
+    pub needs_review: bool,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = photo_retention_log)]
+pub struct NewDbPhotoRetentionLog {
+    pub run_id: Uuid,
+    pub photo_id: Uuid,
+    pub user_id: Uuid,
+    pub photo_type: String,
+    pub photo_status: String,
+    pub photo_url: String,
+    pub archived_at: Option<DateTime<Utc>>,
+    pub purged_at: DateTime<Utc>,
+}
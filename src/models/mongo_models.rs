@@ -78,8 +78,8 @@ impl From<&MongoPhoto> for PhotoMetadata {
             content_type: photo.content_type.clone(),
             photo_url: photo.get_photo_url(),
             is_verified: photo.is_verified,
-            created_at: DateTime::<Utc>::from_timestamp_millis(photo.created_at.timestamp_millis()).unwrap_or_else(|| Utc::now()),
-            updated_at: DateTime::<Utc>::from_timestamp_millis(photo.updated_at.timestamp_millis()).unwrap_or_else(|| Utc::now()),
+            created_at: DateTime::<Utc>::from_timestamp_millis(photo.created_at.timestamp_millis()).unwrap_or_else(Utc::now),
+            updated_at: DateTime::<Utc>::from_timestamp_millis(photo.updated_at.timestamp_millis()).unwrap_or_else(Utc::now),
         }
     }
 }
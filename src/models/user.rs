@@ -27,6 +27,44 @@ pub struct UserPhoto {
     pub is_verified: bool,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
+    pub status: String,
+    /// Content scanner verdict; `None` for photos stored before scanning was added.
+    pub scan_verdict: Option<String>,
+}
+
+/// Two photos with the same content, or perceptually close when `distance` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PossibleDuplicate {
+    pub photo_id: Uuid,
+    pub user_id: Uuid,
+    pub duplicate_photo_id: Uuid,
+    pub duplicate_user_id: Uuid,
+    pub photo_type: String,
+    pub exact_match: bool,
+    /// Differing bits between the perceptual hashes.
+    pub distance: Option<i32>,
+    pub needs_review: bool,
+}
+
+impl From<crate::models::db_models::DbPossibleDuplicate> for PossibleDuplicate {
+    fn from(row: crate::models::db_models::DbPossibleDuplicate) -> Self {
+        Self {
+            photo_id: row.photo_id,
+            user_id: row.user_id,
+            duplicate_photo_id: row.duplicate_photo_id,
+            duplicate_user_id: row.duplicate_user_id,
+            photo_type: row.photo_type,
+            exact_match: row.exact_match,
+            distance: row.distance,
+            needs_review: row.needs_review,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedPhotoUrl {
+    pub url: String,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -77,3 +115,71 @@ pub struct PhotoUploadRequest {
     pub photo_data: Vec<u8>,
     pub file_extension: String,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadSession {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub photo_type: String,
+    pub file_extension: String,
+    pub upload_length: i64,
+    pub upload_offset: i64,
+    pub photo_id: Option<Uuid>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl UploadSession {
+    pub fn is_complete(&self) -> bool {
+        self.upload_offset == self.upload_length
+    }
+}
+
+/// Criteria for listing users; every `None` field matches all users.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UserListFilter {
+    pub role: Option<String>,
+    pub is_active: Option<bool>,
+    pub email_verified: Option<bool>,
+    pub phone_verified: Option<bool>,
+    pub created_after: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_before: Option<chrono::DateTime<chrono::Utc>>,
+    /// Case-insensitive substring match on first name, last name, email or phone.
+    pub search: Option<String>,
+}
+
+/// Columns a user listing may be ordered by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UserSortField {
+    #[default]
+    CreatedAt,
+    UpdatedAt,
+    Email,
+    FirstName,
+    LastName,
+    Role,
+}
+
+impl UserSortField {
+    /// Only the whitelisted column names are accepted.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "created_at" => Some(Self::CreatedAt),
+            "updated_at" => Some(Self::UpdatedAt),
+            "email" => Some(Self::Email),
+            "first_name" => Some(Self::FirstName),
+            "last_name" => Some(Self::LastName),
+            "role" => Some(Self::Role),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UserListPage {
+    pub users: Vec<User>,
+    /// Number of users matching the filter, ignoring limit and offset.
+    pub total: u64,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+}
@@ -0,0 +1,393 @@
+//! REST and gRPC on a single port
+//!
+//! With `server.single_port` set, one listener on `server.rest_port` serves both APIs. Requests
+//! whose `content-type` is gRPC (`application/grpc`, `application/grpc+proto`) or gRPC-Web
+//! (`application/grpc-web`, `application/grpc-web+proto`, `application/grpc-web-text`) go to
+//! the tonic services; everything else, including CORS preflights, goes to the axum router.
+//!
+//! tonic 0.10 is built on hyper 0.14 and http 0.2 while axum 0.7 uses hyper 1, so the listener
+//! is a hyper 0.14 server. gRPC calls reach tonic as they arrive and REST requests are
+//! converted at the boundary.
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use anyhow::Result;
+use axum::body::{Bytes, HttpBody as _};
+use axum::extract::ConnectInfo;
+use hyper_0_14::body::{HttpBody, SizeHint};
+use hyper_0_14::server::accept::{self, Accept};
+use hyper_0_14::server::conn::AddrIncoming;
+use hyper_0_14::service::make_service_fn;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tonic::body::BoxBody;
+use tonic::codegen::http::{self, header, HeaderValue};
+use tonic::transport::server::{Connected, TcpConnectInfo, TlsConnectInfo};
+use tonic::transport::Body;
+use tonic::Status;
+use tonic_web::GrpcWebLayer;
+use tower::{BoxError, Layer, Service, ServiceExt};
+use tracing::{debug, info, Instrument};
+
+use crate::config::SharedConfig;
+use crate::rest::middleware::cors::origin_allowed;
+use crate::tls::{ClientAuth, ReloadingServerConfig};
+
+/// Serves REST, gRPC and gRPC-Web on `addr` until `shutdown` resolves, then stops accepting
+/// connections and returns once in-flight requests have completed.
+///
+/// With TLS the listener cannot insist on client certificates, because browsers and REST
+/// clients share it. Certificates are verified when presented, and `tls.grpc_require_client_cert`
+/// is enforced per call instead: gRPC calls without one fail with `UNAUTHENTICATED`.
+pub async fn start_multiplexed_server(
+    addr: SocketAddr,
+    app_state: crate::AppState,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> Result<()> {
+    info!("Starting REST and gRPC server on {}", addr);
+    let tls_settings = app_state.config.current().tls.clone();
+    let multiplexer = Multiplexer {
+        rest: crate::rest::create_router(app_state.clone()),
+        grpc: GrpcWebLayer::new().layer(crate::grpc::with_middleware(crate::grpc::create_grpc_routes(app_state.clone()))),
+        config: app_state.config.clone(),
+        require_client_cert: tls_settings.grpc_require_client_cert,
+    };
+
+    if tls_settings.enabled {
+        let client_auth = match ClientAuth::for_grpc(&tls_settings) {
+            ClientAuth::Required => ClientAuth::Optional,
+            client_auth => client_auth,
+        };
+        let tls = ReloadingServerConfig::load(&tls_settings, client_auth, crate::tls::REST_ALPN)?;
+        tls.spawn_reload_task("REST and gRPC", app_state.shutdown.clone(), |_| {});
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        info!(
+            "REST and gRPC server listening on {} with TLS certificate {} (client certificates: {:?})",
+            addr, tls_settings.cert_file, client_auth,
+        );
+        serve(accept::from_stream(crate::tls::incoming(listener, tls)), multiplexer, shutdown).await?;
+    } else {
+        let incoming = AddrIncoming::bind(&addr)?;
+        info!("REST and gRPC server listening on {}", addr);
+        serve(incoming, multiplexer, shutdown).await?;
+    }
+    info!("REST and gRPC server drained");
+    Ok(())
+}
+
+async fn serve<A, G>(
+    incoming: A,
+    multiplexer: Multiplexer<G>,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> Result<()>
+where
+    A: Accept,
+    A::Conn: Connected + AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    A::Error: Into<BoxError>,
+    <A::Conn as Connected>::ConnectInfo: PeerAddr + Clone + Send + Sync + 'static,
+    G: Service<http::Request<Body>, Response = http::Response<BoxBody>> + Clone + Send + 'static,
+    G::Error: Into<BoxError>,
+    G::Future: Send + 'static,
+{
+    let make_service = make_service_fn(move |conn: &A::Conn| {
+        let service = multiplexer.connection(conn.connect_info());
+        async move { Ok::<_, Infallible>(service) }
+    });
+    hyper_0_14::Server::builder(incoming)
+        .serve(make_service)
+        .with_graceful_shutdown(shutdown)
+        .await?;
+    Ok(())
+}
+
+/// The client address of an accepted connection, for REST rate limiting.
+trait PeerAddr {
+    fn peer_addr(&self) -> Option<SocketAddr>;
+}
+
+impl PeerAddr for TcpConnectInfo {
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        self.remote_addr()
+    }
+}
+
+impl PeerAddr for TlsConnectInfo<TcpConnectInfo> {
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        self.get_ref().remote_addr()
+    }
+}
+
+#[derive(Clone)]
+struct Multiplexer<G> {
+    rest: axum::Router,
+    grpc: G,
+    config: SharedConfig,
+    require_client_cert: bool,
+}
+
+impl<G: Clone> Multiplexer<G> {
+    /// The service for one connection. Its gRPC calls carry `info` as an extension, as they
+    /// would on tonic's own server.
+    fn connection<I>(&self, info: I) -> MultiplexService<G, I> {
+        MultiplexService {
+            rest: self.rest.clone(),
+            grpc: self.grpc.clone(),
+            config: self.config.clone(),
+            require_client_cert: self.require_client_cert,
+            info,
+        }
+    }
+}
+
+struct MultiplexService<G, I> {
+    rest: axum::Router,
+    grpc: G,
+    config: SharedConfig,
+    require_client_cert: bool,
+    info: I,
+}
+
+impl<G, I> Service<http::Request<Body>> for MultiplexService<G, I>
+where
+    G: Service<http::Request<Body>, Response = http::Response<BoxBody>> + Send + 'static,
+    G::Error: Into<BoxError>,
+    G::Future: Send + 'static,
+    I: PeerAddr + Clone + Send + Sync + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.grpc.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, mut request: http::Request<Body>) -> Self::Future {
+        if !is_grpc(request.headers()) {
+            return Box::pin(call_rest(self.rest.clone(), request, self.info.peer_addr()));
+        }
+
+        request.extensions_mut().insert(self.info.clone());
+        if self.require_client_cert && !has_client_certificate(&request) {
+            debug!("Rejecting gRPC call to {} without a client certificate", request.uri().path());
+            return Box::pin(async { Ok(Status::unauthenticated("A client certificate is required").to_http()) });
+        }
+        let origin = request.headers().get(header::ORIGIN)
+            .filter(|origin| origin_allowed(&self.config, origin.as_bytes()))
+            .cloned();
+        let span = crate::grpc::request_span(&request);
+        let future = span.in_scope(|| self.grpc.call(request));
+        Box::pin(
+            async move {
+                let mut response = future.await.map_err(Into::into)?;
+                if let Some(origin) = origin {
+                    allow_origin(response.headers_mut(), origin);
+                }
+                Ok(response)
+            }
+            .instrument(span),
+        )
+    }
+}
+
+/// Whether the request is a gRPC or gRPC-Web call, judged by its content type.
+fn is_grpc(headers: &http::HeaderMap) -> bool {
+    headers.get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|content_type| content_type.strip_prefix("application/grpc"))
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(['+', ';']) || rest.starts_with("-web"))
+}
+
+fn has_client_certificate<B>(request: &http::Request<B>) -> bool {
+    request.extensions().get::<TlsConnectInfo<TcpConnectInfo>>()
+        .and_then(|info| info.peer_certs())
+        .is_some_and(|certs| !certs.is_empty())
+}
+
+/// Lets a browser on an allowed origin read a gRPC-Web response. Preflights have no gRPC
+/// content type, so the REST router's CORS layer answers them.
+fn allow_origin(headers: &mut http::HeaderMap, origin: HeaderValue) {
+    headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+    headers.insert(
+        header::ACCESS_CONTROL_EXPOSE_HEADERS,
+        HeaderValue::from_static("grpc-status, grpc-message, grpc-status-details-bin, x-request-id"),
+    );
+    headers.append(header::VARY, HeaderValue::from_static("origin"));
+}
+
+async fn call_rest(
+    router: axum::Router,
+    request: http::Request<Body>,
+    peer: Option<SocketAddr>,
+) -> Result<http::Response<BoxBody>, BoxError> {
+    let request = match to_axum_request(request, peer) {
+        Ok(request) => request,
+        Err(e) => {
+            debug!("Rejecting request that does not convert to axum: {}", e);
+            let mut response = http::Response::new(tonic::body::empty_body());
+            *response.status_mut() = http::StatusCode::BAD_REQUEST;
+            return Ok(response);
+        }
+    };
+    let response = router.oneshot(request).await?;
+    Ok(from_axum_response(response)?)
+}
+
+fn to_axum_request(request: http::Request<Body>, peer: Option<SocketAddr>) -> Result<axum::extract::Request, axum::http::Error> {
+    let (parts, body) = request.into_parts();
+    let mut builder = axum::http::Request::builder()
+        .method(parts.method.as_str())
+        .uri(parts.uri.to_string())
+        .version(to_axum_version(parts.version));
+    for (name, value) in &parts.headers {
+        builder = builder.header(name.as_str(), value.as_bytes());
+    }
+    if let Some(peer) = peer {
+        builder = builder.extension(ConnectInfo(peer));
+    }
+    builder.body(axum::body::Body::from_stream(body))
+}
+
+fn to_axum_version(version: http::Version) -> axum::http::Version {
+    match version {
+        http::Version::HTTP_09 => axum::http::Version::HTTP_09,
+        http::Version::HTTP_10 => axum::http::Version::HTTP_10,
+        http::Version::HTTP_2 => axum::http::Version::HTTP_2,
+        http::Version::HTTP_3 => axum::http::Version::HTTP_3,
+        _ => axum::http::Version::HTTP_11,
+    }
+}
+
+fn from_axum_response(response: axum::response::Response) -> Result<http::Response<BoxBody>, http::Error> {
+    let (parts, body) = response.into_parts();
+    let mut builder = http::Response::builder().status(parts.status.as_u16());
+    for (name, value) in &parts.headers {
+        builder = builder.header(name.as_str(), value.as_bytes());
+    }
+    builder.body(RestBody(body).map_err(|e| Status::from_error(e.into())).boxed_unsync())
+}
+
+/// An axum response body as an http-body 0.4 body. REST responses have no trailers; any the
+/// body ends with are dropped.
+struct RestBody(axum::body::Body);
+
+impl HttpBody for RestBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_data(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes, axum::Error>>> {
+        loop {
+            return match ready!(Pin::new(&mut self.0).poll_frame(cx)) {
+                Some(Ok(frame)) => match frame.into_data() {
+                    Ok(data) => Poll::Ready(Some(Ok(data))),
+                    Err(_) => continue,
+                },
+                Some(Err(e)) => Poll::Ready(Some(Err(e))),
+                None => Poll::Ready(None),
+            };
+        }
+    }
+
+    fn poll_trailers(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<Option<http::HeaderMap>, axum::Error>> {
+        Poll::Ready(Ok(None))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.0.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        let hint = self.0.size_hint();
+        let mut converted = SizeHint::new();
+        converted.set_lower(hint.lower());
+        if let Some(upper) = hint.upper() {
+            converted.set_upper(upper);
+        }
+        converted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+
+    use crate::config::Config;
+
+    #[derive(Clone)]
+    struct Anonymous;
+
+    impl PeerAddr for Anonymous {
+        fn peer_addr(&self) -> Option<SocketAddr> {
+            None
+        }
+    }
+
+    #[derive(Clone)]
+    struct GrpcStub;
+
+    impl Service<http::Request<Body>> for GrpcStub {
+        type Response = http::Response<BoxBody>;
+        type Error = Infallible;
+        type Future = std::future::Ready<Result<Self::Response, Infallible>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _request: http::Request<Body>) -> Self::Future {
+            let mut response = http::Response::new(tonic::body::empty_body());
+            response.headers_mut().insert("grpc-status", HeaderValue::from_static("0"));
+            std::future::ready(Ok(response))
+        }
+    }
+
+    fn service(require_client_cert: bool) -> MultiplexService<GrpcStub, Anonymous> {
+        Multiplexer {
+            rest: axum::Router::new().route("/health/live", get(|| async { "rest" })),
+            grpc: GrpcStub,
+            config: SharedConfig::new(Config::default()),
+            require_client_cert,
+        }
+        .connection(Anonymous)
+    }
+
+    fn request(content_type: Option<&str>) -> http::Request<Body> {
+        let mut builder = http::Request::post("/health/live").header(header::ORIGIN, "https://app.example.com");
+        if let Some(content_type) = content_type {
+            builder = builder.header(header::CONTENT_TYPE, content_type);
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_routes_by_content_type() {
+        for content_type in ["application/grpc", "application/grpc+proto", "application/grpc-web+proto", "application/grpc-web-text"] {
+            let response = service(false).oneshot(request(Some(content_type))).await.unwrap();
+            assert_eq!(response.headers()["grpc-status"], "0", "{}", content_type);
+            assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://app.example.com");
+        }
+
+        for content_type in [None, Some("application/json"), Some("application/grpcx")] {
+            let mut get = request(content_type);
+            *get.method_mut() = http::Method::GET;
+            let response = service(false).oneshot(get).await.unwrap();
+            assert_eq!(response.status(), http::StatusCode::OK);
+            assert!(response.headers().get("grpc-status").is_none(), "{:?}", content_type);
+            let body = hyper_0_14::body::to_bytes(response.into_body()).await.unwrap();
+            assert_eq!(body, "rest");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_required_client_certificate_is_enforced_per_call() {
+        let response = service(true).oneshot(request(Some("application/grpc"))).await.unwrap();
+        assert_eq!(response.headers()["grpc-status"], "16");
+
+        let response = service(true).oneshot(request(None)).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::METHOD_NOT_ALLOWED);
+    }
+}
@@ -0,0 +1,71 @@
+//! Operational admin handlers
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::Json,
+};
+use axum_extra::{
+    TypedHeader,
+    headers::{Authorization, authorization::Bearer},
+};
+use crate::{
+    AppState,
+    services::AuthService,
+    services::health_service::ReadinessReport,
+    common::response::ApiResponse,
+    common::request_id::current_request_id,
+};
+
+/// Admin-only: the latest readiness report including each failed probe's error, which the
+/// public health endpoints leave out.
+pub async fn get_health_details(
+    State(app_state): State<AppState>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+) -> Result<Json<ApiResponse<ReadinessReport>>, StatusCode> {
+    require_admin(&app_state, auth.token()).await?;
+    let report = app_state.readiness.latest();
+    let message = if report.is_some() {
+        "Readiness report retrieved successfully"
+    } else {
+        "No readiness probe has finished yet"
+    };
+    Ok(Json(ApiResponse {
+        success: true,
+        data: report,
+        error: None,
+        message: message.to_string(),
+        timestamp: chrono::Utc::now(),
+        request_id: current_request_id(),
+    }))
+}
+
+/// Admin-only: the configuration the service is currently running with, secrets redacted.
+/// Reflects any settings applied by a live reload.
+pub async fn get_effective_config(
+    State(app_state): State<AppState>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
+    require_admin(&app_state, auth.token()).await?;
+    Ok(Json(ApiResponse {
+        success: true,
+        data: Some(app_state.config.current().redacted()),
+        error: None,
+        message: "Effective configuration retrieved successfully".to_string(),
+        timestamp: chrono::Utc::now(),
+        request_id: current_request_id(),
+    }))
+}
+
+async fn require_admin(app_state: &AppState, token: &str) -> Result<(), StatusCode> {
+    let auth_service = AuthService::new(app_state.clone());
+    let current_user = match auth_service.verify_token(token).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err(StatusCode::UNAUTHORIZED),
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+    };
+    if current_user.role != "admin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(())
+}
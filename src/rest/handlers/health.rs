@@ -1,4 +1,15 @@
 //! Health check handlers
+//!
+//! `/health/live` only shows the process is serving requests; orchestrators restart it when this
+//! fails. `/health/ready` reports the latest dependency probes and answers 503 while a critical one
+//! is down, so traffic is withheld without a restart, and from the moment shutdown starts. `/health`
+//! is kept as an alias of the readiness check.
+//!
+//! These endpoints are unauthenticated, so they serve the report cached by the readiness task
+//! rather than probing per request, and leave out the probe errors. Admins read those at
+//! `/api/v1/admin/health`.
+
+use std::collections::BTreeMap;
 
 use axum::{
     extract::State,
@@ -6,59 +17,101 @@ use axum::{
     response::Json,
 };
 use serde::{Deserialize, Serialize};
-use tracing::info;
+use tracing::warn;
+
+use crate::services::health_service::{DependencyCheck, ReadinessReport};
 
 #[derive(Serialize, Deserialize)]
-pub struct HealthResponse {
+pub struct LivenessResponse {
     pub status: String,
     pub timestamp: String,
     pub version: String,
-    pub services: ServiceStatus,
 }
 
 #[derive(Serialize, Deserialize)]
-pub struct ServiceStatus {
-    pub database: String,
-    pub mongodb: String,
-    pub aws: String,
-    pub huawei: String,
+pub struct ReadinessResponse {
+    /// `ready`, `not_ready` or `shutting_down`.
+    pub status: String,
+    pub timestamp: String,
+    pub version: String,
+    /// When the reported probes ran; absent before the first one finishes.
+    pub checked_at: Option<String>,
+    pub checks: BTreeMap<String, DependencyCheck>,
 }
 
+pub async fn liveness() -> Json<LivenessResponse> {
+    Json(LivenessResponse {
+        status: "alive".to_string(),
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+    })
+}
 
-pub async fn health_check(
+pub async fn readiness(
     State(app_state): State<crate::AppState>,
-) -> Result<Json<HealthResponse>, StatusCode> {
-    info!("Health check requested");
-
-    let postgres_status = match crate::database::postgres::get_connection(&app_state.postgres_pool) {
-        Ok(_) => "healthy".to_string(),
-        Err(_) => "unhealthy".to_string(),
+) -> (StatusCode, Json<ReadinessResponse>) {
+    let report = if app_state.shutdown.is_triggered() {
+        Some(ReadinessReport::shutting_down())
+    } else {
+        app_state.readiness.latest()
     };
-
-    let mongodb_status = match crate::database::mongodb::test_connection_with_client(&app_state.mongodb_client).await {
-        Ok(_) => "healthy".to_string(),
-        Err(_) => "unhealthy".to_string(),
-    };
-    let aws_status = match &app_state.aws_config {
-        Some(_) => "enabled".to_string(),
-        None => "disabled".to_string(),
+    let (status, label) = match &report {
+        Some(report) if report.ready => (StatusCode::OK, "ready"),
+        Some(report) if report.shutting_down => (StatusCode::SERVICE_UNAVAILABLE, "shutting_down"),
+        Some(report) => {
+            warn!("Readiness check failed, critical dependencies down: {:?}", report.down());
+            (StatusCode::SERVICE_UNAVAILABLE, "not_ready")
+        }
+        None => (StatusCode::SERVICE_UNAVAILABLE, "not_ready"),
     };
-
-    let huawei_status = match &app_state.huawei_config {
-        Some(_) => "enabled".to_string(),
-        None => "disabled".to_string(),
+    let (checked_at, checks) = match report {
+        Some(report) => (Some(report.checked_at.to_rfc3339()), report.checks),
+        None => (None, BTreeMap::new()),
     };
-
-    let response = HealthResponse {
-        status: "ok".to_string(),
+    let response = ReadinessResponse {
+        status: label.to_string(),
         timestamp: chrono::Utc::now().to_rfc3339(),
         version: env!("CARGO_PKG_VERSION").to_string(),
-        services: ServiceStatus {
-            database: postgres_status,
-            mongodb: mongodb_status,
-            aws: aws_status,
-            huawei: huawei_status,
-        },
+        checked_at,
+        checks: checks.into_iter()
+            .map(|(name, check)| (name, DependencyCheck { error: None, ..check }))
+            .collect(),
     };
-    Ok(Json(response))
+    (status, Json(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::health_service::ProbeStatus;
+
+    #[tokio::test]
+    async fn test_readiness_serves_cached_report_without_errors() {
+        let app_state = crate::offline_app_state().await;
+        let (status, Json(response)) = readiness(State(app_state.clone())).await;
+        assert_eq!((status, response.status.as_str()), (StatusCode::SERVICE_UNAVAILABLE, "not_ready"));
+        assert!(response.checked_at.is_none() && response.checks.is_empty());
+
+        let down = DependencyCheck {
+            status: ProbeStatus::Down,
+            critical: true,
+            latency_ms: 3,
+            error: Some("connection to server at \"10.0.0.7\", port 5432 failed".to_string()),
+        };
+        app_state.readiness.publish(ReadinessReport {
+            ready: false,
+            shutting_down: false,
+            checks: BTreeMap::from([("postgres".to_string(), down)]),
+            checked_at: chrono::Utc::now(),
+        });
+        let (status, Json(response)) = readiness(State(app_state.clone())).await;
+        assert_eq!((status, response.status.as_str()), (StatusCode::SERVICE_UNAVAILABLE, "not_ready"));
+        let check = &response.checks["postgres"];
+        assert_eq!((check.status, check.latency_ms, check.error.as_deref()), (ProbeStatus::Down, 3, None));
+        assert!(app_state.readiness.latest().unwrap().checks["postgres"].error.is_some());
+
+        app_state.shutdown.trigger();
+        let (_, Json(response)) = readiness(State(app_state)).await;
+        assert_eq!(response.status, "shutting_down");
+    }
 }
@@ -0,0 +1,14 @@
+//! Prometheus scrape endpoint
+
+use axum::{
+    extract::State,
+    http::header,
+    response::IntoResponse,
+};
+
+pub async fn metrics(State(app_state): State<crate::AppState>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+        crate::metrics::render(&app_state).await,
+    )
+}
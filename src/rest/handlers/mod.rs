@@ -2,4 +2,8 @@
 
 pub mod health;
 pub mod user;
+pub mod photo;
+pub mod upload;
+pub mod admin;
+pub mod metrics;
 
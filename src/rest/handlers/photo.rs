@@ -0,0 +1,295 @@
+//! Photo download and signed URL handlers
+
+use axum::{
+    extract::{State, Path, Query},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use axum_extra::{
+    TypedHeader,
+    headers::{Authorization, authorization::Bearer},
+};
+use serde::Deserialize;
+use uuid::Uuid;
+use tracing::{error, warn};
+use crate::{
+    AppState,
+    services::{AuthService, PhotoService},
+    services::photo_service::{PhotoUrlScope, DuplicatePhotoQuery, rejection_cause, storage_area_for, PHOTO_STATUS_QUARANTINED},
+    services::photo_storage::StorageArea,
+    models::user::{SignedPhotoUrl, PossibleDuplicate, UserPhoto},
+    models::common::{PaginatedResponse, PaginationQuery},
+    utils::pagination::{PageCursor, PagePosition},
+    common::response::ApiResponse,
+    common::request_id::current_request_id,
+    rest::middleware::auth::extract_token,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct PhotoDownloadQuery {
+    pub expires: Option<i64>,
+    pub size: Option<u32>,
+    pub sig: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PhotoUrlQuery {
+    pub size: Option<u32>,
+    pub expires_in: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DuplicatesQuery {
+    pub photo_type: Option<String>,
+    pub max_distance: Option<u32>,
+    pub cross_account_only: Option<bool>,
+    /// Photos examined per page.
+    pub limit: Option<u32>,
+    /// `next_cursor`/`prev_cursor` from an earlier page.
+    pub cursor: Option<String>,
+}
+
+/// Serves a photo either to the holder of a valid signed URL or to its owner/an admin via bearer token.
+pub async fn download_photo(
+    State(app_state): State<AppState>,
+    Path(storage_id): Path<String>,
+    Query(query): Query<PhotoDownloadQuery>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    let photo_service = PhotoService::new(app_state.clone());
+    let scope = PhotoUrlScope::from_size(query.size).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let (cache_control, area) = match (query.expires, query.sig.as_deref()) {
+        (Some(expires), Some(sig)) => {
+            if !photo_service.verify_photo_url(&storage_id, expires, scope, sig) {
+                warn!("Rejected photo download with invalid or expired signature: {}", storage_id);
+                return Err(StatusCode::FORBIDDEN);
+            }
+            let max_age = (expires - chrono::Utc::now().timestamp()).max(0);
+            // Signed URLs only ever reach the public area, never quarantine.
+            (format!("private, max-age={}", max_age), StorageArea::Photos)
+        }
+        _ => {
+            let token = extract_token(&headers).ok_or(StatusCode::UNAUTHORIZED)?;
+            let auth_service = AuthService::new(app_state);
+            let current_user = match auth_service.verify_token(&token).await {
+                Ok(Some(user)) => user,
+                Ok(None) => return Err(StatusCode::UNAUTHORIZED),
+                Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+            };
+            let photo = match photo_service.get_photo_by_storage_id(&storage_id).await {
+                Ok(Some(photo)) => photo,
+                Ok(None) => return Err(StatusCode::NOT_FOUND),
+                Err(e) => {
+                    error!("Failed to look up photo: {}", e);
+                    return Err(StatusCode::INTERNAL_SERVER_ERROR);
+                }
+            };
+            if photo.user_id != current_user.id && current_user.role != "admin" {
+                return Err(StatusCode::FORBIDDEN);
+            }
+            if photo.status == PHOTO_STATUS_QUARANTINED && current_user.role != "admin" {
+                return Err(StatusCode::FORBIDDEN);
+            }
+            ("private, no-store".to_string(), storage_area_for(photo.scan_verdict.as_deref()))
+        }
+    };
+
+    match photo_service.get_photo_data(&storage_id, scope, area).await {
+        Ok(object) => Ok((
+            [
+                (header::CONTENT_TYPE, object.content_type),
+                (header::CACHE_CONTROL, cache_control),
+            ],
+            object.data,
+        ).into_response()),
+        Err(e) => {
+            error!("Failed to load photo {}: {}", storage_id, e);
+            Err(StatusCode::NOT_FOUND)
+        }
+    }
+}
+
+pub async fn get_photo_url(
+    State(app_state): State<AppState>,
+    Path((user_id, photo_id)): Path<(Uuid, Uuid)>,
+    Query(query): Query<PhotoUrlQuery>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+) -> Result<Json<ApiResponse<SignedPhotoUrl>>, StatusCode> {
+    let auth_service = AuthService::new(app_state.clone());
+    let photo_service = PhotoService::new(app_state);
+    let current_user = match auth_service.verify_token(auth.token()).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err(StatusCode::UNAUTHORIZED),
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+    };
+    if current_user.id != user_id && current_user.role != "admin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let scope = PhotoUrlScope::from_size(query.size).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let photo = match photo_service.get_photo(photo_id).await {
+        Ok(Some(photo)) if photo.user_id == user_id => photo,
+        Ok(_) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to get photo: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    match photo_service.issue_photo_url(&photo, query.expires_in, scope).await {
+        Ok(signed_url) => Ok(Json(ApiResponse {
+            success: true,
+            data: Some(signed_url),
+            error: None,
+            message: "Photo URL issued successfully".to_string(),
+            timestamp: chrono::Utc::now(),
+            request_id: current_request_id(),
+        })),
+        Err(e) => {
+            error!("Failed to issue photo URL: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Admin-only: photo pairs that are byte-identical or perceptually near-identical, paged by photo.
+pub async fn list_possible_duplicates(
+    State(app_state): State<AppState>,
+    Query(query): Query<DuplicatesQuery>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+) -> Result<Json<ApiResponse<PaginatedResponse<PossibleDuplicate>>>, StatusCode> {
+    let auth_service = AuthService::new(app_state.clone());
+    let photo_service = PhotoService::new(app_state);
+    let current_user = match auth_service.verify_token(auth.token()).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err(StatusCode::UNAUTHORIZED),
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+    };
+    if current_user.role != "admin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let position = match query.cursor.as_deref() {
+        Some(cursor) => PagePosition::Cursor(PageCursor::decode(cursor).map_err(|_| StatusCode::BAD_REQUEST)?),
+        None => PagePosition::Offset(0),
+    };
+    let query = DuplicatePhotoQuery {
+        photo_type: query.photo_type,
+        max_distance: query.max_distance,
+        cross_account_only: query.cross_account_only.unwrap_or(false),
+        limit: query.limit.unwrap_or(50).clamp(1, 500),
+    };
+    match photo_service.find_possible_duplicates(query, &position).await {
+        Ok(duplicates) => Ok(Json(ApiResponse {
+            success: true,
+            data: Some(duplicates),
+            error: None,
+            message: "Possible duplicates retrieved successfully".to_string(),
+            timestamp: chrono::Utc::now(),
+            request_id: current_request_id(),
+        })),
+        Err(e) => {
+            error!("Failed to query possible duplicates: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Admin-only: rejects a photo after review. It is archived and later purged by the retention job.
+pub async fn reject_photo(
+    State(app_state): State<AppState>,
+    Path(photo_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+) -> Result<Json<ApiResponse<UserPhoto>>, StatusCode> {
+    let auth_service = AuthService::new(app_state.clone());
+    let photo_service = PhotoService::new(app_state);
+    let current_user = match auth_service.verify_token(auth.token()).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err(StatusCode::UNAUTHORIZED),
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+    };
+    if current_user.role != "admin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    match photo_service.reject_photo(photo_id).await {
+        Ok(photo) => Ok(Json(ApiResponse {
+            success: true,
+            data: Some(photo),
+            error: None,
+            message: "Photo rejected successfully".to_string(),
+            timestamp: chrono::Utc::now(),
+            request_id: current_request_id(),
+        })),
+        Err(e) => {
+            error!("Failed to reject photo: {}", e);
+            Err(StatusCode::NOT_FOUND)
+        }
+    }
+}
+
+/// Admin-only: uploads the content scanner did not pass, with their verdicts.
+pub async fn list_quarantined_photos(
+    State(app_state): State<AppState>,
+    Query(pagination): Query<PaginationQuery>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+) -> Result<Json<ApiResponse<PaginatedResponse<UserPhoto>>>, StatusCode> {
+    let auth_service = AuthService::new(app_state.clone());
+    let photo_service = PhotoService::new(app_state);
+    let current_user = match auth_service.verify_token(auth.token()).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err(StatusCode::UNAUTHORIZED),
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+    };
+    if current_user.role != "admin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let limit = pagination.limit_or(50, 500);
+    let position = pagination.position(limit).ok_or(StatusCode::BAD_REQUEST)?;
+    match photo_service.list_quarantined_photos(limit, &position).await {
+        Ok(photos) => Ok(Json(ApiResponse {
+            success: true,
+            data: Some(photos),
+            error: None,
+            message: "Quarantined photos retrieved successfully".to_string(),
+            timestamp: chrono::Utc::now(),
+            request_id: current_request_id(),
+        })),
+        Err(e) => {
+            error!("Failed to list quarantined photos: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Admin-only: clears a quarantined photo and moves it back to normal storage.
+pub async fn release_photo(
+    State(app_state): State<AppState>,
+    Path(photo_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+) -> Result<Json<ApiResponse<UserPhoto>>, StatusCode> {
+    let auth_service = AuthService::new(app_state.clone());
+    let photo_service = PhotoService::new(app_state);
+    let current_user = match auth_service.verify_token(auth.token()).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err(StatusCode::UNAUTHORIZED),
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+    };
+    if current_user.role != "admin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    match photo_service.release_photo(photo_id).await {
+        Ok(photo) => Ok(Json(ApiResponse {
+            success: true,
+            data: Some(photo),
+            error: None,
+            message: "Photo released successfully".to_string(),
+            timestamp: chrono::Utc::now(),
+            request_id: current_request_id(),
+        })),
+        Err(e) => match rejection_cause(&e) {
+            Some(rejected) => Err(StatusCode::from_u16(rejected.status_code()).unwrap_or(StatusCode::CONFLICT)),
+            None => {
+                error!("Failed to release photo: {}", e);
+                Err(StatusCode::NOT_FOUND)
+            }
+        },
+    }
+}
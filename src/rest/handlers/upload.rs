@@ -0,0 +1,318 @@
+//! Resumable photo upload handlers implementing the tus 1.0.0 protocol
+//!
+//! Supported extensions: creation, expiration, checksum (sha1, sha256) and termination.
+//! Upload-Metadata must carry `photo_type` and `filename` (or `file_extension`); admins
+//! may upload on behalf of another account with `user_id`, which answers 404 if no such user exists.
+//!
+//! Once the last byte arrives the file becomes a photo, whose id is returned in `X-Photo-Id`.
+//! If that step fails with a 5xx, an empty PATCH at the final offset retries it.
+
+use axum::{
+    body::Bytes,
+    extract::{State, Path},
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use base64::{Engine as _, engine::general_purpose};
+use sha2::{Sha256, Digest};
+use std::collections::HashMap;
+use uuid::Uuid;
+use tracing::{info, error, warn};
+use crate::{
+    AppState,
+    models::user::{User, UploadSession},
+    services::AuthService,
+    services::photo_service::MAX_PHOTO_SIZE_BYTES,
+    services::upload_service::{UploadService, UploadError},
+    rest::middleware::auth::extract_token,
+};
+
+pub const TUS_VERSION: &str = "1.0.0";
+const TUS_EXTENSIONS: &str = "creation,expiration,checksum,termination";
+const TUS_CHECKSUM_ALGORITHMS: &str = "sha1,sha256";
+/// tus-specific status for a failed `Upload-Checksum` verification.
+const CHECKSUM_MISMATCH: u16 = 460;
+
+pub async fn upload_options() -> Response {
+    let mut response = tus_response(StatusCode::NO_CONTENT);
+    let headers = response.headers_mut();
+    headers.insert("tus-version", HeaderValue::from_static(TUS_VERSION));
+    headers.insert("tus-extension", HeaderValue::from_static(TUS_EXTENSIONS));
+    headers.insert("tus-checksum-algorithm", HeaderValue::from_static(TUS_CHECKSUM_ALGORITHMS));
+    insert_header(headers, "tus-max-size", MAX_PHOTO_SIZE_BYTES);
+    response
+}
+
+pub async fn create_upload(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+) -> Response {
+    if let Some(response) = tus_version_mismatch(&headers) {
+        return response;
+    }
+    let current_user = match authenticate(&app_state, &headers).await {
+        Ok(user) => user,
+        Err(status) => return tus_response(status),
+    };
+    let Some(upload_length) = header_i64(&headers, "upload-length") else {
+        return tus_response(StatusCode::BAD_REQUEST);
+    };
+    if upload_length as usize > MAX_PHOTO_SIZE_BYTES {
+        return tus_response(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+    let metadata = match headers.get("upload-metadata").map(|v| v.to_str()) {
+        Some(Ok(raw)) => match parse_upload_metadata(raw) {
+            Some(metadata) => metadata,
+            None => return tus_response(StatusCode::BAD_REQUEST),
+        },
+        _ => HashMap::new(),
+    };
+    let Some(photo_type) = metadata.get("photo_type").cloned() else {
+        return tus_response(StatusCode::BAD_REQUEST);
+    };
+    let file_extension = metadata.get("file_extension").cloned()
+        .or_else(|| metadata.get("filename").and_then(|f| f.rsplit_once('.')).map(|(_, ext)| ext.to_string()))
+        .unwrap_or_default();
+    let user_id = match metadata.get("user_id").map(|id| Uuid::parse_str(id)) {
+        Some(Ok(user_id)) => user_id,
+        Some(Err(_)) => return tus_response(StatusCode::BAD_REQUEST),
+        None => current_user.id,
+    };
+    if current_user.id != user_id && current_user.role != "admin" {
+        return tus_response(StatusCode::FORBIDDEN);
+    }
+
+    let upload_service = UploadService::new(app_state);
+    match upload_service.create_upload(user_id, photo_type, file_extension, upload_length).await {
+        Ok(session) => {
+            info!("Resumable upload {} created for user {}", session.id, user_id);
+            let mut response = tus_response(StatusCode::CREATED);
+            let headers = response.headers_mut();
+            insert_header(headers, "location", format!("/api/v1/uploads/{}", session.id));
+            insert_session_headers(headers, &session);
+            response
+        }
+        Err(e) => upload_error_response(e),
+    }
+}
+
+pub async fn upload_status(
+    State(app_state): State<AppState>,
+    Path(upload_id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Response {
+    if let Some(response) = tus_version_mismatch(&headers) {
+        return response;
+    }
+    let session = match load_owned_session(&app_state, &headers, upload_id).await {
+        Ok(session) => session,
+        Err(status) => return tus_response(status),
+    };
+    let mut response = tus_response(StatusCode::OK);
+    let headers = response.headers_mut();
+    headers.insert("cache-control", HeaderValue::from_static("no-store"));
+    insert_session_headers(headers, &session);
+    response
+}
+
+pub async fn upload_chunk(
+    State(app_state): State<AppState>,
+    Path(upload_id): Path<Uuid>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if let Some(response) = tus_version_mismatch(&headers) {
+        return response;
+    }
+    if headers.get("content-type").and_then(|v| v.to_str().ok()) != Some("application/offset+octet-stream") {
+        return tus_response(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+    let Some(offset) = header_i64(&headers, "upload-offset") else {
+        return tus_response(StatusCode::BAD_REQUEST);
+    };
+    if let Some(checksum) = headers.get("upload-checksum") {
+        match checksum.to_str().ok().and_then(|c| verify_upload_checksum(c, &body)) {
+            Some(true) => {}
+            Some(false) => {
+                warn!("Checksum mismatch for upload {}", upload_id);
+                return tus_response(StatusCode::from_u16(CHECKSUM_MISMATCH).unwrap_or(StatusCode::BAD_REQUEST));
+            }
+            None => return tus_response(StatusCode::BAD_REQUEST),
+        }
+    }
+    if let Err(status) = load_owned_session(&app_state, &headers, upload_id).await {
+        return tus_response(status);
+    }
+
+    let upload_service = UploadService::new(app_state);
+    match upload_service.append_chunk(upload_id, offset, &body).await {
+        Ok(session) => {
+            let mut response = tus_response(StatusCode::NO_CONTENT);
+            let headers = response.headers_mut();
+            insert_session_headers(headers, &session);
+            response
+        }
+        Err(e) => upload_error_response(e),
+    }
+}
+
+pub async fn terminate_upload(
+    State(app_state): State<AppState>,
+    Path(upload_id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Response {
+    if let Some(response) = tus_version_mismatch(&headers) {
+        return response;
+    }
+    if let Err(status) = load_owned_session(&app_state, &headers, upload_id).await {
+        return tus_response(status);
+    }
+    let upload_service = UploadService::new(app_state);
+    match upload_service.delete_upload(upload_id).await {
+        Ok(true) => tus_response(StatusCode::NO_CONTENT),
+        Ok(false) => tus_response(StatusCode::NOT_FOUND),
+        Err(e) => upload_error_response(e),
+    }
+}
+
+async fn authenticate(app_state: &AppState, headers: &HeaderMap) -> Result<User, StatusCode> {
+    let token = extract_token(headers).ok_or(StatusCode::UNAUTHORIZED)?;
+    let auth_service = AuthService::new(app_state.clone());
+    match auth_service.verify_token(&token).await {
+        Ok(Some(user)) => Ok(user),
+        Ok(None) => Err(StatusCode::UNAUTHORIZED),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+async fn load_owned_session(
+    app_state: &AppState,
+    headers: &HeaderMap,
+    upload_id: Uuid,
+) -> Result<UploadSession, StatusCode> {
+    let current_user = authenticate(app_state, headers).await?;
+    let upload_service = UploadService::new(app_state.clone());
+    match upload_service.get_upload(upload_id).await {
+        Ok(Some(session)) if session.user_id == current_user.id || current_user.role == "admin" => Ok(session),
+        Ok(Some(_)) => Err(StatusCode::FORBIDDEN),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => Err(upload_error_status(e)),
+    }
+}
+
+fn upload_error_response(error: UploadError) -> Response {
+    tus_response(upload_error_status(error))
+}
+
+fn upload_error_status(error: UploadError) -> StatusCode {
+    match &error {
+        UploadError::NotFound | UploadError::UserNotFound => StatusCode::NOT_FOUND,
+        UploadError::OffsetMismatch { .. } => StatusCode::CONFLICT,
+        UploadError::ExceedsLength => StatusCode::PAYLOAD_TOO_LARGE,
+        // tus answers PATCHes to a finished upload with 403.
+        UploadError::Completed => StatusCode::FORBIDDEN,
+        UploadError::Rejected(rejected) => StatusCode::from_u16(rejected.status_code()).unwrap_or(StatusCode::BAD_REQUEST),
+        UploadError::Internal(e) => {
+            error!("Resumable upload failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Every tus request must declare the protocol version it speaks.
+fn tus_version_mismatch(headers: &HeaderMap) -> Option<Response> {
+    if headers.get("tus-resumable").and_then(|v| v.to_str().ok()) == Some(TUS_VERSION) {
+        return None;
+    }
+    let mut response = tus_response(StatusCode::PRECONDITION_FAILED);
+    response.headers_mut().insert("tus-version", HeaderValue::from_static(TUS_VERSION));
+    Some(response)
+}
+
+fn tus_response(status: StatusCode) -> Response {
+    let mut response = status.into_response();
+    response.headers_mut().insert("tus-resumable", HeaderValue::from_static(TUS_VERSION));
+    response
+}
+
+fn insert_session_headers(headers: &mut HeaderMap, session: &UploadSession) {
+    insert_header(headers, "upload-offset", session.upload_offset);
+    insert_header(headers, "upload-length", session.upload_length);
+    insert_header(headers, "upload-expires", session.expires_at.to_rfc2822());
+    if let Some(photo_id) = session.photo_id {
+        insert_header(headers, "x-photo-id", photo_id);
+    }
+}
+
+fn insert_header(headers: &mut HeaderMap, name: &'static str, value: impl ToString) {
+    if let Ok(value) = HeaderValue::from_str(&value.to_string()) {
+        headers.insert(HeaderName::from_static(name), value);
+    }
+}
+
+fn header_i64(headers: &HeaderMap, name: &str) -> Option<i64> {
+    headers.get(name)?.to_str().ok()?.parse().ok().filter(|v: &i64| *v >= 0)
+}
+
+/// Parses `key base64value,key2 base64value2`; values may be omitted.
+pub fn parse_upload_metadata(raw: &str) -> Option<HashMap<String, String>> {
+    let mut metadata = HashMap::new();
+    for pair in raw.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let mut parts = pair.splitn(2, ' ');
+        let key = parts.next()?.to_string();
+        let value = match parts.next() {
+            Some(encoded) => String::from_utf8(general_purpose::STANDARD.decode(encoded.trim()).ok()?).ok()?,
+            None => String::new(),
+        };
+        metadata.insert(key, value);
+    }
+    Some(metadata)
+}
+
+/// Verifies an `Upload-Checksum: <algorithm> <base64 digest>` header. `None` means the header is malformed
+/// or names an unsupported algorithm.
+pub fn verify_upload_checksum(header: &str, body: &[u8]) -> Option<bool> {
+    let (algorithm, encoded) = header.split_once(' ')?;
+    let expected = general_purpose::STANDARD.decode(encoded.trim()).ok()?;
+    let actual = match algorithm {
+        "sha256" => Sha256::digest(body).to_vec(),
+        "sha1" => sha1::Sha1::digest(body).to_vec(),
+        _ => return None,
+    };
+    Some(actual == expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_parse_upload_metadata() {
+        let metadata = parse_upload_metadata("filename aWQuanBn,photo_type ZW1pcmF0ZXNfaWQ=,is_confidential").unwrap();
+        assert_eq!(metadata.get("filename").map(String::as_str), Some("id.jpg"));
+        assert_eq!(metadata.get("photo_type").map(String::as_str), Some("emirates_id"));
+        assert_eq!(metadata.get("is_confidential").map(String::as_str), Some(""));
+        assert!(parse_upload_metadata("filename not-base64!").is_none());
+    }
+    #[test]
+    fn test_verify_upload_checksum() {
+        let body = b"chunk";
+        let digest = general_purpose::STANDARD.encode(Sha256::digest(body));
+        assert_eq!(verify_upload_checksum(&format!("sha256 {}", digest), body), Some(true));
+        assert_eq!(verify_upload_checksum(&format!("sha256 {}", digest), b"other"), Some(false));
+        assert_eq!(verify_upload_checksum(&format!("md5 {}", digest), body), None);
+    }
+    #[test]
+    fn test_upload_error_status() {
+        use crate::services::photo_service::PhotoRejected;
+        assert_eq!(upload_error_status(UploadError::Completed), StatusCode::FORBIDDEN);
+        assert_eq!(upload_error_status(UploadError::UserNotFound), StatusCode::NOT_FOUND);
+        assert_eq!(
+            upload_error_status(UploadError::Rejected(PhotoRejected::InvalidType("selfie".to_string()))),
+            StatusCode::BAD_REQUEST
+        );
+        let rejected = anyhow::Error::from(PhotoRejected::TooLarge).context("Failed to store photo");
+        assert_eq!(upload_error_status(UploadError::from_photo_error(rejected)), StatusCode::PAYLOAD_TOO_LARGE);
+        let failed = anyhow::anyhow!("connection refused");
+        assert_eq!(upload_error_status(UploadError::from_photo_error(failed)), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -16,8 +16,12 @@ use tracing::{info, error};
 use crate::{
     AppState,
     services::{UserService, AuthService, PhotoService},
-    models::user::{CreateUser, UpdateUser, LoginRequest, LoginResponse, User},
+    services::photo_service::rejection_cause,
+    models::user::{CreateUser, UpdateUser, LoginRequest, LoginResponse, User, UserListFilter, UserSortField},
+    models::common::{PaginationQuery, SortOrder},
+    utils::pagination::PagePosition,
     common::response::ApiResponse,
+    common::request_id::current_request_id,
 };
 
 
@@ -44,9 +48,17 @@ pub struct UpdateUserRequest {
 
 #[derive(Debug, Deserialize)]
 pub struct ListUsersQuery {
-    pub page: Option<u32>,
-    pub limit: Option<u32>,
     pub role: Option<String>,
+    pub is_active: Option<bool>,
+    pub email_verified: Option<bool>,
+    pub phone_verified: Option<bool>,
+    pub created_after: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_before: Option<chrono::DateTime<chrono::Utc>>,
+    /// Free-text match on name, email or phone.
+    pub q: Option<String>,
+    /// Comma-separated relations to embed. Photos are embedded when omitted, so pass
+    /// `include=` to skip them or `include=photos` to ask for them explicitly.
+    pub include: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -55,6 +67,8 @@ pub struct ListUsersResponse {
     pub total: u64,
     pub page: u32,
     pub limit: u32,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
 }
 
 // Authentication handlers
@@ -76,14 +90,14 @@ pub async fn register(
 
     match user_service.create_user(create_user).await {
         Ok(user) => {
-            info!("User registered successfully: {}", user.email);
+            info!(user_id = %user.id, email = %user.email, "User registered successfully");
             Ok(Json(ApiResponse {
                 success: true,
                 data: Some(user),
                 error: None,
                 message: "User registered successfully".to_string(),
                 timestamp: chrono::Utc::now(),
-                request_id: None,
+                request_id: current_request_id(),
             }))
         }
         Err(e) => {
@@ -101,14 +115,14 @@ pub async fn login(
 
     match auth_service.login(req).await {
         Ok(Some(login_response)) => {
-            info!("User logged in successfully: {}", login_response.user.email);
+            info!(user_id = %login_response.user.id, email = %login_response.user.email, "User logged in successfully");
             Ok(Json(ApiResponse {
                 success: true,
                 data: Some(login_response),
                 error: None,
                 message: "Login successful".to_string(),
                 timestamp: chrono::Utc::now(),
-                request_id: None,
+                request_id: current_request_id(),
             }))
         }
         Ok(None) => {
@@ -136,7 +150,7 @@ pub async fn validate_token(
                 error: None,
                 message: "Token is valid".to_string(),
                 timestamp: chrono::Utc::now(),
-                request_id: None,
+                request_id: current_request_id(),
             }))
         }
         Ok(None) => {
@@ -173,7 +187,7 @@ pub async fn get_user(
                 error: None,
                 message: "User retrieved successfully".to_string(),
                 timestamp: chrono::Utc::now(),
-                request_id: None,
+                request_id: current_request_id(),
             }))
         }
         Ok(None) => Err(StatusCode::NOT_FOUND),
@@ -217,7 +231,7 @@ pub async fn update_user(
                 error: None,
                 message: "User updated successfully".to_string(),
                 timestamp: chrono::Utc::now(),
-                request_id: None,
+                request_id: current_request_id(),
             }))
         }
         Ok(None) => Err(StatusCode::NOT_FOUND),
@@ -252,7 +266,7 @@ pub async fn delete_user(
                 error: None,
                 message: "User deleted successfully".to_string(),
                 timestamp: chrono::Utc::now(),
-                request_id: None,
+                request_id: current_request_id(),
             }))
         }
         Ok(false) => Err(StatusCode::NOT_FOUND),
@@ -265,6 +279,7 @@ pub async fn delete_user(
 
 pub async fn list_users(
     State(app_state): State<AppState>,
+    Query(pagination): Query<PaginationQuery>,
     Query(query): Query<ListUsersQuery>,
     TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
 ) -> Result<Json<ApiResponse<ListUsersResponse>>, StatusCode> {
@@ -278,23 +293,46 @@ pub async fn list_users(
     if current_user.role != "admin" {
         return Err(StatusCode::FORBIDDEN);
     }
-    let page = query.page.unwrap_or(1);
-    let limit = query.limit.unwrap_or(10);
-    match user_service.list_users(limit, (page - 1) * limit).await {
-        Ok(users) => {
-            let total = users.len() as u64;
+    let page = pagination.page();
+    let limit = pagination.limit_or(10, 100);
+    // `sort_by` is one of `created_at`, `updated_at`, `email`, `first_name`, `last_name`, `role`.
+    let sort_by = match pagination.sort_by.as_deref() {
+        Some(sort_by) => UserSortField::parse(sort_by).ok_or(StatusCode::BAD_REQUEST)?,
+        None => UserSortField::default(),
+    };
+    let sort_order = pagination.sort_order.unwrap_or(SortOrder::Descending);
+    let position = pagination.position(limit).ok_or(StatusCode::BAD_REQUEST)?;
+    if matches!(position, PagePosition::Cursor(_)) && sort_by != UserSortField::CreatedAt {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let filter = UserListFilter {
+        role: query.role,
+        is_active: query.is_active,
+        email_verified: query.email_verified,
+        phone_verified: query.phone_verified,
+        created_after: query.created_after,
+        created_before: query.created_before,
+        search: query.q,
+    };
+    let include_photos = query.include.as_deref()
+        .map(|include| include.split(',').any(|relation| relation.trim() == "photos"))
+        .unwrap_or(true);
+    match user_service.list_users(&filter, sort_by, sort_order, limit, &position, include_photos).await {
+        Ok(result) => {
             Ok(Json(ApiResponse {
                 success: true,
                 data: Some(ListUsersResponse {
-                    users,
-                    total,
+                    users: result.users,
+                    total: result.total,
                     page,
                     limit,
+                    next_cursor: result.next_cursor,
+                    prev_cursor: result.prev_cursor,
                 }),
                 error: None,
                 message: "Users retrieved successfully".to_string(),
                 timestamp: chrono::Utc::now(),
-                request_id: None,
+                request_id: current_request_id(),
             }))
         }
         Err(e) => {
@@ -334,7 +372,7 @@ pub async fn upload_photo(
             }
             "file" => {
                 let filename = field.file_name().unwrap_or("unknown").to_string();
-                if let Some(ext) = filename.split('.').last() {
+                if let Some(ext) = filename.rsplit('.').next() {
                     file_extension = ext.to_string();
                 }
                 photo_data = field.bytes().await.map_err(|_| StatusCode::BAD_REQUEST)?.to_vec();
@@ -356,12 +394,18 @@ pub async fn upload_photo(
                 error: None,
                 message: "Photo uploaded successfully".to_string(),
                 timestamp: chrono::Utc::now(),
-                request_id: None,
+                request_id: current_request_id(),
             }))
         }
-        Err(e) => {
-            error!("Failed to upload photo: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
+        Err(e) => match rejection_cause(&e) {
+            Some(rejected) => {
+                info!("Photo upload rejected for user {}: {}", user_id, rejected);
+                Err(StatusCode::from_u16(rejected.status_code()).unwrap_or(StatusCode::BAD_REQUEST))
+            }
+            None => {
+                error!("Failed to upload photo: {}", e);
+                Err(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        },
     }
 }
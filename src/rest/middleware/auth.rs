@@ -11,7 +11,7 @@ use std::task::{Context, Poll};
 use tracing::{info, warn};
 
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct AuthLayer;
 
 impl AuthLayer {
@@ -45,10 +45,7 @@ where
     }
 
     fn call(&mut self, request: Request) -> Self::Future {
-        let future = self.inner.call(request);
-        Box::pin(async move {
-            future.await
-        })
+        Box::pin(self.inner.call(request))
     }
 }
 
@@ -56,13 +53,8 @@ pub fn extract_token(headers: &HeaderMap) -> Option<String> {
     headers
         .get("authorization")
         .and_then(|header| header.to_str().ok())
-        .and_then(|header| {
-            if header.starts_with("Bearer ") {
-                Some(header[7..].to_string())
-            } else {
-                None
-            }
-        })
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .map(|token| token.to_string())
 }
 
 pub fn validate_token(token: &str) -> Result<Claims, AuthError> {
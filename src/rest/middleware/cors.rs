@@ -1,8 +1,9 @@
 //! CORS middleware configuration
 
 use axum::http::{HeaderValue, HeaderName, Method};
-use tower_http::cors::{Any, CorsLayer};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
 use std::time::Duration;
+use crate::config::SharedConfig;
 
 
 pub fn setup_cors() -> CorsLayer {
@@ -26,6 +27,21 @@ pub fn setup_cors() -> CorsLayer {
 }
 
 
+/// Permissive CORS restricted to `cors.allowed_origins`, checked against the live config
+/// on every request so a reload takes effect immediately. An empty list allows any origin.
+pub fn setup_cors_from_config(config: SharedConfig) -> CorsLayer {
+    CorsLayer::permissive()
+        .allow_origin(AllowOrigin::predicate(move |origin: &HeaderValue, _| {
+            origin_allowed(&config, origin.as_bytes())
+        }))
+}
+
+/// Whether `cors.allowed_origins` currently admits `origin`.
+pub fn origin_allowed(config: &SharedConfig, origin: &[u8]) -> bool {
+    let allowed = &config.current().cors.allowed_origins;
+    allowed.is_empty() || allowed.iter().any(|allowed| allowed.as_bytes() == origin)
+}
+
 pub fn setup_cors_dev() -> CorsLayer {
     CorsLayer::permissive()
 }
@@ -0,0 +1,105 @@
+//! Answers 503 on routes whose backend is unavailable
+//!
+//! Applied with `route_layer` to a group of routes. While the check reports a missing
+//! dependency, requests are rejected with a `SERVICE_UNAVAILABLE` error naming it instead of
+//! reaching handlers that would fail or hang on the backend. The reason stays in the log and
+//! `/admin/health`; clients only learn which dependency is down.
+
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use axum::{
+    extract::Request,
+    response::{IntoResponse, Response},
+};
+use tower::{Layer, Service};
+
+use crate::common::dependencies::DependencyUnavailable;
+use crate::common::response::{error_codes, ApiResponse};
+
+type Check = Arc<dyn Fn() -> Result<(), DependencyUnavailable> + Send + Sync>;
+
+#[derive(Clone)]
+pub struct DependencyGuardLayer {
+    check: Check,
+}
+
+impl DependencyGuardLayer {
+    pub fn new(check: impl Fn() -> Result<(), DependencyUnavailable> + Send + Sync + 'static) -> Self {
+        Self { check: Arc::new(check) }
+    }
+}
+
+impl<S> Layer<S> for DependencyGuardLayer {
+    type Service = DependencyGuardMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        DependencyGuardMiddleware { inner, check: self.check.clone() }
+    }
+}
+
+#[derive(Clone)]
+pub struct DependencyGuardMiddleware<S> {
+    inner: S,
+    check: Check,
+}
+
+impl<S> Service<Request> for DependencyGuardMiddleware<S>
+where
+    S: Service<Request, Response = Response> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = std::pin::Pin<Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        match (self.check)() {
+            Ok(()) => Box::pin(self.inner.call(request)),
+            Err(e) => Box::pin(async move {
+                tracing::warn!(dependency = e.dependency, "Rejecting {} {}: {}", request.method(), request.uri().path(), e);
+                let message = format!("{} is temporarily unavailable", e.dependency);
+                Ok(ApiResponse::error(error_codes::SERVICE_UNAVAILABLE, message).into_response())
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::StatusCode, routing::get, Json, Router};
+    use tower::ServiceExt;
+
+    use crate::common::dependencies::{Dependencies, MONGODB};
+
+    #[tokio::test]
+    async fn test_guarded_routes_answer_503_while_unavailable() {
+        let dependencies = Dependencies::default();
+        let guard = {
+            let dependencies = dependencies.clone();
+            DependencyGuardLayer::new(move || dependencies.check(MONGODB))
+        };
+        let app = Router::new()
+            .route("/photos", get(|| async { Json(ApiResponse::success((), "ok")) }))
+            .route_layer(guard)
+            .route("/users", get(|| async { Json(ApiResponse::success((), "ok")) }));
+        let status = |path: &'static str| {
+            let app = app.clone();
+            async move { app.oneshot(Request::get(path).body(Body::empty()).unwrap()).await.unwrap() }
+        };
+
+        assert_eq!(status("/photos").await.status(), StatusCode::OK);
+        dependencies.mark_unavailable(MONGODB, "connection refused");
+        let response = status("/photos").await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: ApiResponse<()> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body.error.unwrap().message, "mongodb is temporarily unavailable");
+        assert_eq!(status("/users").await.status(), StatusCode::OK);
+    }
+}
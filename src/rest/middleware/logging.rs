@@ -9,8 +9,12 @@ use std::task::{Context, Poll};
 use tracing::{info};
 use std::time::Instant;
 
+use super::request_id::RequestId;
 
-#[derive(Clone)]
+
+/// Writes one access log line per request. Only the path is logged: query strings can carry
+/// credentials such as photo URL signatures. Place it inside `RequestIdLayer`.
+#[derive(Clone, Default)]
 pub struct RequestLoggingLayer;
 
 impl RequestLoggingLayer {
@@ -47,7 +51,8 @@ where
 
     fn call(&mut self, request: Request) -> Self::Future {
         let method = request.method().clone();
-        let uri = request.uri().clone();
+        let path = request.uri().path().to_string();
+        let request_id = request.extensions().get::<RequestId>().map(|id| id.0.clone()).unwrap_or_default();
         let start_time = Instant::now();
 
         let future = self.inner.call(request);
@@ -58,9 +63,10 @@ where
 
             info!(
                 method = %method,
-                uri = %uri,
+                path = %path,
                 status = %response.status(),
                 duration_ms = %duration.as_millis(),
+                request_id = %request_id,
                 "HTTP request completed"
             );
 
@@ -0,0 +1,87 @@
+//! Request metrics middleware
+
+use axum::{
+    extract::{MatchedPath, Request},
+    response::Response,
+};
+use tower::{Layer, Service};
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use crate::metrics::{HTTP_REQUESTS, HTTP_REQUEST_DURATION};
+
+/// Counts requests and records their latency per route template. Requests that match no
+/// route share the `unmatched` label so arbitrary paths cannot create new series.
+#[derive(Clone, Default)]
+pub struct MetricsLayer;
+
+impl MetricsLayer {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<S> Layer<S> for MetricsLayer {
+    type Service = MetricsMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MetricsMiddleware { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct MetricsMiddleware<S> {
+    inner: S,
+}
+
+impl<S> Service<Request> for MetricsMiddleware<S>
+where
+    S: Service<Request, Response = Response> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = std::pin::Pin<Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let method = request.method().to_string();
+        let route = request.extensions().get::<MatchedPath>()
+            .map(|path| path.as_str().to_string())
+            .unwrap_or_else(|| "unmatched".to_string());
+        let start_time = Instant::now();
+
+        let future = self.inner.call(request);
+
+        Box::pin(async move {
+            let response = future.await?;
+            HTTP_REQUEST_DURATION.with_label_values(&[&method, &route])
+                .observe(start_time.elapsed().as_secs_f64());
+            HTTP_REQUESTS.with_label_values(&[&method, &route, response.status().as_str()]).inc();
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, routing::get, Router};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_requests_are_counted_by_route_template() {
+        let app = Router::new()
+            .route("/metrics-test/:id", get(|| async { "ok" }))
+            .layer(MetricsLayer::new());
+        for path in ["/metrics-test/1", "/metrics-test/2"] {
+            let response = app.clone().oneshot(Request::get(path).body(Body::empty()).unwrap()).await.unwrap();
+            assert_eq!(response.status(), 200);
+        }
+        assert_eq!(HTTP_REQUESTS.with_label_values(&["GET", "/metrics-test/:id", "200"]).get(), 2);
+        assert_eq!(HTTP_REQUEST_DURATION.with_label_values(&["GET", "/metrics-test/:id"]).get_sample_count(), 2);
+    }
+}
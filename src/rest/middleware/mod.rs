@@ -3,8 +3,17 @@
 pub mod auth;
 pub mod logging;
 pub mod cors;
+pub mod rate_limit;
+pub mod metrics;
+pub mod trace_context;
+pub mod request_id;
+pub mod dependency;
 
 
 pub use auth::AuthLayer;
 pub use logging::RequestLoggingLayer;
 pub use cors::setup_cors;
+pub use rate_limit::RateLimitLayer;
+pub use metrics::MetricsLayer;
+pub use request_id::RequestIdLayer;
+pub use dependency::DependencyGuardLayer;
@@ -0,0 +1,116 @@
+//! Per-client rate limiting middleware
+
+use axum::{
+    extract::{ConnectInfo, Request},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use tower::{Layer, Service};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Instant;
+use crate::config::{RateLimitConfig, SharedConfig};
+
+/// Buckets idle for this long are forgotten once the table grows past `MAX_TRACKED_CLIENTS`.
+const IDLE_BUCKET_SECONDS: f64 = 300.0;
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Token bucket per client address. Limits are read from the live config on every request.
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    config: SharedConfig,
+    buckets: Arc<Mutex<HashMap<IpAddr, Bucket>>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimitLayer {
+    pub fn new(config: SharedConfig) -> Self {
+        Self {
+            config,
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn allow(&self, client: IpAddr, limits: &RateLimitConfig, now: Instant) -> bool {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() > MAX_TRACKED_CLIENTS {
+            buckets.retain(|_, bucket| now.duration_since(bucket.refilled_at).as_secs_f64() < IDLE_BUCKET_SECONDS);
+        }
+        let burst = limits.burst as f64;
+        let bucket = buckets.entry(client).or_insert(Bucket { tokens: burst, refilled_at: now });
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * limits.requests_per_second as f64).min(burst);
+        bucket.refilled_at = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitMiddleware { inner, limiter: self.clone() }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitMiddleware<S> {
+    inner: S,
+    limiter: RateLimitLayer,
+}
+
+impl<S> Service<Request> for RateLimitMiddleware<S>
+where
+    S: Service<Request, Response = Response> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = std::pin::Pin<Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let limits = self.limiter.config.current().rate_limit.clone();
+        let client = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|info| info.0.ip());
+        if let (true, Some(client)) = (limits.requests_per_second > 0, client) {
+            if !self.limiter.allow(client, &limits, Instant::now()) {
+                return Box::pin(async { Ok(StatusCode::TOO_MANY_REQUESTS.into_response()) });
+            }
+        }
+        Box::pin(self.inner.call(request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_token_bucket() {
+        let limiter = RateLimitLayer::new(SharedConfig::new(crate::config::Config::default()));
+        let limits = RateLimitConfig { requests_per_second: 2, burst: 3 };
+        let client: IpAddr = "10.0.0.1".parse().unwrap();
+        let start = Instant::now();
+        assert!((0..3).all(|_| limiter.allow(client, &limits, start)));
+        assert!(!limiter.allow(client, &limits, start));
+        assert!(limiter.allow("10.0.0.2".parse().unwrap(), &limits, start));
+        assert!(limiter.allow(client, &limits, start + Duration::from_millis(500)));
+        assert!(!limiter.allow(client, &limits, start + Duration::from_millis(500)));
+    }
+}
@@ -0,0 +1,107 @@
+//! Request ID middleware
+
+use axum::{
+    extract::Request,
+    http::HeaderValue,
+    response::Response,
+};
+use tower::{Layer, Service};
+use std::task::{Context, Poll};
+
+use crate::common::request_id::{self, REQUEST_ID_HEADER};
+
+/// The request's ID, also stored in the request extensions for handlers that want it directly.
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+/// Accepts or generates `X-Request-Id`, records it on the request span, makes it the
+/// `ApiResponse.request_id` of everything the request returns and echoes it in the response.
+/// Must sit inside the `TraceLayer` so the request span is current.
+#[derive(Clone, Default)]
+pub struct RequestIdLayer;
+
+impl RequestIdLayer {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<S> Layer<S> for RequestIdLayer {
+    type Service = RequestIdMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestIdMiddleware { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct RequestIdMiddleware<S> {
+    inner: S,
+}
+
+impl<S> Service<Request> for RequestIdMiddleware<S>
+where
+    S: Service<Request, Response = Response> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = std::pin::Pin<Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request) -> Self::Future {
+        let incoming = request.headers().get(REQUEST_ID_HEADER).and_then(|value| value.to_str().ok());
+        let id = request_id::accept_or_generate(incoming);
+        tracing::Span::current().record("request_id", id.as_str());
+        request.extensions_mut().insert(RequestId(id.clone()));
+
+        let future = request_id::scope(id.clone(), self.inner.call(request));
+
+        Box::pin(async move {
+            let mut response = future.await?;
+            // Accepted and generated IDs are both valid header values.
+            if let Ok(value) = HeaderValue::from_str(&id) {
+                response.headers_mut().insert(REQUEST_ID_HEADER, value);
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, routing::get, Json, Router};
+    use tower::ServiceExt;
+
+    use crate::common::response::ApiResponse;
+
+    async fn call(app: Router, request_id: Option<&str>) -> (String, ApiResponse<()>) {
+        let mut request = Request::get("/ping");
+        if let Some(id) = request_id {
+            request = request.header(REQUEST_ID_HEADER, id);
+        }
+        let response = app.oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+        let header = response.headers()[REQUEST_ID_HEADER].to_str().unwrap().to_string();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (header, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_request_id_is_accepted_or_generated() {
+        let app = Router::new()
+            .route("/ping", get(|| async { Json(ApiResponse::success((), "pong")) }))
+            .layer(RequestIdLayer::new());
+
+        let (header, body) = call(app.clone(), Some("client-chosen-id")).await;
+        assert_eq!(header, "client-chosen-id");
+        assert_eq!(body.request_id.as_deref(), Some("client-chosen-id"));
+
+        let (header, body) = call(app, None).await;
+        assert!(uuid::Uuid::parse_str(&header).is_ok());
+        assert_eq!(body.request_id, Some(header));
+    }
+}
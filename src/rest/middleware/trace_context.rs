@@ -0,0 +1,70 @@
+//! Request spans that continue the caller's trace
+//!
+//! Hooks for `tower_http::trace::TraceLayer`: each request gets an `HTTP request` span whose
+//! parent is the W3C `traceparent` sent by the client, if any.
+
+use std::time::Duration;
+
+use axum::extract::MatchedPath;
+use axum::http::{Request, Response};
+use tracing::{field::Empty, info_span, Span};
+
+use crate::telemetry;
+
+pub fn make_span<B>(request: &Request<B>) -> Span {
+    let route = request.extensions().get::<MatchedPath>()
+        .map(|path| path.as_str())
+        .unwrap_or_else(|| request.uri().path());
+    let span = info_span!(
+        "HTTP request",
+        otel.name = %format!("{} {}", request.method(), route),
+        otel.kind = "server",
+        otel.status_code = Empty,
+        http.method = %request.method(),
+        http.route = %route,
+        http.status_code = Empty,
+        request_id = Empty,
+    );
+    let header = |name: &str| request.headers().get(name).and_then(|value| value.to_str().ok());
+    telemetry::set_remote_parent(&span, header("traceparent"), header("tracestate"));
+    span
+}
+
+pub fn record_response<B>(response: &Response<B>, _latency: Duration, span: &Span) {
+    span.record("http.status_code", response.status().as_u16());
+    if response.status().is_server_error() {
+        span.record("otel.status_code", "ERROR");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, routing::get, Json, Router};
+    use opentelemetry::trace::TracerProvider as _;
+    use tower::ServiceExt;
+    use tower_http::trace::TraceLayer;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use crate::common::response::ApiResponse;
+
+    #[tokio::test]
+    async fn test_request_id_continues_remote_trace() {
+        let provider = opentelemetry_sdk::trace::TracerProvider::builder().build();
+        let tracer = provider.tracer("test");
+        let subscriber = tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        let app = Router::new()
+            .route("/ping", get(|| async { Json(ApiResponse::success((), "pong")) }))
+            .layer(TraceLayer::new_for_http().make_span_with(make_span).on_response(record_response));
+        let request = Request::get("/ping")
+            .header("traceparent", "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: ApiResponse<()> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body.request_id.as_deref(), Some("4bf92f3577b34da6a3ce929d0e0e4736"));
+    }
+}
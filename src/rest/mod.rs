@@ -5,38 +5,73 @@ use axum::{
     routing::{get},
     Router,
 };
+use axum_server::tls_rustls::RustlsConfig;
 use std::net::SocketAddr;
 use tower::ServiceBuilder;
-use tower_http::{
-    cors::CorsLayer,
-    trace::TraceLayer,
-};
+use tower_http::trace::TraceLayer;
 use tracing::info;
 
+use crate::tls::{ClientAuth, ReloadingServerConfig};
+
 pub mod handlers;
 pub mod middleware;
 pub mod routes;
 
 
 pub fn create_router(app_state: crate::AppState) -> Router {
+    let config = app_state.config.clone();
     Router::new()
-        .route("/health", get(handlers::health::health_check))
-        .nest("/api/v1", routes::v1::create_v1_routes())
+        .route("/health", get(handlers::health::readiness))
+        .route("/health/live", get(handlers::health::liveness))
+        .route("/health/ready", get(handlers::health::readiness))
+        .route("/metrics", get(handlers::metrics::metrics))
+        .nest("/api/v1", routes::v1::create_v1_routes(&app_state))
         .layer(
             ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
-                .layer(CorsLayer::permissive())
+                .layer(TraceLayer::new_for_http()
+                    .make_span_with(middleware::trace_context::make_span)
+                    .on_response(middleware::trace_context::record_response))
+                .layer(middleware::RequestIdLayer::new())
+                .layer(middleware::RequestLoggingLayer::new())
+                .layer(middleware::MetricsLayer::new())
+                .layer(middleware::cors::setup_cors_from_config(config.clone()))
+                .layer(middleware::RateLimitLayer::new(config))
                 .layer(middleware::auth::AuthLayer::new())
         )
         .with_state(app_state)
 }
+/// Serves until `shutdown` resolves, then stops accepting connections and returns once every
+/// in-flight request has completed. Serves HTTPS when `tls.enabled` is set.
 pub async fn start_rest_server(
     addr: SocketAddr,
     app_state: crate::AppState,
+    shutdown: impl std::future::Future<Output = ()> + Send + 'static,
 ) -> Result<()> {
     info!("Starting REST API server on {}", addr);
+    let tls_settings = app_state.config.current().tls.clone();
+    let tls_shutdown = app_state.shutdown.clone();
     let app = create_router(app_state);
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    // Client addresses are needed for per-client rate limiting.
+    let make_service = app.into_make_service_with_connect_info::<SocketAddr>();
+    if tls_settings.enabled {
+        let tls = ReloadingServerConfig::load(&tls_settings, ClientAuth::None, crate::tls::REST_ALPN)?;
+        let rustls_config = RustlsConfig::from_config(tls.current());
+        let reloaded = rustls_config.clone();
+        tls.spawn_reload_task("REST", tls_shutdown, move |server_config| reloaded.reload_from_config(server_config));
+        let handle = axum_server::Handle::new();
+        let graceful = handle.clone();
+        tokio::spawn(async move {
+            shutdown.await;
+            graceful.graceful_shutdown(None);
+        });
+        info!("REST API server using TLS certificate {}", tls_settings.cert_file);
+        axum_server::bind_rustls(addr, rustls_config).handle(handle).serve(make_service).await?;
+    } else {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        axum::serve(listener, make_service)
+            .with_graceful_shutdown(shutdown)
+            .await?;
+    }
+    info!("REST server drained");
     Ok(())
 }
@@ -1,17 +1,44 @@
 //! API v1 routes
 
 use axum::{
-    routing::{get, post, put, delete},
+    extract::DefaultBodyLimit,
+    routing::{get, post, put, delete, head},
     Router,
 };
 use crate::{
     rest::handlers::user::{
         register, login, validate_token, get_user, update_user, delete_user, list_users, upload_photo
     },
+    rest::handlers::photo::{
+        download_photo, get_photo_url, list_possible_duplicates, reject_photo,
+        list_quarantined_photos, release_photo,
+    },
+    rest::handlers::admin::{get_effective_config, get_health_details},
+    rest::handlers::upload::{upload_options, create_upload, upload_status, upload_chunk, terminate_upload},
+    rest::middleware::DependencyGuardLayer,
+    services::photo_service::MAX_PHOTO_SIZE_BYTES,
+    services::PhotoStorage,
 };
 
 
-pub fn create_v1_routes() -> Router<crate::AppState> {
+pub fn create_v1_routes(app_state: &crate::AppState) -> Router<crate::AppState> {
+    // Routes that read or write photo blobs answer 503 while the storage backend is unavailable.
+    let storage_state = app_state.clone();
+    let photo_storage_routes = Router::new()
+        .route("/users/:user_id/photo", post(upload_photo))
+        .route("/users/:user_id/photos/:photo_id/url", get(get_photo_url))
+        .route("/photos/:photo_id", get(download_photo))
+        .route("/admin/photos/:photo_id/release", post(release_photo))
+        .route("/uploads", post(create_upload).options(upload_options))
+        .route(
+            "/uploads/:upload_id",
+            head(upload_status)
+                .patch(upload_chunk)
+                .delete(terminate_upload)
+                .layer(DefaultBodyLimit::max(MAX_PHOTO_SIZE_BYTES)),
+        )
+        .route_layer(DependencyGuardLayer::new(move || PhotoStorage::ensure_available(&storage_state)));
+
     Router::new()
 
         .route("/auth/register", post(register))
@@ -25,5 +52,11 @@ pub fn create_v1_routes() -> Router<crate::AppState> {
         .route("/users/:user_id", delete(delete_user))
 
 
-        .route("/users/:user_id/photo", post(upload_photo))
+        .route("/admin/photos/duplicates", get(list_possible_duplicates))
+        .route("/admin/photos/:photo_id/reject", post(reject_photo))
+        .route("/admin/photos/quarantine", get(list_quarantined_photos))
+        .route("/admin/config", get(get_effective_config))
+        .route("/admin/health", get(get_health_details))
+
+        .merge(photo_storage_routes)
 }
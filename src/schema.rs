@@ -27,6 +27,14 @@ diesel::table! {
         is_verified -> Bool,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        content_sha256 -> Nullable<Varchar>,
+        perceptual_hash -> Nullable<Int8>,
+        needs_review -> Bool,
+        duplicate_of -> Nullable<Uuid>,
+        status -> Varchar,
+        archived_at -> Nullable<Timestamptz>,
+        scan_verdict -> Nullable<Varchar>,
+        scan_detail -> Nullable<Text>,
     }
 }
 
@@ -53,13 +61,69 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    upload_sessions (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        photo_type -> Varchar,
+        file_extension -> Varchar,
+        upload_length -> Int8,
+        upload_offset -> Int8,
+        photo_id -> Nullable<Uuid>,
+        expires_at -> Timestamptz,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    upload_chunks (session_id, chunk_offset) {
+        session_id -> Uuid,
+        chunk_offset -> Int8,
+        data -> Bytea,
+    }
+}
+
+diesel::table! {
+    photo_blob_outbox (id) {
+        id -> Uuid,
+        storage_id -> Varchar,
+        attempts -> Int4,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        storage_area -> Varchar,
+    }
+}
+
+diesel::table! {
+    photo_retention_log (id) {
+        id -> Uuid,
+        run_id -> Uuid,
+        photo_id -> Uuid,
+        user_id -> Uuid,
+        photo_type -> Varchar,
+        photo_status -> Varchar,
+        photo_url -> Varchar,
+        archived_at -> Nullable<Timestamptz>,
+        purged_at -> Timestamptz,
+    }
+}
+
 diesel::joinable!(user_photos -> users (user_id));
 diesel::joinable!(verification_codes -> users (user_id));
 diesel::joinable!(refresh_tokens -> users (user_id));
+diesel::joinable!(upload_sessions -> users (user_id));
+diesel::joinable!(upload_sessions -> user_photos (photo_id));
+diesel::joinable!(upload_chunks -> upload_sessions (session_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     users,
     user_photos,
     verification_codes,
     refresh_tokens,
+    upload_sessions,
+    upload_chunks,
+    photo_blob_outbox,
+    photo_retention_log,
 );
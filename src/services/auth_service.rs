@@ -8,6 +8,7 @@ use serde::{Deserialize, Serialize};
 use chrono::{Utc, Duration};
 use crate::models::user::{User, LoginRequest, LoginResponse};
 use crate::services::UserService;
+use crate::database::postgres::with_connection;
 use crate::AppState;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -30,6 +31,17 @@ impl AuthService {
     }
 
     pub async fn login(&self, login_request: LoginRequest) -> Result<Option<LoginResponse>> {
+        let result = self.check_login(login_request).await;
+        let label = match &result {
+            Ok(Some(_)) => "success",
+            Ok(None) => "failure",
+            Err(_) => "error",
+        };
+        crate::metrics::AUTH_LOGINS.with_label_values(&[label]).inc();
+        result
+    }
+
+    async fn check_login(&self, login_request: LoginRequest) -> Result<Option<LoginResponse>> {
         let user = self.user_service.get_user_by_email(&login_request.email).await?;
         if let Some(user) = user {
             if self.verify_password(&login_request.password, &user).await? {
@@ -46,8 +58,8 @@ impl AuthService {
     }
 
     pub async fn verify_token(&self, token: &str) -> Result<Option<User>> {
-        let jwt_secret = self.app_state.config.jwt_secret.as_bytes();
-        let decoding_key = DecodingKey::from_secret(jwt_secret);
+        let config = self.app_state.config.current();
+        let decoding_key = DecodingKey::from_secret(config.jwt_secret.expose().as_bytes());
         let validation = Validation::new(Algorithm::HS256);
         match decode::<Claims>(token, &decoding_key, &validation) {
             Ok(token_data) => {
@@ -73,22 +85,19 @@ impl AuthService {
     }
 
     async fn verify_password(&self, password: &str, user: &User) -> Result<bool> {
-        let mut conn = crate::database::postgres::get_connection(&self.app_state.postgres_pool)
-            .context("Failed to get database connection")?;
         use crate::schema::users;
-        use crate::models::db_models::DbUser;
         use diesel::prelude::*;
-        let db_user = users::table
-            .find(user.id)
-            .first::<DbUser>(&mut conn)
-            .optional()
-            .context("Failed to query user from database")?;
-
-        match db_user {
-            Some(db_user) => {
-                verify(password, &db_user.password_hash)
-                    .context("Failed to verify password")
-            }
+        let user_id = user.id;
+        let password_hash = with_connection(&self.app_state.postgres_pool, move |conn| {
+            users::table
+                .find(user_id)
+                .select(users::password_hash)
+                .first::<String>(conn)
+                .optional()
+                .context("Failed to query user from database")
+        }).await?;
+        match password_hash {
+            Some(password_hash) => verify_password_hash(password, password_hash).await,
             None => Ok(false),
         }
     }
@@ -101,33 +110,56 @@ impl AuthService {
             exp: expires_at.timestamp() as usize,
             iat: now.timestamp() as usize,
         };
-        let jwt_secret = self.app_state.config.jwt_secret.as_bytes();
-        let encoding_key = EncodingKey::from_secret(jwt_secret);
+        let config = self.app_state.config.current();
+        let encoding_key = EncodingKey::from_secret(config.jwt_secret.expose().as_bytes());
         encode(&Header::default(), &claims, &encoding_key)
             .context("Failed to generate JWT token")
     }
 
-    pub fn hash_password(&self, password: &str) -> Result<String> {
-        hash(password, DEFAULT_COST)
-            .context("Failed to hash password")
-    }
     pub async fn change_password(&self, user_id: Uuid, old_password: &str, new_password: &str) -> Result<bool> {
         if let Some(user) = self.user_service.get_user(user_id).await? {
             if self.verify_password(old_password, &user).await? {
-                let new_hash = self.hash_password(new_password)?;
-                use crate::models::user::UpdateUser;
-                let update_data = UpdateUser {
-                    email: None,
-                    country_code: None,
-                    phone: None,
-                    first_name: None,
-                    last_name: None,
-                    is_active: None,
-                };
-                return Ok(true);
+                return self.reset_password(user_id, new_password).await;
             }
         }
 
         Ok(false)
     }
+
+    /// Sets a new password without checking the old one; for administrative resets.
+    /// Returns `false` when the user does not exist.
+    pub async fn reset_password(&self, user_id: Uuid, new_password: &str) -> Result<bool> {
+        let new_hash = hash_password(new_password).await?;
+        let updated = with_connection(&self.app_state.postgres_pool, move |conn| {
+            use crate::schema::users;
+            use diesel::prelude::*;
+            diesel::update(users::table.find(user_id))
+                .set((
+                    users::password_hash.eq(new_hash),
+                    users::updated_at.eq(Utc::now()),
+                ))
+                .execute(conn)
+                .context("Failed to update password in database")
+        }).await?;
+        Ok(updated > 0)
+    }
+}
+
+/// bcrypt is deliberately slow, so it runs on a blocking thread of its own rather than inside
+/// `with_connection`, where it would keep a pooled connection checked out.
+pub async fn hash_password(password: &str) -> Result<String> {
+    let password = password.to_string();
+    tokio::task::spawn_blocking(move || hash(password, DEFAULT_COST))
+        .await
+        .context("Password hashing task failed")?
+        .context("Failed to hash password")
+}
+
+/// Checks `password` against a stored hash, off the connection pool like `hash_password`.
+async fn verify_password_hash(password: &str, password_hash: String) -> Result<bool> {
+    let password = password.to_string();
+    tokio::task::spawn_blocking(move || verify(password, &password_hash))
+        .await
+        .context("Password verification task failed")?
+        .context("Failed to verify password")
 }
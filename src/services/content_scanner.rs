@@ -0,0 +1,155 @@
+//! Upload content scanning (ClamAV or no-op)
+
+use anyhow::{Result, Context};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::config::ScannerConfig;
+
+/// clamd rejects INSTREAM chunks above its StreamMaxLength; 64 KiB is well inside every default.
+const CLAMD_CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanVerdict {
+    Clean,
+    /// Carries the signature name reported by the scanner.
+    Infected(String),
+    /// The scanner could not decide; a human has to look at the file.
+    NeedsReview(String),
+}
+
+impl ScanVerdict {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Clean => "clean",
+            Self::Infected(_) => "infected",
+            Self::NeedsReview(_) => "needs_review",
+        }
+    }
+
+    pub fn detail(&self) -> Option<&str> {
+        match self {
+            Self::Clean => None,
+            Self::Infected(detail) | Self::NeedsReview(detail) => Some(detail),
+        }
+    }
+
+    pub fn is_clean(&self) -> bool {
+        matches!(self, Self::Clean)
+    }
+}
+
+#[tonic::async_trait]
+pub trait ContentScanner: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn scan(&self, data: &[u8]) -> Result<ScanVerdict>;
+}
+
+/// Accepts everything. Used when no scanner is configured.
+pub struct NoopScanner;
+
+#[tonic::async_trait]
+impl ContentScanner for NoopScanner {
+    fn name(&self) -> &'static str {
+        "none"
+    }
+
+    async fn scan(&self, _data: &[u8]) -> Result<ScanVerdict> {
+        Ok(ScanVerdict::Clean)
+    }
+}
+
+/// Talks the clamd `INSTREAM` protocol over TCP (`host:port`) or a Unix socket (absolute path).
+pub struct ClamAvScanner {
+    address: String,
+    timeout: Duration,
+}
+
+impl ClamAvScanner {
+    pub fn new(address: String, timeout: Duration) -> Self {
+        Self { address, timeout }
+    }
+
+    async fn scan_over<S>(mut stream: S, data: &[u8]) -> Result<ScanVerdict>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        stream.write_all(b"zINSTREAM\0").await?;
+        for chunk in data.chunks(CLAMD_CHUNK_SIZE) {
+            stream.write_all(&(chunk.len() as u32).to_be_bytes()).await?;
+            stream.write_all(chunk).await?;
+        }
+        stream.write_all(&0u32.to_be_bytes()).await?;
+        stream.flush().await?;
+        let mut reply = Vec::new();
+        stream.read_to_end(&mut reply).await?;
+        parse_clamd_reply(&String::from_utf8_lossy(&reply))
+    }
+}
+
+#[tonic::async_trait]
+impl ContentScanner for ClamAvScanner {
+    fn name(&self) -> &'static str {
+        "clamav"
+    }
+
+    async fn scan(&self, data: &[u8]) -> Result<ScanVerdict> {
+        let scan = async {
+            if self.address.starts_with('/') {
+                #[cfg(unix)]
+                {
+                    let stream = tokio::net::UnixStream::connect(&self.address).await
+                        .context("Failed to connect to clamd socket")?;
+                    return Self::scan_over(stream, data).await;
+                }
+                #[cfg(not(unix))]
+                return Err(anyhow::anyhow!("Unix sockets are not supported on this platform"));
+            }
+            let stream = tokio::net::TcpStream::connect(&self.address).await
+                .context("Failed to connect to clamd")?;
+            Self::scan_over(stream, data).await
+        };
+        tokio::time::timeout(self.timeout, scan).await
+            .map_err(|_| anyhow::anyhow!("clamd scan timed out"))?
+    }
+}
+
+pub fn scanner_from_config(config: &ScannerConfig) -> Result<Box<dyn ContentScanner>> {
+    match config.backend.as_str() {
+        "none" => Ok(Box::new(NoopScanner)),
+        "clamav" => Ok(Box::new(ClamAvScanner::new(
+            config.clamav_address.clone(),
+            Duration::from_secs(config.timeout_seconds),
+        ))),
+        other => Err(anyhow::anyhow!("Unsupported content scanner: {}", other)),
+    }
+}
+
+/// Interprets a clamd reply such as `stream: OK` or `stream: Eicar-Signature FOUND`.
+pub fn parse_clamd_reply(reply: &str) -> Result<ScanVerdict> {
+    let reply = reply.trim_end_matches(['\0', '\n', ' ']);
+    let result = reply.strip_prefix("stream: ").unwrap_or(reply);
+    if result == "OK" {
+        Ok(ScanVerdict::Clean)
+    } else if let Some(signature) = result.strip_suffix(" FOUND") {
+        Ok(ScanVerdict::Infected(signature.to_string()))
+    } else {
+        Err(anyhow::anyhow!("clamd error: {}", result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_clamd_reply() {
+        assert_eq!(parse_clamd_reply("stream: OK\0").unwrap(), ScanVerdict::Clean);
+        assert_eq!(
+            parse_clamd_reply("stream: Win.Test.EICAR_HDB-1 FOUND\0").unwrap(),
+            ScanVerdict::Infected("Win.Test.EICAR_HDB-1".to_string())
+        );
+        assert!(parse_clamd_reply("INSTREAM size limit exceeded. ERROR\0").is_err());
+    }
+}
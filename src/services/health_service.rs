@@ -0,0 +1,381 @@
+//! Dependency health probes behind the readiness endpoint and the gRPC health service
+//!
+//! The probes run on a timer in `spawn_readiness_task`, not per request, so unauthenticated
+//! health checks cannot make the service hammer its dependencies. REST and gRPC health both
+//! serve the latest report from `Readiness`.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use diesel::RunQueryDsl;
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tonic_health::server::HealthReporter;
+use tracing::{info, warn};
+
+use crate::common::dependencies::{AWS, HUAWEI, MONGODB, POSTGRES};
+use crate::AppState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProbeStatus {
+    Up,
+    Down,
+    Disabled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DependencyCheck {
+    pub status: ProbeStatus,
+    /// A critical dependency that is down makes the service not ready.
+    pub critical: bool,
+    pub latency_ms: u64,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadinessReport {
+    pub ready: bool,
+    /// Set once shutdown has started; readiness then fails without probing anything.
+    pub shutting_down: bool,
+    pub checks: BTreeMap<String, DependencyCheck>,
+    pub checked_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// The latest readiness report, shared through `AppState`; `None` until the first probe finishes.
+#[derive(Debug, Clone)]
+pub struct Readiness(watch::Sender<Option<ReadinessReport>>);
+
+impl Default for Readiness {
+    fn default() -> Self {
+        Self(watch::channel(None).0)
+    }
+}
+
+impl Readiness {
+    pub fn latest(&self) -> Option<ReadinessReport> {
+        self.0.borrow().clone()
+    }
+
+    pub fn publish(&self, report: ReadinessReport) {
+        self.0.send_replace(Some(report));
+    }
+
+    /// Sees every report published from now on, starting with the current one.
+    pub fn subscribe(&self) -> watch::Receiver<Option<ReadinessReport>> {
+        let mut receiver = self.0.subscribe();
+        receiver.mark_changed();
+        receiver
+    }
+}
+
+#[derive(Clone)]
+pub struct HealthService {
+    app_state: AppState,
+}
+
+impl HealthService {
+    pub fn new(app_state: AppState) -> Self {
+        Self { app_state }
+    }
+
+    /// Probes every dependency concurrently. Only a required dependency that is down (see
+    /// `health.required_dependencies`; PostgreSQL always is) makes the service not ready.
+    /// Request handlers serve `Readiness::latest` instead of calling this.
+    pub async fn check_readiness(&self) -> ReadinessReport {
+        if self.app_state.shutdown.is_triggered() {
+            return ReadinessReport::shutting_down();
+        }
+        let config = self.app_state.config.current();
+        let timeout = Duration::from_millis(config.health.probe_timeout_ms);
+        let required = |name| config.health.is_required(name);
+        let (postgres, mongodb, aws, huawei) = tokio::join!(
+            probe(timeout, true, self.probe_postgres()),
+            probe(timeout, required(MONGODB), self.probe_mongodb()),
+            self.probe_aws(timeout, required(AWS), config.storage.backend == "s3", &config.storage.bucket),
+            self.probe_huawei(timeout, required(HUAWEI), config.storage.backend == "obs", &config.storage.bucket),
+        );
+        let checks = BTreeMap::from([
+            (POSTGRES.to_string(), postgres),
+            (MONGODB.to_string(), mongodb),
+            (AWS.to_string(), aws),
+            (HUAWEI.to_string(), huawei),
+        ]);
+        let ready = checks.values().all(|check| !check.critical || check.status != ProbeStatus::Down);
+        ReadinessReport { ready, shutting_down: false, checks, checked_at: chrono::Utc::now() }
+    }
+
+    async fn probe_postgres(&self) -> Result<()> {
+        crate::database::postgres::with_connection(&self.app_state.postgres_pool, |conn| {
+            diesel::sql_query("SELECT 1").execute(conn).context("SELECT 1 failed")?;
+            Ok(())
+        }).await
+    }
+
+    async fn probe_mongodb(&self) -> Result<()> {
+        crate::database::mongodb::test_connection_with_client(&self.app_state.mongodb_client.current()).await
+    }
+
+    /// `HeadBucket` on the storage bucket when S3 holds the photos, else `ListBuckets`.
+    async fn probe_aws(&self, timeout: Duration, critical: bool, stores_photos: bool, bucket: &str) -> DependencyCheck {
+        let Some(aws) = self.app_state.aws_config.current() else {
+            return self.not_initialized(AWS, critical);
+        };
+        let client = aws.s3_client.clone();
+        probe(timeout, critical, async move {
+            if stores_photos {
+                client.head_bucket().bucket(bucket).send().await.context("HeadBucket failed")?;
+            } else {
+                client.list_buckets().send().await.context("ListBuckets failed")?;
+            }
+            Ok(())
+        }).await
+    }
+
+    /// `HeadBucket` on the storage bucket when OBS holds the photos; otherwise any HTTP response
+    /// from the regional endpoint shows it is reachable.
+    async fn probe_huawei(&self, timeout: Duration, critical: bool, stores_photos: bool, bucket: &str) -> DependencyCheck {
+        let Some(huawei) = self.app_state.huawei_config.current() else {
+            return self.not_initialized(HUAWEI, critical);
+        };
+        probe(timeout, critical, async move {
+            if stores_photos {
+                huawei.obs_client.head_bucket().bucket(bucket).send().await.context("OBS HeadBucket failed")?;
+            } else {
+                huawei.http_client.head(&huawei.base_url).send().await
+                    .with_context(|| format!("{} is unreachable", huawei.base_url))?;
+            }
+            Ok(())
+        }).await
+    }
+
+    /// A cloud service without a client is either disabled or has failed to initialise so far;
+    /// `spawn_dependency_monitor_task` keeps retrying the latter.
+    fn not_initialized(&self, dependency: &'static str, critical: bool) -> DependencyCheck {
+        match self.app_state.dependencies.check(dependency) {
+            Ok(()) => DependencyCheck { status: ProbeStatus::Disabled, critical: false, latency_ms: 0, error: None },
+            Err(e) => DependencyCheck { status: ProbeStatus::Down, critical, latency_ms: 0, error: Some(e.reason) },
+        }
+    }
+}
+
+impl ReadinessReport {
+    pub fn shutting_down() -> Self {
+        Self { ready: false, shutting_down: true, checks: BTreeMap::new(), checked_at: chrono::Utc::now() }
+    }
+
+    /// Critical dependencies that are down.
+    pub fn down(&self) -> Vec<&String> {
+        self.checks.iter()
+            .filter(|(_, check)| check.critical && check.status == ProbeStatus::Down)
+            .map(|(name, _)| name)
+            .collect()
+    }
+}
+
+async fn probe(timeout: Duration, critical: bool, check: impl Future<Output = Result<()>>) -> DependencyCheck {
+    let started = Instant::now();
+    let result = match tokio::time::timeout(timeout, check).await {
+        Ok(result) => result,
+        Err(_) => Err(anyhow::anyhow!("timed out after {}ms", timeout.as_millis())),
+    };
+    let latency_ms = started.elapsed().as_millis() as u64;
+    match result {
+        Ok(()) => DependencyCheck { status: ProbeStatus::Up, critical, latency_ms, error: None },
+        Err(e) => DependencyCheck { status: ProbeStatus::Down, critical, latency_ms, error: Some(format!("{:#}", e)) },
+    }
+}
+
+/// Runs the readiness probes every `health.grpc_check_interval_seconds` and publishes each report
+/// to `app_state.readiness`. Publishes a shutting-down report as soon as shutdown starts, then ends.
+pub fn spawn_readiness_task(app_state: AppState) -> JoinHandle<()> {
+    let interval_seconds = app_state.config.current().health.grpc_check_interval_seconds.max(1);
+    let shutdown = app_state.shutdown.clone();
+    let readiness = app_state.readiness.clone();
+    let health_service = HealthService::new(app_state);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_seconds));
+        let mut was_ready = None;
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.triggered() => {
+                    readiness.publish(ReadinessReport::shutting_down());
+                    return;
+                }
+            }
+            let report = health_service.check_readiness().await;
+            if was_ready != Some(report.ready) {
+                if report.ready {
+                    info!("Readiness: ready");
+                } else {
+                    warn!("Readiness: not ready, critical dependencies down: {:?}", report.down());
+                }
+                was_ready = Some(report.ready);
+            }
+            readiness.publish(report);
+        }
+    })
+}
+
+/// Keeps the gRPC health service in line with readiness: the overall status (`""`) and each
+/// registered service are `SERVING` only while the latest readiness report is ready. Everything
+/// turns `NOT_SERVING` as soon as shutdown starts, and the task then ends.
+pub fn spawn_grpc_health_task(
+    app_state: AppState,
+    mut reporter: HealthReporter,
+    services: Vec<&'static str>,
+) -> JoinHandle<()> {
+    let shutdown = app_state.shutdown.clone();
+    let mut updates = app_state.readiness.subscribe();
+    tokio::spawn(async move {
+        let mut was_ready = None;
+        loop {
+            tokio::select! {
+                changed = updates.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+                }
+                _ = shutdown.triggered() => {
+                    for service in std::iter::once("").chain(services.iter().copied()) {
+                        reporter.set_service_status(service, tonic_health::ServingStatus::NotServing).await;
+                    }
+                    info!("gRPC health: NOT_SERVING, shutting down");
+                    return;
+                }
+            }
+            let Some(ready) = updates.borrow_and_update().as_ref().map(|report| report.ready) else {
+                continue;
+            };
+            let status = if ready {
+                tonic_health::ServingStatus::Serving
+            } else {
+                tonic_health::ServingStatus::NotServing
+            };
+            for service in std::iter::once("").chain(services.iter().copied()) {
+                reporter.set_service_status(service, status).await;
+            }
+            if was_ready != Some(ready) {
+                info!("gRPC health: {}", if ready { "SERVING" } else { "NOT_SERVING" });
+                was_ready = Some(ready);
+            }
+        }
+    })
+}
+
+/// Every `health.reconnect_interval_seconds`, pings MongoDB and retries the enabled cloud
+/// clients that have failed to initialise, marking each dependency available or unavailable so
+/// that features backed by it answer 503 without waiting on it while it is down. Does nothing
+/// when the interval is 0.
+pub fn spawn_dependency_monitor_task(app_state: AppState) -> Option<JoinHandle<()>> {
+    let config = app_state.config.current();
+    if config.health.reconnect_interval_seconds == 0 {
+        return None;
+    }
+    let interval = Duration::from_secs(config.health.reconnect_interval_seconds);
+    let timeout = Duration::from_millis(config.health.probe_timeout_ms);
+    let shutdown = app_state.shutdown.clone();
+    Some(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.triggered() => return,
+            }
+            let period = interval.period().as_secs();
+            let client = app_state.mongodb_client.current();
+            let ping = crate::database::mongodb::test_connection_with_client(&client);
+            update_availability(&app_state, MONGODB, within(timeout, MONGODB, ping).await, period);
+            retry_cloud_clients(&app_state, timeout, period).await;
+        }
+    }))
+}
+
+/// Initialises the AWS and Huawei clients that are enabled but missing, e.g. because the
+/// credentials or endpoint were unusable at startup.
+async fn retry_cloud_clients(app_state: &AppState, timeout: Duration, period: u64) {
+    let config = app_state.config.current();
+    if config.cloud.enable_aws_services && app_state.aws_config.current().is_none() {
+        let result = within(timeout, AWS, crate::cloud::aws::initialize_aws_config(&config.cloud.aws)).await
+            .map(|aws_config| app_state.aws_config.replace(Some(aws_config)));
+        update_availability(app_state, AWS, result, period);
+    }
+    if config.cloud.enable_huawei_services && app_state.huawei_config.current().is_none() {
+        let result = within(timeout, HUAWEI, crate::cloud::huawei::initialize_huawei_config(&config.cloud.huawei)).await
+            .map(|huawei_config| app_state.huawei_config.replace(Some(huawei_config)));
+        update_availability(app_state, HUAWEI, result, period);
+    }
+}
+
+async fn within<T>(timeout: Duration, dependency: &str, attempt: impl Future<Output = Result<T>>) -> Result<T> {
+    match tokio::time::timeout(timeout, attempt).await {
+        Ok(result) => result,
+        Err(_) => Err(anyhow::anyhow!("No response from {} within {}ms", dependency, timeout.as_millis())),
+    }
+}
+
+/// Logs only when the dependency's availability changes.
+fn update_availability(app_state: &AppState, dependency: &'static str, result: Result<()>, period: u64) {
+    match result {
+        Ok(()) => {
+            if app_state.dependencies.mark_available(dependency) {
+                info!("{} is available again", dependency);
+            }
+        }
+        Err(e) => {
+            if app_state.dependencies.mark_unavailable(dependency, format!("{:#}", e)) {
+                warn!("{} became unavailable, retrying every {}s: {:#}", dependency, period, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_failed_cloud_client_is_retried() {
+        let app_state = crate::offline_app_state().await;
+        let mut config = (*app_state.config.current()).clone();
+        config.cloud.enable_huawei_services = true;
+        config.cloud.enable_aws_services = false;
+        app_state.config.replace(config.clone());
+        app_state.dependencies.mark_unavailable(HUAWEI, "missing credentials".to_string());
+        let timeout = Duration::from_secs(1);
+
+        retry_cloud_clients(&app_state, timeout, 15).await;
+        assert!(app_state.huawei_config.current().is_none());
+        assert!(app_state.dependencies.check(HUAWEI).unwrap_err().reason.contains("access_key is required"));
+
+        config.cloud.huawei.access_key = "AK".to_string();
+        config.cloud.huawei.secret_key = "SK".into();
+        config.cloud.huawei.project_id = "project".to_string();
+        app_state.config.replace(config);
+        retry_cloud_clients(&app_state, timeout, 15).await;
+        assert!(app_state.huawei_config.current().is_some());
+        assert!(app_state.dependencies.check(HUAWEI).is_ok());
+        assert!(app_state.aws_config.current().is_none(), "disabled services are left alone");
+    }
+
+    #[tokio::test]
+    async fn test_probe_timeout_and_failure() {
+        let timeout = Duration::from_millis(20);
+        let up = probe(timeout, true, async { Ok(()) }).await;
+        assert_eq!(up.status, ProbeStatus::Up);
+
+        let slow = probe(timeout, true, async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(())
+        }).await;
+        assert_eq!(slow.status, ProbeStatus::Down);
+        assert_eq!(slow.error.as_deref(), Some("timed out after 20ms"));
+
+        let failed = probe(timeout, false, async { Err(anyhow::anyhow!("refused")) }).await;
+        assert_eq!((failed.status, failed.critical), (ProbeStatus::Down, false));
+    }
+}
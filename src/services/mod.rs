@@ -3,7 +3,18 @@
 pub mod user_service;
 pub mod auth_service;
 pub mod photo_service;
+pub mod photo_storage;
+pub mod content_scanner;
+pub mod photo_reconciler;
+pub mod upload_service;
+pub mod retention_service;
+pub mod health_service;
 
 pub use user_service::UserService;
 pub use auth_service::AuthService;
 pub use photo_service::PhotoService;
+pub use photo_storage::PhotoStorage;
+pub use photo_reconciler::PhotoReconciler;
+pub use upload_service::UploadService;
+pub use retention_service::RetentionService;
+pub use health_service::HealthService;
@@ -0,0 +1,246 @@
+//! Keeps photo blobs and their Postgres metadata consistent.
+//!
+//! Deletions go through `photo_blob_outbox`: the metadata row and the outbox entry are
+//! removed/inserted in one transaction, and the blob delete is applied afterwards and
+//! retried here until it succeeds. The periodic sweep also repairs orphans left by
+//! crashes: blobs nobody references, and metadata rows whose blob is gone.
+
+use anyhow::{Result, Context};
+use chrono::Utc;
+use diesel::prelude::*;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::time::Duration;
+use tracing::{info, warn, error};
+use uuid::Uuid;
+
+use crate::common::dependencies::unavailable_cause;
+use crate::database::postgres::with_connection;
+use crate::models::{DbPhotoBlobOutbox, NewDbPhotoBlobOutbox};
+use crate::schema::{photo_blob_outbox, user_photos};
+use crate::services::photo_service::{storage_id_from_url, storage_area_for};
+use crate::services::photo_storage::{PhotoStorage, StorageArea};
+use crate::AppState;
+
+/// What a reconciliation pass found and (unless `dry_run`) changed.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ReconcileReport {
+    pub dry_run: bool,
+    /// Outbox blob deletions that were applied, as `<area>/<storage id>`.
+    pub outbox_applied: Vec<String>,
+    /// Outbox blob deletions that failed again and stay queued.
+    pub outbox_failed: Vec<String>,
+    /// Blobs with no metadata row that were deleted.
+    pub orphan_blobs_deleted: Vec<String>,
+    /// Metadata rows pointing at a missing blob that were deleted.
+    pub dangling_photos_deleted: Vec<Uuid>,
+}
+
+impl ReconcileReport {
+    pub fn is_clean(&self) -> bool {
+        self.outbox_applied.is_empty()
+            && self.outbox_failed.is_empty()
+            && self.orphan_blobs_deleted.is_empty()
+            && self.dangling_photos_deleted.is_empty()
+    }
+}
+
+#[derive(Clone)]
+pub struct PhotoReconciler {
+    app_state: AppState,
+}
+
+impl PhotoReconciler {
+    pub fn new(app_state: AppState) -> Self {
+        Self { app_state }
+    }
+
+    /// Queues a blob for deletion. Use `enqueue_blob_deletion_in` to share a transaction.
+    pub async fn enqueue_blob_deletion(&self, storage_id: &str, area: StorageArea) -> Result<DbPhotoBlobOutbox> {
+        let storage_id = storage_id.to_string();
+        with_connection(&self.app_state.postgres_pool, move |conn| {
+            enqueue_blob_deletion_in(conn, &storage_id, area)
+        }).await
+    }
+
+    /// Deletes the blob for an outbox entry and clears the entry. On failure the entry is
+    /// kept with its attempt count bumped. Returns whether the blob is now gone.
+    pub async fn apply_blob_deletion(&self, entry: &DbPhotoBlobOutbox) -> Result<bool> {
+        let storage = PhotoStorage::from_app_state(&self.app_state)?
+            .in_area(StorageArea::parse(&entry.storage_area));
+        let outcome = match storage.exists(&entry.storage_id).await {
+            Ok(false) => Ok(()),
+            Ok(true) => storage.delete(&entry.storage_id).await,
+            Err(e) => Err(e),
+        };
+        let entry_id = entry.id;
+        match outcome {
+            Ok(()) => {
+                with_connection(&self.app_state.postgres_pool, move |conn| {
+                    diesel::delete(photo_blob_outbox::table.find(entry_id))
+                        .execute(conn)
+                        .context("Failed to clear photo blob outbox entry")
+                }).await?;
+                Ok(true)
+            }
+            Err(e) => {
+                warn!("Failed to delete photo blob {}: {}", entry.storage_id, e);
+                let last_error = e.to_string();
+                with_connection(&self.app_state.postgres_pool, move |conn| {
+                    diesel::update(photo_blob_outbox::table.find(entry_id))
+                        .set((
+                            photo_blob_outbox::attempts.eq(photo_blob_outbox::attempts + 1),
+                            photo_blob_outbox::last_error.eq(Some(last_error)),
+                            photo_blob_outbox::updated_at.eq(Utc::now()),
+                        ))
+                        .execute(conn)
+                        .context("Failed to record photo blob outbox failure")
+                }).await?;
+                Ok(false)
+            }
+        }
+    }
+
+    pub async fn reconcile(&self, dry_run: bool) -> Result<ReconcileReport> {
+        let mut report = ReconcileReport { dry_run, ..Default::default() };
+        let storage = PhotoStorage::from_app_state(&self.app_state)?;
+        let cutoff = Utc::now() - chrono::Duration::seconds(self.app_state.config.current().storage.orphan_grace_seconds as i64);
+
+        let (outbox, photos) = with_connection(&self.app_state.postgres_pool, |conn| {
+            let outbox = photo_blob_outbox::table
+                .order(photo_blob_outbox::created_at.asc())
+                .select(DbPhotoBlobOutbox::as_select())
+                .load(conn)
+                .context("Failed to load photo blob outbox")?;
+            let photos: Vec<(Uuid, String, chrono::DateTime<Utc>, Option<String>)> = user_photos::table
+                .select((user_photos::id, user_photos::photo_url, user_photos::created_at, user_photos::scan_verdict))
+                .load(conn)
+                .context("Failed to load photo metadata")?;
+            Ok((outbox, photos))
+        }).await?;
+
+        // 1. Finish deletions that were committed in Postgres but not yet applied to storage.
+        for entry in &outbox {
+            let blob = format!("{}/{}", entry.storage_area, entry.storage_id);
+            if dry_run {
+                report.outbox_applied.push(blob);
+                continue;
+            }
+            match self.apply_blob_deletion(entry).await {
+                Ok(true) => report.outbox_applied.push(blob),
+                Ok(false) => report.outbox_failed.push(blob),
+                Err(e) => {
+                    error!("Failed to process photo blob outbox entry {}: {}", entry.id, e);
+                    report.outbox_failed.push(blob);
+                }
+            }
+        }
+
+        for area in StorageArea::ALL {
+            let area_storage = storage.in_area(area);
+            let queued: HashSet<&str> = outbox.iter()
+                .filter(|entry| StorageArea::parse(&entry.storage_area) == area)
+                .map(|entry| entry.storage_id.as_str())
+                .collect();
+            let area_photos: Vec<(Uuid, String, chrono::DateTime<Utc>)> = photos.iter()
+                .filter(|(_, _, _, scan_verdict)| storage_area_for(scan_verdict.as_deref()) == area)
+                .filter_map(|(photo_id, photo_url, created_at, _)| {
+                    storage_id_from_url(photo_url).map(|storage_id| (*photo_id, storage_id, *created_at))
+                })
+                .collect();
+
+            // 2. Blobs with no metadata row, e.g. an upload whose Postgres insert never landed.
+            let blobs = area_storage.list().await
+                .context("Failed to list photo storage")?;
+            let referenced: HashSet<&str> = area_photos.iter().map(|(_, storage_id, _)| storage_id.as_str()).collect();
+            for blob in &blobs {
+                let old_enough = blob.created_at.map(|created_at| created_at < cutoff).unwrap_or(false);
+                if !old_enough || referenced.contains(blob.id.as_str()) || queued.contains(blob.id.as_str()) {
+                    continue;
+                }
+                if !dry_run {
+                    if let Err(e) = area_storage.delete(&blob.id).await {
+                        warn!("Failed to delete orphan photo blob {}: {}", blob.id, e);
+                        continue;
+                    }
+                }
+                report.orphan_blobs_deleted.push(format!("{}/{}", area.as_str(), blob.id));
+            }
+
+            // 3. Metadata rows whose blob is gone. Each candidate is re-checked individually
+            //    so a partial listing can never cause metadata to be dropped.
+            let stored: HashSet<&str> = blobs.iter().map(|blob| blob.id.as_str()).collect();
+            for (photo_id, storage_id, created_at) in &area_photos {
+                if *created_at >= cutoff || stored.contains(storage_id.as_str()) {
+                    continue;
+                }
+                match area_storage.exists(storage_id).await {
+                    Ok(false) => {}
+                    Ok(true) => continue,
+                    Err(e) => {
+                        warn!("Failed to check photo blob {}: {}", storage_id, e);
+                        continue;
+                    }
+                }
+                if !dry_run {
+                    let photo_id = *photo_id;
+                    with_connection(&self.app_state.postgres_pool, move |conn| {
+                        diesel::delete(user_photos::table.find(photo_id))
+                            .execute(conn)
+                            .context("Failed to delete dangling photo metadata")
+                    }).await?;
+                }
+                report.dangling_photos_deleted.push(*photo_id);
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+/// Inserts an outbox entry on an existing connection, so callers can do it inside their transaction.
+pub fn enqueue_blob_deletion_in(conn: &mut PgConnection, storage_id: &str, area: StorageArea) -> Result<DbPhotoBlobOutbox> {
+    let now = Utc::now();
+    diesel::insert_into(photo_blob_outbox::table)
+        .values(&NewDbPhotoBlobOutbox {
+            storage_id: storage_id.to_string(),
+            created_at: now,
+            updated_at: now,
+            storage_area: area.as_str().to_string(),
+        })
+        .returning(DbPhotoBlobOutbox::as_returning())
+        .get_result(conn)
+        .context("Failed to queue photo blob deletion")
+}
+
+/// Periodically runs a reconciliation pass. Does nothing when the interval is 0. On shutdown a
+/// pass already under way is completed before the task ends.
+pub fn spawn_photo_reconcile_task(app_state: AppState) -> Option<tokio::task::JoinHandle<()>> {
+    let interval_seconds = app_state.config.current().storage.reconcile_interval_seconds;
+    if interval_seconds == 0 {
+        return None;
+    }
+    let shutdown = app_state.shutdown.clone();
+    let reconciler = PhotoReconciler::new(app_state);
+    Some(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_seconds));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.triggered() => return,
+            }
+            match reconciler.reconcile(false).await {
+                Ok(report) if report.is_clean() => {}
+                Ok(report) => info!(
+                    "Photo reconciliation: {} outbox deletions applied, {} failed, {} orphan blobs deleted, {} dangling photos deleted",
+                    report.outbox_applied.len(),
+                    report.outbox_failed.len(),
+                    report.orphan_blobs_deleted.len(),
+                    report.dangling_photos_deleted.len(),
+                ),
+                Err(e) if unavailable_cause(&e).is_some() => warn!("Photo reconciliation skipped: {:#}", e),
+                Err(e) => error!("Photo reconciliation failed: {}", e),
+            }
+        }
+    }))
+}
@@ -3,22 +3,136 @@
 use anyhow::{Result, Context};
 use uuid::Uuid;
 use diesel::prelude::*;
-use mongodb::bson::oid::ObjectId;
-use chrono::Utc;
-use tracing::{info};
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+use futures::{Stream, StreamExt};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
 
 use crate::models::{
     MongoPhoto,
-    DbUserPhoto, NewDbUserPhoto,
-    UserPhoto
-};
-use crate::database::{
-    postgres::get_connection,
-    mongodb::{get_database, get_collection}
+    DbUserPhoto, NewDbUserPhoto, DbPossibleDuplicate,
+    UserPhoto, SignedPhotoUrl, PossibleDuplicate, PaginatedResponse,
 };
+use crate::database::postgres::with_connection;
 use crate::schema::user_photos;
+use crate::services::photo_storage::{PhotoStorage, PhotoObject, PhotoUploadWriter, StorageArea};
+use crate::services::content_scanner::{scanner_from_config, ScanVerdict};
+use crate::services::photo_reconciler::{PhotoReconciler, enqueue_blob_deletion_in};
+use crate::utils::encryption::{generate_checksum, sign_hmac_sha256, verify_hmac_sha256};
+use crate::utils::pagination::{keyset_page, PagePosition};
 use crate::AppState;
 
+pub const MAX_PHOTO_SIZE_BYTES: usize = 10 * 1024 * 1024;
+pub const PHOTO_STATUS_ACTIVE: &str = "active";
+/// Replaced by a newer upload of the same type under the per-type quota.
+pub const PHOTO_STATUS_SUPERSEDED: &str = "superseded";
+pub const PHOTO_STATUS_REJECTED: &str = "rejected";
+/// Held in the quarantine storage area because the content scanner did not pass it.
+pub const PHOTO_STATUS_QUARANTINED: &str = "quarantined";
+/// Verdict recorded when an admin clears a quarantined photo.
+pub const SCAN_VERDICT_RELEASED: &str = "released";
+/// Longest lifetime accepted for an issued photo URL (the S3 presigning limit).
+pub const MAX_PHOTO_URL_TTL_SECONDS: u64 = 7 * 24 * 3600;
+const MIN_DERIVATIVE_SIZE: u32 = 16;
+const MAX_DERIVATIVE_SIZE: u32 = 2048;
+/// Unique index holding one active photo per user, type and content.
+const ACTIVE_CONTENT_INDEX: &str = "idx_user_photos_active_content";
+/// Cap on the matches listed for a single photo in the possible-duplicates report.
+const MAX_DUPLICATES_PER_PHOTO: i64 = 20;
+
+struct PhotoFingerprint {
+    sha256: String,
+    perceptual_hash: Option<i64>,
+}
+
+/// An upload refused because of what the client sent, as opposed to a server-side failure.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PhotoRejected {
+    #[error("Invalid photo type: {0}")]
+    InvalidType(String),
+    #[error("Unsupported file format: {0}")]
+    UnsupportedFormat(String),
+    #[error("File size too large. Maximum 10MB allowed")]
+    TooLarge,
+    #[error("Invalid SHA-256 checksum")]
+    MalformedChecksum,
+    #[error("Upload exceeds declared size of {0} bytes")]
+    ExceedsDeclaredSize(u64),
+    #[error("Upload ended after {received} of {declared} bytes")]
+    Truncated { received: u64, declared: u64 },
+    #[error("SHA-256 checksum mismatch")]
+    ChecksumMismatch,
+    #[error("The same photo is already active")]
+    Duplicate,
+}
+
+impl PhotoRejected {
+    /// HTTP status the APIs report for this rejection.
+    pub fn status_code(&self) -> u16 {
+        match self {
+            Self::TooLarge => 413,
+            Self::Duplicate => 409,
+            _ => 400,
+        }
+    }
+}
+
+/// Whether `error` was caused by the client's upload rather than by the server.
+pub fn rejection_cause(error: &anyhow::Error) -> Option<&PhotoRejected> {
+    error.chain().find_map(|cause| cause.downcast_ref::<PhotoRejected>())
+}
+
+/// Filters for the admin possible-duplicates query.
+#[derive(Debug, Clone)]
+pub struct DuplicatePhotoQuery {
+    pub photo_type: Option<String>,
+    /// Overrides the configured perceptual-hash distance.
+    pub max_distance: Option<u32>,
+    pub cross_account_only: bool,
+    /// Photos examined per page.
+    pub limit: u32,
+}
+
+/// Declared properties of a streamed upload, checked once the last chunk arrives.
+#[derive(Debug, Clone)]
+pub struct PhotoStreamUpload {
+    pub user_id: Uuid,
+    pub photo_type: String,
+    pub file_extension: String,
+    pub total_size: u64,
+    /// Hex-encoded SHA-256 of the whole file.
+    pub sha256: String,
+}
+
+/// What a signed photo URL grants access to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhotoUrlScope {
+    Original,
+    /// Derivative scaled to fit within a `size` x `size` box.
+    Size(u32),
+}
+
+impl PhotoUrlScope {
+    pub fn from_size(size: Option<u32>) -> Result<Self> {
+        match size {
+            None | Some(0) => Ok(Self::Original),
+            Some(size) if (MIN_DERIVATIVE_SIZE..=MAX_DERIVATIVE_SIZE).contains(&size) => Ok(Self::Size(size)),
+            Some(size) => Err(anyhow::anyhow!(
+                "Photo size must be between {} and {}, got {}",
+                MIN_DERIVATIVE_SIZE, MAX_DERIVATIVE_SIZE, size
+            )),
+        }
+    }
+
+    fn as_signing_input(&self) -> String {
+        match self {
+            Self::Original => "original".to_string(),
+            Self::Size(size) => format!("size={}", size),
+        }
+    }
+}
+
 pub struct PhotoService {
     app_state: AppState,
 }
@@ -35,20 +149,19 @@ impl PhotoService {
         photo_data: Vec<u8>,
         file_extension: String,
     ) -> Result<UserPhoto> {
-        if !matches!(photo_type.as_str(), "profile" | "emirates_id" | "verification") {
-            return Err(anyhow::anyhow!("Invalid photo type: {}", photo_type));
-        }
-        if photo_data.len() > 10 * 1024 * 1024 {
-            return Err(anyhow::anyhow!("File size too large. Maximum 10MB allowed"));
-        }
-        let content_type = match file_extension.to_lowercase().as_str() {
-            "jpg" | "jpeg" => "image/jpeg",
-            "png" => "image/png",
-            "gif" => "image/gif",
-            "webp" => "image/webp",
-            _ => return Err(anyhow::anyhow!("Unsupported file format: {}", file_extension)),
-        };
+        validate_photo_type(&photo_type)?;
+        if photo_data.len() > MAX_PHOTO_SIZE_BYTES {
+            return Err(PhotoRejected::TooLarge.into());
+        }
+        let content_type = content_type_for_extension(&file_extension)?;
+
+        let fingerprint = self.fingerprint(generate_checksum(&photo_data), &photo_data);
+        if let Some(existing) = self.find_own_duplicate(user_id, &photo_type, &fingerprint.sha256).await? {
+            info!("Deduplicated photo upload for user {}: {}", user_id, existing.id);
+            return Ok(existing);
+        }
 
+        let verdict = self.scan_photo(&photo_data).await;
         let file_name = format!("{}_{}.{}", user_id, photo_type, file_extension);
         let file_size = photo_data.len() as i64;
 
@@ -61,101 +174,527 @@ impl PhotoService {
             photo_data,
         );
 
-        let photo_id = self.store_photo_in_mongodb(mongo_photo).await
-            .context("Failed to store photo in MongoDB")?;
-
-        let photo_url = format!("/api/v1/photos/{}", photo_id.to_hex());
+        let storage = PhotoStorage::from_app_state(&self.app_state)?
+            .in_area(storage_area_for(Some(verdict.as_str())));
+        let storage_id = storage.put(mongo_photo).await
+            .context("Failed to store photo")?;
 
-        let user_photo = self.store_photo_metadata_in_postgres(
+        let user_photo = self.store_photo_metadata_or_compensate(
+            &storage,
+            &storage_id,
             user_id,
             photo_type,
-            photo_url,
-        ).await.context("Failed to store photo metadata in PostgreSQL")?;
+            fingerprint,
+            &verdict,
+        ).await?;
 
+        crate::metrics::PHOTO_UPLOAD_BYTES.with_label_values(&["buffered"]).observe(file_size as f64);
         info!("Photo uploaded successfully for user {}: {}", user_id, user_photo.id);
         Ok(user_photo)
     }
 
-    pub async fn get_photo_data(&self, photo_id: &str) -> Result<MongoPhoto> {
-        let object_id = ObjectId::parse_str(photo_id)
-            .context("Invalid photo ID format")?;
-        let db = get_database(&self.app_state.mongodb_client, "stander_db");
-        let collection = get_collection::<MongoPhoto>(&db, "photos");
-        let filter = mongodb::bson::doc! { "_id": object_id };
-        let photo = collection.find_one(filter, None).await
-            .context("Failed to query MongoDB")?;
-        photo.ok_or_else(|| anyhow::anyhow!("Photo not found"))
+    /// Writes chunks to storage as they arrive; the blob is discarded if the size or checksum do not match.
+    pub async fn upload_photo_stream<S>(&self, upload: PhotoStreamUpload, mut chunks: S) -> Result<UserPhoto>
+    where
+        S: Stream<Item = Result<Vec<u8>>> + Unpin,
+    {
+        validate_photo_type(&upload.photo_type)?;
+        if upload.total_size == 0 || upload.total_size as usize > MAX_PHOTO_SIZE_BYTES {
+            return Err(PhotoRejected::TooLarge.into());
+        }
+        let expected_sha256 = upload.sha256.to_ascii_lowercase();
+        if expected_sha256.len() != 64 || !expected_sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(PhotoRejected::MalformedChecksum.into());
+        }
+        let content_type = content_type_for_extension(&upload.file_extension)?;
+        let file_name = format!("{}_{}.{}", upload.user_id, upload.photo_type, upload.file_extension);
+
+        let storage = PhotoStorage::from_app_state(&self.app_state)?;
+        let mut writer = storage.start_upload(upload.user_id, &upload.photo_type, &file_name, content_type).await
+            .context("Failed to start photo upload")?;
+
+        let mut hasher = Sha256::new();
+        // The perceptual hash and the content scanner need the whole file, so keep a copy only when either is on.
+        let scanning = self.app_state.config.current().scanner.backend != "none";
+        let mut retained = (scanning || self.app_state.config.current().storage.perceptual_hash_enabled).then(Vec::new);
+        let mut received: u64 = 0;
+        let mut outcome = Ok(());
+        while let Some(chunk) = chunks.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    outcome = Err(e);
+                    break;
+                }
+            };
+            received += chunk.len() as u64;
+            if received > upload.total_size {
+                outcome = Err(PhotoRejected::ExceedsDeclaredSize(upload.total_size).into());
+                break;
+            }
+            hasher.update(&chunk);
+            if let Some(retained) = retained.as_mut() {
+                retained.extend_from_slice(&chunk);
+            }
+            if let Err(e) = writer.write_chunk(&chunk).await {
+                outcome = Err(e);
+                break;
+            }
+        }
+        if outcome.is_ok() && received != upload.total_size {
+            outcome = Err(PhotoRejected::Truncated { received, declared: upload.total_size }.into());
+        }
+        if outcome.is_ok() && format!("{:x}", hasher.finalize()) != expected_sha256 {
+            outcome = Err(PhotoRejected::ChecksumMismatch.into());
+        }
+        let duplicate = match outcome {
+            Ok(()) => self.find_own_duplicate(upload.user_id, &upload.photo_type, &expected_sha256).await,
+            Err(e) => Err(e),
+        };
+        match duplicate {
+            Ok(None) => {}
+            Ok(Some(existing)) => {
+                abort_upload(writer, upload.user_id).await;
+                info!("Deduplicated streamed photo upload for user {}: {}", upload.user_id, existing.id);
+                return Ok(existing);
+            }
+            Err(e) => {
+                abort_upload(writer, upload.user_id).await;
+                return Err(e);
+            }
+        }
+
+        let retained = retained.unwrap_or_default();
+        let fingerprint = self.fingerprint(expected_sha256, &retained);
+        let verdict = if scanning { self.scan_photo(&retained).await } else { ScanVerdict::Clean };
+        let (storage, storage_id) = if verdict.is_clean() {
+            let storage_id = writer.finish().await
+                .context("Failed to store photo")?;
+            (storage, storage_id)
+        } else {
+            // The streamed copy sits in the public area; drop it and keep the retained bytes in quarantine.
+            abort_upload(writer, upload.user_id).await;
+            let quarantine = storage.in_area(StorageArea::Quarantine);
+            let mongo_photo = MongoPhoto::new(
+                upload.user_id,
+                upload.photo_type.clone(),
+                file_name,
+                retained.len() as i64,
+                content_type.to_string(),
+                retained,
+            );
+            let storage_id = quarantine.put(mongo_photo).await
+                .context("Failed to store quarantined photo")?;
+            (quarantine, storage_id)
+        };
+        let user_photo = self.store_photo_metadata_or_compensate(
+            &storage,
+            &storage_id,
+            upload.user_id,
+            upload.photo_type,
+            fingerprint,
+            &verdict,
+        ).await?;
+
+        crate::metrics::PHOTO_UPLOAD_BYTES.with_label_values(&["stream"]).observe(upload.total_size as f64);
+        info!("Streamed photo uploaded successfully for user {}: {}", upload.user_id, user_photo.id);
+        Ok(user_photo)
+    }
+
+    pub async fn get_photo_data(&self, storage_id: &str, scope: PhotoUrlScope, area: StorageArea) -> Result<PhotoObject> {
+        let storage = PhotoStorage::from_app_state(&self.app_state)?.in_area(area);
+        let object = storage.get(storage_id).await?;
+        match scope {
+            PhotoUrlScope::Original => Ok(object),
+            PhotoUrlScope::Size(size) => resize_photo(object, size),
+        }
+    }
+
+    pub async fn get_photo(&self, photo_id: Uuid) -> Result<Option<UserPhoto>> {
+        let db_photo = with_connection(&self.app_state.postgres_pool, move |conn| {
+            user_photos::table
+                .find(photo_id)
+                .first::<DbUserPhoto>(conn)
+                .optional()
+                .context("Failed to query photo from database")
+        }).await?;
+        Ok(db_photo.map(db_photo_to_user_photo))
+    }
+
+    pub async fn get_photo_by_storage_id(&self, storage_id: &str) -> Result<Option<UserPhoto>> {
+        let photo_url = format!("/api/v1/photos/{}", storage_id);
+        let db_photo = with_connection(&self.app_state.postgres_pool, move |conn| {
+            user_photos::table
+                .filter(user_photos::photo_url.eq(photo_url))
+                .first::<DbUserPhoto>(conn)
+                .optional()
+                .context("Failed to query photo from database")
+        }).await?;
+        Ok(db_photo.map(db_photo_to_user_photo))
+    }
+
+    /// Issues a time-limited URL for a photo. Originals stored in S3 or OBS get a
+    /// native presigned URL; everything else gets an HMAC-signed link to the download route.
+    pub async fn issue_photo_url(
+        &self,
+        photo: &UserPhoto,
+        ttl_seconds: Option<u64>,
+        scope: PhotoUrlScope,
+    ) -> Result<SignedPhotoUrl> {
+        let config = self.app_state.config.current();
+        let storage_config = &config.storage;
+        let ttl = ttl_seconds
+            .filter(|ttl| *ttl > 0)
+            .unwrap_or(storage_config.signed_url_ttl_seconds)
+            .min(MAX_PHOTO_URL_TTL_SECONDS);
+        let expires_at = Utc::now() + chrono::Duration::seconds(ttl as i64);
+        if photo.status == PHOTO_STATUS_QUARANTINED {
+            return Err(anyhow::anyhow!("Photo is quarantined"));
+        }
+        let storage_id = self.extract_storage_id_from_url(&photo.photo_url)?;
+
+        if scope == PhotoUrlScope::Original {
+            let storage = PhotoStorage::from_app_state(&self.app_state)?;
+            if let Some(url) = storage.presigned_url(&storage_id, Duration::from_secs(ttl)).await? {
+                return Ok(SignedPhotoUrl { url, expires_at });
+            }
+        }
+
+        let expires = expires_at.timestamp();
+        let signature = self.sign_photo_url(&storage_id, expires, scope);
+        let mut url = format!(
+            "{}/api/v1/photos/{}?expires={}",
+            storage_config.public_base_url.trim_end_matches('/'),
+            storage_id,
+            expires
+        );
+        if let PhotoUrlScope::Size(size) = scope {
+            url.push_str(&format!("&size={}", size));
+        }
+        url.push_str(&format!("&sig={}", signature));
+        Ok(SignedPhotoUrl { url, expires_at })
+    }
+
+    pub fn sign_photo_url(&self, storage_id: &str, expires: i64, scope: PhotoUrlScope) -> String {
+        let message = format!("{}:{}:{}", storage_id, expires, scope.as_signing_input());
+        sign_hmac_sha256(self.app_state.config.current().url_signing_key().expose().as_bytes(), message.as_bytes())
     }
+
+    /// Checks the signature and expiry of a URL produced by [`Self::issue_photo_url`].
+    pub fn verify_photo_url(&self, storage_id: &str, expires: i64, scope: PhotoUrlScope, signature: &str) -> bool {
+        let Some(expires_at) = DateTime::<Utc>::from_timestamp(expires, 0) else {
+            return false;
+        };
+        if expires_at < Utc::now() {
+            return false;
+        }
+        let message = format!("{}:{}:{}", storage_id, expires, scope.as_signing_input());
+        verify_hmac_sha256(self.app_state.config.current().url_signing_key().expose().as_bytes(), message.as_bytes(), signature)
+    }
+
     pub async fn get_user_photos(&self, user_id: Uuid) -> Result<Vec<UserPhoto>> {
-        let mut conn = get_connection(&self.app_state.postgres_pool)
-            .context("Failed to get database connection")?;
-        let db_photos = user_photos::table
-            .filter(user_photos::user_id.eq(user_id))
-            .load::<DbUserPhoto>(&mut conn)
-            .context("Failed to load user photos from database")?;
-        let photos = db_photos.into_iter().map(|db_photo| UserPhoto {
-            id: db_photo.id,
-            user_id: db_photo.user_id,
-            photo_type: db_photo.photo_type,
-            photo_url: db_photo.photo_url,
-            is_verified: db_photo.is_verified,
-            created_at: db_photo.created_at,
-            updated_at: db_photo.updated_at,
-        }).collect();
-
-        Ok(photos)
+        let db_photos = with_connection(&self.app_state.postgres_pool, move |conn| {
+            user_photos::table
+                .filter(user_photos::user_id.eq(user_id))
+                .filter(user_photos::status.eq(PHOTO_STATUS_ACTIVE))
+                .load::<DbUserPhoto>(conn)
+                .context("Failed to load user photos from database")
+        }).await?;
+        Ok(db_photos.into_iter().map(db_photo_to_user_photo).collect())
     }
 
     pub async fn delete_photo(&self, user_id: Uuid, photo_id: Uuid) -> Result<()> {
-        let mut conn = get_connection(&self.app_state.postgres_pool)
-            .context("Failed to get database connection")?;
-        let db_photo = user_photos::table
-            .filter(user_photos::id.eq(photo_id))
-            .filter(user_photos::user_id.eq(user_id))
-            .first::<DbUserPhoto>(&mut conn)
-            .context("Photo not found or access denied")?;
-        let mongo_id = self.extract_mongo_id_from_url(&db_photo.photo_url)
-            .context("Invalid photo URL format")?;
-        self.delete_photo_from_mongodb(&mongo_id).await
-            .context("Failed to delete photo from MongoDB")?;
-        diesel::delete(user_photos::table.filter(user_photos::id.eq(photo_id)))
-            .execute(&mut conn)
-            .context("Failed to delete photo metadata from PostgreSQL")?;
+        let (storage_id, outbox_entry) = with_connection(&self.app_state.postgres_pool, move |conn| {
+            let db_photo = user_photos::table
+                .filter(user_photos::id.eq(photo_id))
+                .filter(user_photos::user_id.eq(user_id))
+                .first::<DbUserPhoto>(conn)
+                .context("Photo not found or access denied")?;
+            let storage_id = storage_id_from_url(&db_photo.photo_url)
+                .context("Invalid photo URL format")?;
+            // Drop the metadata and queue the blob delete atomically, then apply the delete.
+            // If storage is unavailable the outbox entry stays and the reconciler retries it.
+            let outbox_entry = conn.transaction(|conn| {
+                diesel::delete(user_photos::table.filter(user_photos::id.eq(photo_id)))
+                    .execute(conn)
+                    .context("Failed to delete photo metadata from PostgreSQL")?;
+                enqueue_blob_deletion_in(conn, &storage_id, storage_area_for(db_photo.scan_verdict.as_deref()))
+            })?;
+            Ok((storage_id, outbox_entry))
+        }).await?;
+        let reconciler = PhotoReconciler::new(self.app_state.clone());
+        if !reconciler.apply_blob_deletion(&outbox_entry).await? {
+            warn!("Photo {} deleted; blob {} queued for retry", photo_id, storage_id);
+        }
         info!("Photo deleted successfully: {}", photo_id);
         Ok(())
     }
 
     pub async fn verify_photo(&self, user_id: Uuid, photo_id: Uuid) -> Result<UserPhoto> {
-        let mut conn = get_connection(&self.app_state.postgres_pool)
-            .context("Failed to get database connection")?;
-        let updated_photo = diesel::update(
+        let updated_photo = with_connection(&self.app_state.postgres_pool, move |conn| {
+            diesel::update(
+                user_photos::table
+                    .filter(user_photos::id.eq(photo_id))
+                    .filter(user_photos::user_id.eq(user_id))
+            )
+            .set((
+                user_photos::is_verified.eq(true),
+                user_photos::updated_at.eq(Utc::now()),
+            ))
+            .get_result::<DbUserPhoto>(conn)
+            .context("Failed to verify photo or photo not found")
+        }).await?;
+        Ok(db_photo_to_user_photo(updated_photo))
+    }
+
+    /// Admin view of uploads held back by the content scanner, newest first.
+    /// Accepts an offset or a cursor from an earlier page.
+    pub async fn list_quarantined_photos(&self, limit: u32, position: &PagePosition) -> Result<PaginatedResponse<UserPhoto>> {
+        let scan_position = position.clone();
+        let (total, db_photos) = with_connection(&self.app_state.postgres_pool, move |conn| {
+            let position = scan_position;
+            let total = user_photos::table
+                .filter(user_photos::status.eq(PHOTO_STATUS_QUARANTINED))
+                .count()
+                .get_result::<i64>(conn)
+                .context("Failed to count quarantined photos")?;
+            let mut query = user_photos::table
+                .filter(user_photos::status.eq(PHOTO_STATUS_QUARANTINED))
+                .into_boxed();
+            query = match &position {
+                PagePosition::Offset(offset) => query.offset(*offset as i64),
+                PagePosition::Cursor(cursor) if position.scans_descending(true) => query
+                    .filter(user_photos::created_at.lt(cursor.created_at)
+                        .or(user_photos::created_at.eq(cursor.created_at).and(user_photos::id.lt(cursor.id)))),
+                PagePosition::Cursor(cursor) => query
+                    .filter(user_photos::created_at.gt(cursor.created_at)
+                        .or(user_photos::created_at.eq(cursor.created_at).and(user_photos::id.gt(cursor.id)))),
+            };
+            query = if position.scans_descending(true) {
+                query.order((user_photos::created_at.desc(), user_photos::id.desc()))
+            } else {
+                query.order((user_photos::created_at.asc(), user_photos::id.asc()))
+            };
+            let db_photos = query
+                .limit(limit as i64 + 1)
+                .load::<DbUserPhoto>(conn)
+                .context("Failed to load quarantined photos")?;
+            Ok((total, db_photos))
+        }).await?;
+        let page = keyset_page(db_photos, limit as usize, position, |photo| (photo.created_at, photo.id));
+        Ok(PaginatedResponse {
+            has_next: page.next_cursor.is_some(),
+            has_prev: page.prev_cursor.is_some(),
+            items: page.items.into_iter().map(db_photo_to_user_photo).collect(),
+            total: total as u32,
+            limit,
+            offset: match position {
+                PagePosition::Offset(offset) => *offset,
+                PagePosition::Cursor(_) => 0,
+            },
+            next_cursor: page.next_cursor,
+            prev_cursor: page.prev_cursor,
+        })
+    }
+
+    /// Moves a quarantined photo back into the public area after an admin has cleared it.
+    pub async fn release_photo(&self, photo_id: Uuid) -> Result<UserPhoto> {
+        let db_photo = with_connection(&self.app_state.postgres_pool, move |conn| {
             user_photos::table
-                .filter(user_photos::id.eq(photo_id))
-                .filter(user_photos::user_id.eq(user_id))
-        )
-        .set((
-            user_photos::is_verified.eq(true),
-            user_photos::updated_at.eq(Utc::now()),
-        ))
-        .get_result::<DbUserPhoto>(&mut conn)
-        .context("Failed to verify photo or photo not found")?;
-        Ok(UserPhoto {
-            id: updated_photo.id,
-            user_id: updated_photo.user_id,
-            photo_type: updated_photo.photo_type,
-            photo_url: updated_photo.photo_url,
-            is_verified: updated_photo.is_verified,
-            created_at: updated_photo.created_at,
-            updated_at: updated_photo.updated_at,
+                .find(photo_id)
+                .filter(user_photos::status.eq(PHOTO_STATUS_QUARANTINED))
+                .first::<DbUserPhoto>(conn)
+                .context("Quarantined photo not found")
+        }).await?;
+        let storage_id = self.extract_storage_id_from_url(&db_photo.photo_url)?;
+        let storage = PhotoStorage::from_app_state(&self.app_state)?;
+        let object = storage.in_area(StorageArea::Quarantine).get(&storage_id).await
+            .context("Failed to read quarantined photo")?;
+        let file_size = object.data.len() as i64;
+        let released_id = storage.put(MongoPhoto::new(
+            db_photo.user_id,
+            db_photo.photo_type.clone(),
+            format!("{}_{}", db_photo.user_id, db_photo.photo_type),
+            file_size,
+            object.content_type,
+            object.data,
+        )).await.context("Failed to store released photo")?;
+
+        let released_url = format!("/api/v1/photos/{}", released_id);
+        let quarantined_id = storage_id.clone();
+        let result = with_connection(&self.app_state.postgres_pool, move |conn| {
+            conn.transaction(|conn| {
+                let updated = diesel::update(user_photos::table.find(photo_id))
+                    .set((
+                        user_photos::photo_url.eq(released_url),
+                        user_photos::status.eq(PHOTO_STATUS_ACTIVE),
+                        user_photos::scan_verdict.eq(Some(SCAN_VERDICT_RELEASED)),
+                        user_photos::updated_at.eq(Utc::now()),
+                    ))
+                    .get_result::<DbUserPhoto>(conn)
+                    .context("Failed to release photo")?;
+                let entry = enqueue_blob_deletion_in(conn, &quarantined_id, StorageArea::Quarantine)?;
+                Ok::<_, anyhow::Error>((updated, entry))
+            })
+        }).await;
+        let (updated, outbox_entry) = match result {
+            Ok(result) => result,
+            Err(e) => {
+                if let Err(delete_error) = storage.delete(&released_id).await {
+                    warn!("Failed to remove released copy {} after metadata error: {}", released_id, delete_error);
+                }
+                if is_active_duplicate(&e) {
+                    return Err(PhotoRejected::Duplicate.into());
+                }
+                return Err(e);
+            }
+        };
+        let reconciler = PhotoReconciler::new(self.app_state.clone());
+        if !reconciler.apply_blob_deletion(&outbox_entry).await? {
+            warn!("Photo {} released; quarantined blob {} queued for deletion", photo_id, storage_id);
+        }
+        info!("Photo released from quarantine: {}", photo_id);
+        Ok(db_photo_to_user_photo(updated))
+    }
+
+    /// Marks a photo as failed review and archives it; the retention job purges it later.
+    pub async fn reject_photo(&self, photo_id: Uuid) -> Result<UserPhoto> {
+        let now = Utc::now();
+        let updated_photo = with_connection(&self.app_state.postgres_pool, move |conn| {
+            diesel::update(user_photos::table.find(photo_id))
+                .set((
+                    user_photos::status.eq(PHOTO_STATUS_REJECTED),
+                    user_photos::is_verified.eq(false),
+                    user_photos::archived_at.eq(Some(now)),
+                    user_photos::updated_at.eq(now),
+                ))
+                .get_result::<DbUserPhoto>(conn)
+                .context("Failed to reject photo or photo not found")
+        }).await?;
+        Ok(db_photo_to_user_photo(updated_photo))
+    }
+
+    /// Lists pairs of photos with identical content or, when perceptual hashes are present,
+    /// images within `max_distance` differing bits of each other.
+    ///
+    /// Pages step through photos newest first; each photo is compared only with older photos of
+    /// its type, so every pair shows up once, and contributes at most `MAX_DUPLICATES_PER_PHOTO`
+    /// of its closest matches.
+    pub async fn find_possible_duplicates(
+        &self,
+        query: DuplicatePhotoQuery,
+        position: &PagePosition,
+    ) -> Result<PaginatedResponse<PossibleDuplicate>> {
+        let max_distance = query.max_distance
+            .unwrap_or(self.app_state.config.current().storage.perceptual_hash_max_distance) as i32;
+        let limit = query.limit;
+        let scan_position = position.clone();
+        let (total, photos, rows) = with_connection(&self.app_state.postgres_pool, move |conn| {
+            let position = scan_position;
+            let mut count = user_photos::table.into_boxed();
+            let mut photos = user_photos::table.into_boxed();
+            if let Some(photo_type) = &query.photo_type {
+                count = count.filter(user_photos::photo_type.eq(photo_type.clone()));
+                photos = photos.filter(user_photos::photo_type.eq(photo_type.clone()));
+            }
+            let total = count
+                .count()
+                .get_result::<i64>(conn)
+                .context("Failed to count photos")?;
+            photos = match &position {
+                PagePosition::Offset(offset) => photos.offset(*offset as i64),
+                PagePosition::Cursor(cursor) if position.scans_descending(true) => photos
+                    .filter(user_photos::created_at.lt(cursor.created_at)
+                        .or(user_photos::created_at.eq(cursor.created_at).and(user_photos::id.lt(cursor.id)))),
+                PagePosition::Cursor(cursor) => photos
+                    .filter(user_photos::created_at.gt(cursor.created_at)
+                        .or(user_photos::created_at.eq(cursor.created_at).and(user_photos::id.gt(cursor.id)))),
+            };
+            photos = if position.scans_descending(true) {
+                photos.order((user_photos::created_at.desc(), user_photos::id.desc()))
+            } else {
+                photos.order((user_photos::created_at.asc(), user_photos::id.asc()))
+            };
+            let photos: Vec<(DateTime<Utc>, Uuid)> = photos
+                .select((user_photos::created_at, user_photos::id))
+                .limit(limit as i64 + 1)
+                .load(conn)
+                .context("Failed to load photos to check for duplicates")?;
+            let ids: Vec<Uuid> = photos.iter().take(limit as usize).map(|(_, id)| *id).collect();
+            let rows = diesel::sql_query(
+                "SELECT a.id AS photo_id, a.user_id, b.id AS duplicate_photo_id, b.user_id AS duplicate_user_id, \
+                        a.photo_type::text AS photo_type, b.exact_match, b.distance, \
+                        (a.needs_review OR b.needs_review) AS needs_review \
+                 FROM user_photos a \
+                 CROSS JOIN LATERAL ( \
+                     SELECT b.id, b.user_id, b.needs_review, \
+                            COALESCE(a.content_sha256 = b.content_sha256, false) AS exact_match, \
+                            bit_count(int8send(a.perceptual_hash # b.perceptual_hash))::int4 AS distance \
+                     FROM user_photos b \
+                     WHERE b.photo_type = a.photo_type \
+                       AND (b.created_at, b.id) < (a.created_at, a.id) \
+                       AND (a.content_sha256 = b.content_sha256 \
+                            OR bit_count(int8send(a.perceptual_hash # b.perceptual_hash)) <= $1) \
+                       AND (NOT $2 OR a.user_id <> b.user_id) \
+                     ORDER BY exact_match DESC, distance ASC NULLS LAST, b.created_at DESC \
+                     LIMIT $3 \
+                 ) b \
+                 WHERE a.id = ANY($4) \
+                 ORDER BY a.created_at DESC, a.id DESC, b.exact_match DESC, b.distance ASC NULLS LAST"
+            )
+            .bind::<diesel::sql_types::Integer, _>(max_distance)
+            .bind::<diesel::sql_types::Bool, _>(query.cross_account_only)
+            .bind::<diesel::sql_types::BigInt, _>(MAX_DUPLICATES_PER_PHOTO)
+            .bind::<diesel::sql_types::Array<diesel::sql_types::Uuid>, _>(ids)
+            .load::<DbPossibleDuplicate>(conn)
+            .context("Failed to query possible duplicate photos")?;
+            Ok::<_, anyhow::Error>((total, photos, rows))
+        }).await?;
+        let page = keyset_page(photos, limit as usize, position, |(created_at, id)| (*created_at, *id));
+        Ok(PaginatedResponse {
+            has_next: page.next_cursor.is_some(),
+            has_prev: page.prev_cursor.is_some(),
+            items: rows.into_iter().map(PossibleDuplicate::from).collect(),
+            total: total as u32,
+            limit,
+            offset: match position {
+                PagePosition::Offset(offset) => *offset,
+                PagePosition::Cursor(_) => 0,
+            },
+            next_cursor: page.next_cursor,
+            prev_cursor: page.prev_cursor,
         })
     }
-    async fn store_photo_in_mongodb(&self, photo: MongoPhoto) -> Result<ObjectId> {
-        let db = get_database(&self.app_state.mongodb_client, "stander_db");
-        let collection = get_collection::<MongoPhoto>(&db, "photos");
-        let result = collection.insert_one(photo, None).await
-            .context("Failed to insert photo into MongoDB")?;
-        result.inserted_id.as_object_id()
-            .ok_or_else(|| anyhow::anyhow!("Failed to get inserted photo ID"))
+
+    fn fingerprint(&self, sha256: String, photo_data: &[u8]) -> PhotoFingerprint {
+        let perceptual_hash = if self.app_state.config.current().storage.perceptual_hash_enabled {
+            match perceptual_hash(photo_data) {
+                Ok(hash) => Some(hash as i64),
+                Err(e) => {
+                    warn!("Could not compute perceptual hash: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        PhotoFingerprint { sha256, perceptual_hash }
+    }
+
+    async fn find_own_duplicate(&self, user_id: Uuid, photo_type: &str, sha256: &str) -> Result<Option<UserPhoto>> {
+        let photo_type = photo_type.to_string();
+        let sha256 = sha256.to_string();
+        let db_photo = with_connection(&self.app_state.postgres_pool, move |conn| {
+            user_photos::table
+                .filter(user_photos::user_id.eq(user_id))
+                .filter(user_photos::photo_type.eq(photo_type))
+                .filter(user_photos::content_sha256.eq(sha256))
+                .filter(user_photos::status.eq(PHOTO_STATUS_ACTIVE))
+                .first::<DbUserPhoto>(conn)
+                .optional()
+                .context("Failed to query photo from database")
+        }).await?;
+        Ok(db_photo.map(db_photo_to_user_photo))
     }
 
     async fn store_photo_metadata_in_postgres(
@@ -163,53 +702,285 @@ impl PhotoService {
         user_id: Uuid,
         photo_type: String,
         photo_url: String,
+        fingerprint: PhotoFingerprint,
+        verdict: &ScanVerdict,
     ) -> Result<UserPhoto> {
-        let mut conn = get_connection(&self.app_state.postgres_pool)
-            .context("Failed to get database connection")?;
-        let now = Utc::now();
-        let new_photo = NewDbUserPhoto {
-            user_id,
-            photo_type: photo_type.clone(),
-            photo_url: photo_url.clone(),
-            is_verified: false,
-            created_at: now,
-            updated_at: now,
-        };
-        let db_photo = diesel::insert_into(user_photos::table)
-            .values(&new_photo)
-            .get_result::<DbUserPhoto>(&mut conn)
-            .context("Failed to insert photo metadata into PostgreSQL")?;
-        Ok(UserPhoto {
-            id: db_photo.id,
-            user_id: db_photo.user_id,
-            photo_type: db_photo.photo_type,
-            photo_url: db_photo.photo_url,
-            is_verified: db_photo.is_verified,
-            created_at: db_photo.created_at,
-            updated_at: db_photo.updated_at,
-        })
+        let status = if verdict.is_clean() { PHOTO_STATUS_ACTIVE } else { PHOTO_STATUS_QUARANTINED };
+        let max_distance = self.app_state.config.current().storage.perceptual_hash_max_distance as i32;
+        let quota = self.app_state.config.current().photo_policy.quotas.get(&photo_type).copied()
+            .filter(|_| status == PHOTO_STATUS_ACTIVE);
+        let verdict = verdict.clone();
+        let db_photo = with_connection(&self.app_state.postgres_pool, move |conn| conn.transaction(|conn| {
+            // The same identity document turning up on another account is flagged on both sides.
+            let matched = if is_identity_document(&photo_type) {
+                let mut candidates = user_photos::table
+                    .filter(user_photos::photo_type.eq(&photo_type))
+                    .filter(user_photos::user_id.ne(user_id))
+                    .into_boxed();
+                candidates = match fingerprint.perceptual_hash {
+                    Some(hash) => candidates.filter(
+                        user_photos::content_sha256.eq(&fingerprint.sha256).or(
+                            diesel::dsl::sql::<diesel::sql_types::Bool>("bit_count(int8send(perceptual_hash # ")
+                                .bind::<diesel::sql_types::BigInt, _>(hash)
+                                .sql(")) <= ")
+                                .bind::<diesel::sql_types::Integer, _>(max_distance)
+                        )
+                    ),
+                    None => candidates.filter(user_photos::content_sha256.eq(&fingerprint.sha256)),
+                };
+                candidates
+                    .select(user_photos::id)
+                    .first::<Uuid>(conn)
+                    .optional()
+                    .context("Failed to check for duplicate documents")?
+            } else {
+                None
+            };
+            let now = Utc::now();
+            let new_photo = NewDbUserPhoto {
+                user_id,
+                photo_type: photo_type.clone(),
+                photo_url: photo_url.clone(),
+                is_verified: false,
+                created_at: now,
+                updated_at: now,
+                content_sha256: Some(fingerprint.sha256.clone()),
+                perceptual_hash: fingerprint.perceptual_hash,
+                needs_review: matched.is_some(),
+                duplicate_of: matched,
+                status: status.to_string(),
+                archived_at: None,
+                scan_verdict: Some(verdict.as_str().to_string()),
+                scan_detail: verdict.detail().map(str::to_string),
+            };
+            let db_photo = diesel::insert_into(user_photos::table)
+                .values(&new_photo)
+                .get_result::<DbUserPhoto>(conn)
+                .context("Failed to insert photo metadata into PostgreSQL")?;
+            if let Some(matched) = matched {
+                warn!("Photo {} of user {} duplicates document {} on another account; flagged for review", db_photo.id, user_id, matched);
+                diesel::update(user_photos::table.find(matched))
+                    .set((
+                        user_photos::needs_review.eq(true),
+                        user_photos::updated_at.eq(now),
+                    ))
+                    .execute(conn)
+                    .context("Failed to flag duplicate document for review")?;
+            }
+            if let Some(limit) = quota {
+                // Newest `limit` photos of this type stay active; older ones are archived.
+                let superseded: Vec<Uuid> = user_photos::table
+                    .filter(user_photos::user_id.eq(user_id))
+                    .filter(user_photos::photo_type.eq(&photo_type))
+                    .filter(user_photos::status.eq(PHOTO_STATUS_ACTIVE))
+                    .order((user_photos::created_at.desc(), user_photos::id.desc()))
+                    .offset(limit as i64)
+                    .select(user_photos::id)
+                    .load(conn)
+                    .context("Failed to check photo quota")?;
+                if !superseded.is_empty() {
+                    diesel::update(user_photos::table.filter(user_photos::id.eq_any(&superseded)))
+                        .set((
+                            user_photos::status.eq(PHOTO_STATUS_SUPERSEDED),
+                            user_photos::archived_at.eq(Some(now)),
+                            user_photos::updated_at.eq(now),
+                        ))
+                        .execute(conn)
+                        .context("Failed to archive superseded photos")?;
+                    info!("Archived {} superseded {} photos for user {}", superseded.len(), photo_type, user_id);
+                }
+            }
+            Ok::<_, anyhow::Error>(db_photo)
+        })).await?;
+        Ok(db_photo_to_user_photo(db_photo))
     }
 
-    async fn delete_photo_from_mongodb(&self, photo_id: &str) -> Result<()> {
-        let object_id = ObjectId::parse_str(photo_id)
-            .context("Invalid photo ID format")?;
-        let db = get_database(&self.app_state.mongodb_client, "stander_db");
-        let collection = get_collection::<MongoPhoto>(&db, "photos");
-        let filter = mongodb::bson::doc! { "_id": object_id };
-        let result = collection.delete_one(filter, None).await
-            .context("Failed to delete photo from MongoDB")?;
-        if result.deleted_count == 0 {
-            return Err(anyhow::anyhow!("Photo not found in MongoDB"));
+    /// Records metadata for a freshly stored blob. If that fails the blob is deleted again,
+    /// or queued for deletion when storage refuses, so no orphan is left behind.
+    async fn store_photo_metadata_or_compensate(
+        &self,
+        storage: &PhotoStorage,
+        storage_id: &str,
+        user_id: Uuid,
+        photo_type: String,
+        fingerprint: PhotoFingerprint,
+        verdict: &ScanVerdict,
+    ) -> Result<UserPhoto> {
+        let photo_url = format!("/api/v1/photos/{}", storage_id);
+        let (dedup_type, sha256) = (photo_type.clone(), fingerprint.sha256.clone());
+        match self.store_photo_metadata_in_postgres(user_id, photo_type, photo_url, fingerprint, verdict).await {
+            Ok(user_photo) => Ok(user_photo),
+            Err(e) => {
+                if let Err(delete_error) = storage.delete(storage_id).await {
+                    warn!("Failed to remove photo blob {} after metadata error: {}", storage_id, delete_error);
+                    let reconciler = PhotoReconciler::new(self.app_state.clone());
+                    if let Err(queue_error) = reconciler.enqueue_blob_deletion(storage_id, storage.area()).await {
+                        warn!("Failed to queue photo blob {} for deletion: {}", storage_id, queue_error);
+                    }
+                }
+                // A concurrent upload of the same file won the insert; answer with its photo.
+                if is_active_duplicate(&e) {
+                    if let Some(existing) = self.find_own_duplicate(user_id, &dedup_type, &sha256).await? {
+                        info!("Deduplicated concurrent photo upload for user {}: {}", user_id, existing.id);
+                        return Ok(existing);
+                    }
+                }
+                Err(e.context("Failed to store photo metadata in PostgreSQL"))
+            }
         }
-        Ok(())
     }
 
-    fn extract_mongo_id_from_url(&self, photo_url: &str) -> Result<String> {
-        let parts: Vec<&str> = photo_url.split('/').collect();
-        if parts.len() >= 4 && parts[parts.len() - 2] == "photos" {
-            Ok(parts[parts.len() - 1].to_string())
-        } else {
-            Err(anyhow::anyhow!("Invalid photo URL format: {}", photo_url))
+    /// Runs the configured content scanner. A scanner failure holds the upload for review
+    /// rather than letting it through unchecked.
+    async fn scan_photo(&self, photo_data: &[u8]) -> ScanVerdict {
+        let scanner = match scanner_from_config(&self.app_state.config.current().scanner) {
+            Ok(scanner) => scanner,
+            Err(e) => return ScanVerdict::NeedsReview(e.to_string()),
+        };
+        match scanner.scan(photo_data).await {
+            Ok(ScanVerdict::Clean) => ScanVerdict::Clean,
+            Ok(verdict) => {
+                warn!("{} scanner flagged upload as {}: {}", scanner.name(), verdict.as_str(), verdict.detail().unwrap_or_default());
+                verdict
+            }
+            Err(e) => {
+                warn!("{} scanner failed, holding upload for review: {}", scanner.name(), e);
+                ScanVerdict::NeedsReview(format!("Scanner error: {}", e))
+            }
         }
     }
+
+    fn extract_storage_id_from_url(&self, photo_url: &str) -> Result<String> {
+        storage_id_from_url(photo_url)
+            .ok_or_else(|| anyhow::anyhow!("Invalid photo URL format: {}", photo_url))
+    }
+}
+
+/// Where the blob for a photo with this scanner verdict is kept.
+pub fn storage_area_for(scan_verdict: Option<&str>) -> StorageArea {
+    match scan_verdict {
+        Some("infected") | Some("needs_review") => StorageArea::Quarantine,
+        _ => StorageArea::Photos,
+    }
+}
+
+/// Storage ID from a `/api/v1/photos/<id>` URL.
+pub fn storage_id_from_url(photo_url: &str) -> Option<String> {
+    let parts: Vec<&str> = photo_url.split('/').collect();
+    if parts.len() >= 4 && parts[parts.len() - 2] == "photos" {
+        Some(parts[parts.len() - 1].to_string())
+    } else {
+        None
+    }
+}
+
+pub fn validate_photo_type(photo_type: &str) -> Result<()> {
+    if !matches!(photo_type, "profile" | "emirates_id" | "verification") {
+        return Err(PhotoRejected::InvalidType(photo_type.to_string()).into());
+    }
+    Ok(())
+}
+
+async fn abort_upload(writer: PhotoUploadWriter, user_id: Uuid) {
+    if let Err(e) = writer.abort().await {
+        warn!("Failed to abort photo upload for user {}: {}", user_id, e);
+    }
+}
+
+/// Whether `error` is the unique index allowing one active photo per user, type and content.
+fn is_active_duplicate(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| matches!(
+        cause.downcast_ref::<diesel::result::Error>(),
+        Some(diesel::result::Error::DatabaseError(diesel::result::DatabaseErrorKind::UniqueViolation, info))
+            if info.constraint_name() == Some(ACTIVE_CONTENT_INDEX)
+    ))
+}
+
+/// Photo types that show a government ID and must not be shared between accounts.
+fn is_identity_document(photo_type: &str) -> bool {
+    photo_type == "emirates_id"
+}
+
+/// 64-bit difference hash: survives re-encoding and resizing, unlike a byte checksum.
+pub fn perceptual_hash(photo_data: &[u8]) -> Result<u64> {
+    let image = image::load_from_memory(photo_data)
+        .context("Failed to decode photo")?;
+    let pixels = image.resize_exact(9, 8, image::imageops::FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if pixels.get_pixel(x, y)[0] < pixels.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    Ok(hash)
+}
+
+pub fn content_type_for_extension(file_extension: &str) -> Result<&'static str> {
+    match file_extension.to_lowercase().as_str() {
+        "jpg" | "jpeg" => Ok("image/jpeg"),
+        "png" => Ok("image/png"),
+        "gif" => Ok("image/gif"),
+        "webp" => Ok("image/webp"),
+        _ => Err(PhotoRejected::UnsupportedFormat(file_extension.to_string()).into()),
+    }
+}
+
+fn db_photo_to_user_photo(db_photo: DbUserPhoto) -> UserPhoto {
+    UserPhoto {
+        id: db_photo.id,
+        user_id: db_photo.user_id,
+        photo_type: db_photo.photo_type,
+        photo_url: db_photo.photo_url,
+        is_verified: db_photo.is_verified,
+        created_at: db_photo.created_at,
+        updated_at: db_photo.updated_at,
+        status: db_photo.status,
+        scan_verdict: db_photo.scan_verdict,
+    }
+}
+
+/// Renders a derivative that fits within `size` x `size`. JPEGs stay JPEG, everything else becomes PNG.
+fn resize_photo(object: PhotoObject, size: u32) -> Result<PhotoObject> {
+    let image = image::load_from_memory(&object.data)
+        .context("Failed to decode photo")?;
+    let (format, content_type) = if object.content_type == "image/jpeg" {
+        (image::ImageOutputFormat::Jpeg(85), "image/jpeg")
+    } else {
+        (image::ImageOutputFormat::Png, "image/png")
+    };
+    let mut data = std::io::Cursor::new(Vec::new());
+    image.thumbnail(size, size)
+        .write_to(&mut data, format)
+        .context("Failed to encode photo derivative")?;
+    Ok(PhotoObject {
+        content_type: content_type.to_string(),
+        data: data.into_inner(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(image: &image::DynamicImage, format: image::ImageOutputFormat) -> Vec<u8> {
+        let mut data = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut data, format).unwrap();
+        data.into_inner()
+    }
+
+    #[test]
+    fn test_perceptual_hash_survives_reencoding() {
+        let gradient = image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(64, 48, |x, y| {
+            image::Rgb([(x * 4) as u8, (y * 5) as u8, ((x * y) % 256) as u8])
+        }));
+        let png = perceptual_hash(&encode(&gradient, image::ImageOutputFormat::Png)).unwrap();
+        let jpeg = perceptual_hash(&encode(&gradient, image::ImageOutputFormat::Jpeg(60))).unwrap();
+        assert!((png ^ jpeg).count_ones() <= 6);
+
+        let flipped = perceptual_hash(&encode(&gradient.fliph(), image::ImageOutputFormat::Png)).unwrap();
+        assert!((png ^ flipped).count_ones() > 6);
+    }
 }
@@ -0,0 +1,426 @@
+//! Photo blob storage backends (MongoDB, AWS S3, Huawei OBS)
+//!
+//! OBS is S3-compatible, so it shares the S3 code paths through `HuaweiConfig::obs_client`,
+//! including multipart uploads and V4-presigned download URLs.
+
+use anyhow::{Result, Context};
+use futures::io::AsyncWriteExt;
+use futures::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::gridfs::{GridFsBucket, GridFsUploadStream};
+use mongodb::options::{GridFsBucketOptions, GridFsUploadOptions};
+use mongodb::Database;
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::cloud::aws;
+use crate::common::dependencies::{self, DependencyUnavailable};
+use crate::database::mongodb::{get_database, get_collection, MongoClient};
+use crate::models::MongoPhoto;
+use crate::AppState;
+
+/// S3 rejects multipart parts smaller than this, except the last one.
+const S3_MIN_PART_SIZE: usize = 5 * 1024 * 1024;
+
+/// A blob found in photo storage, as seen by the reconciler.
+#[derive(Debug, Clone)]
+pub struct StoredBlob {
+    pub id: String,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PhotoObject {
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+/// Which part of the store a blob lives in. Quarantined uploads are kept apart so they are
+/// never served through the normal photo paths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StorageArea {
+    Photos,
+    Quarantine,
+}
+
+impl StorageArea {
+    pub const ALL: [StorageArea; 2] = [StorageArea::Photos, StorageArea::Quarantine];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Photos => "photos",
+            Self::Quarantine => "quarantine",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        if value == "quarantine" { Self::Quarantine } else { Self::Photos }
+    }
+
+    fn mongo_collection(&self) -> &'static str {
+        match self {
+            Self::Photos => "photos",
+            Self::Quarantine => "quarantined_photos",
+        }
+    }
+
+    fn gridfs_bucket(&self) -> &'static str {
+        match self {
+            Self::Photos => "photo_files",
+            Self::Quarantine => "quarantine_files",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PhotoStorage {
+    backend: PhotoBackend,
+    area: StorageArea,
+}
+
+#[derive(Clone)]
+enum PhotoBackend {
+    MongoDb(MongoClient),
+    /// AWS S3, or Huawei OBS through its S3-compatible API.
+    S3 {
+        client: aws_sdk_s3::Client,
+        bucket: String,
+    },
+}
+
+impl PhotoStorage {
+    /// Fails with `DependencyUnavailable` when the backend holding the photos is an optional
+    /// dependency that is currently unavailable.
+    pub fn from_app_state(app_state: &AppState) -> Result<Self> {
+        Self::ensure_available(app_state)?;
+        let config = app_state.config.current();
+        let storage = &config.storage;
+        if storage.backend != "mongodb" && storage.bucket.is_empty() {
+            return Err(anyhow::anyhow!("PHOTO_STORAGE_BUCKET is required for the {} backend", storage.backend));
+        }
+        let backend = match storage.backend.as_str() {
+            "mongodb" => PhotoBackend::MongoDb(app_state.mongodb_client.current()),
+            "s3" => {
+                let aws_config = app_state.aws_config.current()
+                    .ok_or_else(|| anyhow::anyhow!("S3 photo storage requires AWS services to be enabled"))?;
+                PhotoBackend::S3 {
+                    client: aws_config.s3_client,
+                    bucket: storage.bucket.clone(),
+                }
+            }
+            "obs" => {
+                let huawei_config = app_state.huawei_config.current()
+                    .ok_or_else(|| anyhow::anyhow!("OBS photo storage requires Huawei services to be enabled"))?;
+                PhotoBackend::S3 {
+                    client: huawei_config.obs_client,
+                    bucket: storage.bucket.clone(),
+                }
+            }
+            other => return Err(anyhow::anyhow!("Unsupported photo storage backend: {}", other)),
+        };
+        Ok(Self { backend, area: StorageArea::Photos })
+    }
+
+    pub fn ensure_available(app_state: &AppState) -> Result<(), DependencyUnavailable> {
+        let dependency = match app_state.config.current().storage.backend.as_str() {
+            "s3" => dependencies::AWS,
+            "obs" => dependencies::HUAWEI,
+            _ => dependencies::MONGODB,
+        };
+        app_state.dependencies.check(dependency)
+    }
+
+    /// The same backend, addressing another area.
+    pub fn in_area(&self, area: StorageArea) -> Self {
+        Self { backend: self.backend.clone(), area }
+    }
+
+    pub fn area(&self) -> StorageArea {
+        self.area
+    }
+
+    /// Stores the blob and returns the storage ID used in `/api/v1/photos/<id>` URLs.
+    pub async fn put(&self, photo: MongoPhoto) -> Result<String> {
+        match &self.backend {
+            PhotoBackend::MongoDb(client) => {
+                let db = get_database(client, "stander_db");
+                let collection = get_collection::<MongoPhoto>(&db, self.area.mongo_collection());
+                let result = collection.insert_one(photo, None).await
+                    .context("Failed to insert photo into MongoDB")?;
+                result.inserted_id.as_object_id()
+                    .map(|id| id.to_hex())
+                    .ok_or_else(|| anyhow::anyhow!("Failed to get inserted photo ID"))
+            }
+            PhotoBackend::S3 { client, bucket } => {
+                let id = Uuid::new_v4().simple().to_string();
+                aws::s3::put_object(client, bucket, &self.object_key(&id), photo.photo_data, &photo.content_type).await?;
+                Ok(id)
+            }
+        }
+    }
+
+    pub async fn get(&self, id: &str) -> Result<PhotoObject> {
+        match &self.backend {
+            PhotoBackend::MongoDb(client) => {
+                let object_id = ObjectId::parse_str(id)
+                    .context("Invalid photo ID format")?;
+                let db = get_database(client, "stander_db");
+                let collection = get_collection::<MongoPhoto>(&db, self.area.mongo_collection());
+                let filter = mongodb::bson::doc! { "_id": object_id };
+                if let Some(photo) = collection.find_one(filter, None).await
+                    .context("Failed to query MongoDB")? {
+                    return Ok(PhotoObject {
+                        content_type: photo.content_type,
+                        data: photo.photo_data,
+                    });
+                }
+                // Streamed uploads are kept in GridFS rather than inline documents.
+                let bucket = self.gridfs_bucket(&db);
+                let file = bucket.find(doc! { "_id": object_id }, None).await
+                    .context("Failed to query GridFS")?
+                    .try_next().await
+                    .context("Failed to query GridFS")?
+                    .ok_or_else(|| anyhow::anyhow!("Photo not found"))?;
+                let content_type = file.metadata.as_ref()
+                    .and_then(|metadata| metadata.get_str("content_type").ok())
+                    .unwrap_or("application/octet-stream")
+                    .to_string();
+                let mut data = Vec::with_capacity(file.length as usize);
+                bucket.download_to_futures_0_3_writer(object_id.into(), &mut data).await
+                    .context("Failed to download photo from GridFS")?;
+                Ok(PhotoObject { content_type, data })
+            }
+            PhotoBackend::S3 { client, bucket } => {
+                let (data, content_type) = aws::s3::get_object(client, bucket, &self.object_key(id)).await?;
+                let content_type = content_type.unwrap_or_else(|| detect_content_type(&data).to_string());
+                Ok(PhotoObject { content_type, data })
+            }
+        }
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<()> {
+        match &self.backend {
+            PhotoBackend::MongoDb(client) => {
+                let object_id = ObjectId::parse_str(id)
+                    .context("Invalid photo ID format")?;
+                let db = get_database(client, "stander_db");
+                let collection = get_collection::<MongoPhoto>(&db, self.area.mongo_collection());
+                let filter = mongodb::bson::doc! { "_id": object_id };
+                let result = collection.delete_one(filter, None).await
+                    .context("Failed to delete photo from MongoDB")?;
+                if result.deleted_count == 0 {
+                    self.gridfs_bucket(&db).delete(object_id.into()).await
+                        .map_err(|_| anyhow::anyhow!("Photo not found in MongoDB"))?;
+                }
+                Ok(())
+            }
+            PhotoBackend::S3 { client, bucket } => aws::s3::delete_object(client, bucket, &self.object_key(id)).await,
+        }
+    }
+
+    /// Lists every photo blob in the backend.
+    pub async fn list(&self) -> Result<Vec<StoredBlob>> {
+        match &self.backend {
+            PhotoBackend::MongoDb(client) => {
+                let db = get_database(client, "stander_db");
+                let collection = get_collection::<mongodb::bson::Document>(&db, self.area.mongo_collection());
+                let options = mongodb::options::FindOptions::builder()
+                    .projection(doc! { "_id": 1, "created_at": 1 })
+                    .build();
+                let mut blobs: Vec<StoredBlob> = collection.find(doc! {}, options).await
+                    .context("Failed to list photos in MongoDB")?
+                    .try_filter_map(|document| async move {
+                        Ok(document.get_object_id("_id").ok().map(|id| StoredBlob {
+                            id: id.to_hex(),
+                            created_at: document.get_datetime("created_at").ok().and_then(|t| DateTime::from_timestamp_millis(t.timestamp_millis())),
+                        }))
+                    })
+                    .try_collect()
+                    .await
+                    .context("Failed to list photos in MongoDB")?;
+                let files: Vec<_> = self.gridfs_bucket(&db).find(doc! {}, None).await
+                    .context("Failed to list GridFS photos")?
+                    .try_collect()
+                    .await
+                    .context("Failed to list GridFS photos")?;
+                blobs.extend(files.into_iter().filter_map(|file| {
+                    file.id.as_object_id().map(|id| StoredBlob {
+                        id: id.to_hex(),
+                        created_at: DateTime::from_timestamp_millis(file.upload_date.timestamp_millis()),
+                    })
+                }));
+                Ok(blobs)
+            }
+            PhotoBackend::S3 { client, bucket } => {
+                let objects = aws::s3::list_objects(client, bucket, &self.key_prefix()).await?;
+                Ok(objects.into_iter()
+                    .filter_map(|(key, created_at)| {
+                        key.strip_prefix(&self.key_prefix()).map(|id| StoredBlob { id: id.to_string(), created_at })
+                    })
+                    .collect())
+            }
+        }
+    }
+
+    pub async fn exists(&self, id: &str) -> Result<bool> {
+        match &self.backend {
+            PhotoBackend::MongoDb(client) => {
+                let object_id = match ObjectId::parse_str(id) {
+                    Ok(object_id) => object_id,
+                    Err(_) => return Ok(false),
+                };
+                let db = get_database(client, "stander_db");
+                let collection = get_collection::<mongodb::bson::Document>(&db, self.area.mongo_collection());
+                if collection.count_documents(doc! { "_id": object_id }, None).await
+                    .context("Failed to query MongoDB")? > 0 {
+                    return Ok(true);
+                }
+                let file = self.gridfs_bucket(&db).find(doc! { "_id": object_id }, None).await
+                    .context("Failed to query GridFS")?
+                    .try_next().await
+                    .context("Failed to query GridFS")?;
+                Ok(file.is_some())
+            }
+            PhotoBackend::S3 { client, bucket } => aws::s3::object_exists(client, bucket, &self.object_key(id)).await,
+        }
+    }
+
+    /// Opens a streamed write; chunks go to GridFS or an S3 (or OBS) multipart upload.
+    pub async fn start_upload(
+        &self,
+        user_id: Uuid,
+        photo_type: &str,
+        file_name: &str,
+        content_type: &str,
+    ) -> Result<PhotoUploadWriter> {
+        match &self.backend {
+            PhotoBackend::MongoDb(client) => {
+                let db = get_database(client, "stander_db");
+                let options = GridFsUploadOptions::builder()
+                    .metadata(doc! {
+                        "user_id": user_id.to_string(),
+                        "photo_type": photo_type,
+                        "content_type": content_type,
+                    })
+                    .build();
+                Ok(PhotoUploadWriter::MongoDb(Box::new(self.gridfs_bucket(&db).open_upload_stream(file_name, options))))
+            }
+            PhotoBackend::S3 { client, bucket } => {
+                let id = Uuid::new_v4().simple().to_string();
+                let key = self.object_key(&id);
+                let upload_id = aws::s3::create_multipart_upload(client, bucket, &key, content_type).await?;
+                Ok(PhotoUploadWriter::S3 {
+                    client: client.clone(),
+                    bucket: bucket.clone(),
+                    id,
+                    key,
+                    upload_id,
+                    parts: Vec::new(),
+                    buffer: Vec::new(),
+                })
+            }
+        }
+    }
+
+    fn gridfs_bucket(&self, db: &Database) -> GridFsBucket {
+        db.gridfs_bucket(GridFsBucketOptions::builder().bucket_name(self.area.gridfs_bucket().to_string()).build())
+    }
+
+    fn key_prefix(&self) -> String {
+        format!("{}/", self.area.as_str())
+    }
+
+    fn object_key(&self, id: &str) -> String {
+        format!("{}{}", self.key_prefix(), id)
+    }
+
+    /// Native presigned download URL, or `None` when the backend cannot issue one.
+    pub async fn presigned_url(&self, id: &str, expires_in: Duration) -> Result<Option<String>> {
+        match &self.backend {
+            PhotoBackend::MongoDb(_) => Ok(None),
+            PhotoBackend::S3 { client, bucket } => {
+                aws::s3::presign_get_object(client, bucket, &self.object_key(id), expires_in).await.map(Some)
+            }
+        }
+    }
+}
+
+/// An in-progress streamed photo write. Call `finish` to commit or `abort` to discard.
+pub enum PhotoUploadWriter {
+    MongoDb(Box<GridFsUploadStream>),
+    S3 {
+        client: aws_sdk_s3::Client,
+        bucket: String,
+        id: String,
+        key: String,
+        upload_id: String,
+        parts: Vec<aws_sdk_s3::types::CompletedPart>,
+        buffer: Vec<u8>,
+    },
+}
+
+impl PhotoUploadWriter {
+    pub async fn write_chunk(&mut self, chunk: &[u8]) -> Result<()> {
+        match self {
+            Self::MongoDb(stream) => stream.write_all(chunk).await
+                .context("Failed to write photo chunk to GridFS"),
+            Self::S3 { client, bucket, key, upload_id, parts, buffer, .. } => {
+                buffer.extend_from_slice(chunk);
+                if buffer.len() >= S3_MIN_PART_SIZE {
+                    let part_number = parts.len() as i32 + 1;
+                    let data = std::mem::take(buffer);
+                    parts.push(aws::s3::upload_part(client, bucket, key, upload_id, part_number, data).await?);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Commits the write and returns the storage ID.
+    pub async fn finish(self) -> Result<String> {
+        match self {
+            Self::MongoDb(mut stream) => {
+                stream.close().await
+                    .context("Failed to finish GridFS upload")?;
+                stream.id().as_object_id()
+                    .map(|id| id.to_hex())
+                    .ok_or_else(|| anyhow::anyhow!("Failed to get uploaded photo ID"))
+            }
+            Self::S3 { client, bucket, id, key, upload_id, mut parts, buffer } => {
+                if !buffer.is_empty() || parts.is_empty() {
+                    let part_number = parts.len() as i32 + 1;
+                    parts.push(aws::s3::upload_part(&client, &bucket, &key, &upload_id, part_number, buffer).await?);
+                }
+                aws::s3::complete_multipart_upload(&client, &bucket, &key, &upload_id, parts).await?;
+                Ok(id)
+            }
+        }
+    }
+
+    pub async fn abort(self) -> Result<()> {
+        match self {
+            Self::MongoDb(mut stream) => stream.abort().await
+                .context("Failed to abort GridFS upload"),
+            Self::S3 { client, bucket, key, upload_id, .. } => {
+                aws::s3::abort_multipart_upload(&client, &bucket, &key, &upload_id).await
+            }
+        }
+    }
+}
+
+
+/// Sniffs the image format from its magic bytes.
+pub fn detect_content_type(data: &[u8]) -> &'static str {
+    if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        "image/jpeg"
+    } else if data.starts_with(&[0x89, b'P', b'N', b'G']) {
+        "image/png"
+    } else if data.starts_with(b"GIF8") {
+        "image/gif"
+    } else if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        "image/webp"
+    } else {
+        "application/octet-stream"
+    }
+}
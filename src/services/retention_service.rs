@@ -0,0 +1,172 @@
+//! Photo retention policy: purges superseded and rejected photos once they age out
+
+use anyhow::{Result, Context};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::Serialize;
+use std::time::Duration;
+use tracing::{info, warn, error};
+use uuid::Uuid;
+
+use crate::database::postgres::with_connection;
+use crate::models::{DbUserPhoto, NewDbPhotoRetentionLog};
+use crate::schema::{photo_retention_log, user_photos};
+use crate::services::photo_reconciler::{PhotoReconciler, enqueue_blob_deletion_in};
+use crate::services::photo_service::{storage_id_from_url, storage_area_for, PHOTO_STATUS_REJECTED, PHOTO_STATUS_SUPERSEDED};
+use crate::AppState;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PurgedPhoto {
+    pub photo_id: Uuid,
+    pub user_id: Uuid,
+    pub photo_type: String,
+    pub status: String,
+    pub archived_at: Option<DateTime<Utc>>,
+}
+
+/// Outcome of one retention run. Every purged photo is also recorded in `photo_retention_log`
+/// under `run_id`.
+#[derive(Debug, Clone, Serialize)]
+pub struct RetentionReport {
+    pub run_id: Uuid,
+    pub dry_run: bool,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    /// Photos archived before this instant were eligible.
+    pub cutoff: DateTime<Utc>,
+    pub purged: Vec<PurgedPhoto>,
+    /// Photos whose metadata was removed but whose blob delete is queued for retry.
+    pub blob_deletes_pending: Vec<Uuid>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct RetentionService {
+    app_state: AppState,
+}
+
+impl RetentionService {
+    pub fn new(app_state: AppState) -> Self {
+        Self { app_state }
+    }
+
+    pub async fn run_policy(&self, dry_run: bool) -> Result<RetentionReport> {
+        let config = self.app_state.config.current();
+        let policy = &config.photo_policy;
+        let started_at = Utc::now();
+        let cutoff = started_at - chrono::Duration::days(policy.retention_days as i64);
+        let mut report = RetentionReport {
+            run_id: Uuid::new_v4(),
+            dry_run,
+            started_at,
+            finished_at: started_at,
+            cutoff,
+            purged: Vec::new(),
+            blob_deletes_pending: Vec::new(),
+            errors: Vec::new(),
+        };
+
+        let retention_photo_types = policy.retention_photo_types.clone();
+        let candidates = with_connection(&self.app_state.postgres_pool, move |conn| {
+            user_photos::table
+                .filter(user_photos::photo_type.eq_any(retention_photo_types))
+                .filter(user_photos::status.eq_any([PHOTO_STATUS_SUPERSEDED, PHOTO_STATUS_REJECTED]))
+                .filter(user_photos::archived_at.lt(cutoff))
+                .order(user_photos::archived_at.asc())
+                .load::<DbUserPhoto>(conn)
+                .context("Failed to load photos due for retention")
+        }).await?;
+
+        let reconciler = PhotoReconciler::new(self.app_state.clone());
+        for photo in candidates {
+            let purged = PurgedPhoto {
+                photo_id: photo.id,
+                user_id: photo.user_id,
+                photo_type: photo.photo_type.clone(),
+                status: photo.status.clone(),
+                archived_at: photo.archived_at,
+            };
+            if dry_run {
+                report.purged.push(purged);
+                continue;
+            }
+            // Metadata, audit row and blob delete intent commit together; the blob goes afterwards.
+            let run_id = report.run_id;
+            let photo_id = photo.id;
+            let outbox_entry = with_connection(&self.app_state.postgres_pool, move |conn| conn.transaction(|conn| {
+                diesel::delete(user_photos::table.find(photo.id))
+                    .execute(conn)
+                    .context("Failed to delete photo metadata")?;
+                diesel::insert_into(photo_retention_log::table)
+                    .values(&NewDbPhotoRetentionLog {
+                        run_id,
+                        photo_id: photo.id,
+                        user_id: photo.user_id,
+                        photo_type: photo.photo_type.clone(),
+                        photo_status: photo.status.clone(),
+                        photo_url: photo.photo_url.clone(),
+                        archived_at: photo.archived_at,
+                        purged_at: Utc::now(),
+                    })
+                    .execute(conn)
+                    .context("Failed to write retention log")?;
+                let area = storage_area_for(photo.scan_verdict.as_deref());
+                storage_id_from_url(&photo.photo_url)
+                    .map(|storage_id| enqueue_blob_deletion_in(conn, &storage_id, area))
+                    .transpose()
+            })).await;
+            let outbox_entry = match outbox_entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    error!("Failed to purge photo {}: {}", photo_id, e);
+                    report.errors.push(format!("{}: {}", photo_id, e));
+                    continue;
+                }
+            };
+            if let Some(entry) = outbox_entry {
+                match reconciler.apply_blob_deletion(&entry).await {
+                    Ok(true) => {}
+                    Ok(false) => report.blob_deletes_pending.push(photo_id),
+                    Err(e) => {
+                        warn!("Failed to delete blob for purged photo {}: {}", photo_id, e);
+                        report.blob_deletes_pending.push(photo_id);
+                    }
+                }
+            }
+            report.purged.push(purged);
+        }
+
+        report.finished_at = Utc::now();
+        Ok(report)
+    }
+}
+
+/// Periodically applies the retention policy. Does nothing when the interval is 0. Stops at
+/// shutdown, letting a running pass finish.
+pub fn spawn_retention_task(app_state: AppState) -> Option<tokio::task::JoinHandle<()>> {
+    let interval_seconds = app_state.config.current().photo_policy.retention_interval_seconds;
+    if interval_seconds == 0 {
+        return None;
+    }
+    let shutdown = app_state.shutdown.clone();
+    let retention_service = RetentionService::new(app_state);
+    Some(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_seconds));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.triggered() => return,
+            }
+            match retention_service.run_policy(false).await {
+                Ok(report) => info!(
+                    "Photo retention run {}: {} purged, {} blob deletes pending, {} errors",
+                    report.run_id,
+                    report.purged.len(),
+                    report.blob_deletes_pending.len(),
+                    report.errors.len(),
+                ),
+                Err(e) => error!("Photo retention run failed: {}", e),
+            }
+        }
+    }))
+}
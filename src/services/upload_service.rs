@@ -0,0 +1,291 @@
+//! Resumable photo upload sessions (tus protocol backing store)
+
+use anyhow::Context;
+use uuid::Uuid;
+use diesel::prelude::*;
+use chrono::Utc;
+use std::time::Duration;
+use tracing::{info, error};
+
+use crate::models::user::UploadSession;
+use crate::models::db_models::{DbUploadSession, NewDbUploadChunk, NewDbUploadSession};
+use crate::database::postgres::with_connection;
+use crate::schema::{upload_chunks, upload_sessions, users};
+use crate::services::PhotoService;
+use crate::services::photo_service::{
+    validate_photo_type, content_type_for_extension, rejection_cause, PhotoRejected, MAX_PHOTO_SIZE_BYTES,
+};
+use crate::AppState;
+
+const EXPIRY_SWEEP_INTERVAL: Duration = Duration::from_secs(600);
+
+#[derive(Debug, thiserror::Error)]
+pub enum UploadError {
+    #[error("Upload not found or expired")]
+    NotFound,
+    #[error("User not found")]
+    UserNotFound,
+    #[error("Upload offset mismatch: expected {expected}, got {actual}")]
+    OffsetMismatch { expected: i64, actual: i64 },
+    #[error("Chunk exceeds declared upload length")]
+    ExceedsLength,
+    #[error("Upload already completed")]
+    Completed,
+    #[error(transparent)]
+    Rejected(PhotoRejected),
+    #[error(transparent)]
+    Internal(#[from] anyhow::Error),
+}
+
+impl UploadError {
+    /// Keeps client faults reported by `PhotoService` apart from server failures.
+    pub fn from_photo_error(error: anyhow::Error) -> Self {
+        match rejection_cause(&error) {
+            Some(rejected) => Self::Rejected(rejected.clone()),
+            None => Self::Internal(error),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct UploadService {
+    app_state: AppState,
+}
+
+impl UploadService {
+    pub fn new(app_state: AppState) -> Self {
+        Self { app_state }
+    }
+
+    pub async fn create_upload(
+        &self,
+        user_id: Uuid,
+        photo_type: String,
+        file_extension: String,
+        upload_length: i64,
+    ) -> Result<UploadSession, UploadError> {
+        validate_photo_type(&photo_type).map_err(UploadError::from_photo_error)?;
+        content_type_for_extension(&file_extension).map_err(UploadError::from_photo_error)?;
+        if upload_length <= 0 || upload_length as usize > MAX_PHOTO_SIZE_BYTES {
+            return Err(UploadError::ExceedsLength);
+        }
+        let now = Utc::now();
+        let new_session = NewDbUploadSession {
+            user_id,
+            photo_type,
+            file_extension,
+            upload_length,
+            upload_offset: 0,
+            expires_at: now + self.expiry(),
+            created_at: now,
+            updated_at: now,
+        };
+        // Admins may name another account, so check it exists rather than fail on the foreign key.
+        let session = with_connection(&self.app_state.postgres_pool, move |conn| {
+            let user_exists = diesel::select(diesel::dsl::exists(users::table.find(user_id)))
+                .get_result::<bool>(conn)
+                .context("Failed to look up upload owner")?;
+            if !user_exists {
+                return Ok(None);
+            }
+            diesel::insert_into(upload_sessions::table)
+                .values(&new_session)
+                .returning(DbUploadSession::as_returning())
+                .get_result(conn)
+                .map(Some)
+                .context("Failed to create upload session")
+        }).await?;
+        session.map(db_session_to_session).ok_or(UploadError::UserNotFound)
+    }
+
+    /// Returns the session unless it does not exist or has expired.
+    pub async fn get_upload(&self, id: Uuid) -> Result<Option<UploadSession>, UploadError> {
+        let session = with_connection(&self.app_state.postgres_pool, move |conn| {
+            upload_sessions::table
+                .find(id)
+                .filter(upload_sessions::expires_at.gt(Utc::now()))
+                .select(DbUploadSession::as_select())
+                .first(conn)
+                .optional()
+                .context("Failed to query upload session")
+        }).await?;
+        Ok(session.map(db_session_to_session))
+    }
+
+    /// Stages a chunk at `offset` as its own row. When the last byte arrives the staged
+    /// chunks are joined, passed to `PhotoService::upload_photo` and released.
+    ///
+    /// If storing the photo fails the chunks are kept, and an empty chunk at the final
+    /// offset tries again. Once a photo has been created the session accepts no more chunks.
+    pub async fn append_chunk(&self, id: Uuid, offset: i64, chunk: &[u8]) -> Result<UploadSession, UploadError> {
+        let session = self.get_upload(id).await?.ok_or(UploadError::NotFound)?;
+        if session.photo_id.is_some() {
+            return Err(UploadError::Completed);
+        }
+        if session.upload_offset != offset {
+            return Err(UploadError::OffsetMismatch { expected: session.upload_offset, actual: offset });
+        }
+        if offset + chunk.len() as i64 > session.upload_length {
+            return Err(UploadError::ExceedsLength);
+        }
+        let now = Utc::now();
+        let expires_at = now + self.expiry();
+        let chunk = chunk.to_vec();
+        // The offset guard makes concurrent PATCHes for the same offset lose cleanly.
+        let updated = with_connection(&self.app_state.postgres_pool, move |conn| conn.transaction(|conn| {
+            let chunk_len = chunk.len() as i64;
+            let updated = diesel::update(
+                upload_sessions::table
+                    .filter(upload_sessions::id.eq(id))
+                    .filter(upload_sessions::upload_offset.eq(offset))
+                    .filter(upload_sessions::photo_id.is_null())
+            )
+            .set((
+                upload_sessions::upload_offset.eq(upload_sessions::upload_offset + chunk_len),
+                upload_sessions::expires_at.eq(expires_at),
+                upload_sessions::updated_at.eq(now),
+            ))
+            .returning(DbUploadSession::as_returning())
+            .get_result(conn)
+            .optional()
+            .context("Failed to advance upload offset")?;
+            if updated.is_some() && !chunk.is_empty() {
+                diesel::insert_into(upload_chunks::table)
+                    .values(&NewDbUploadChunk { session_id: id, chunk_offset: offset, data: chunk })
+                    .execute(conn)
+                    .context("Failed to stage upload chunk")?;
+            }
+            Ok::<_, anyhow::Error>(updated)
+        })).await?;
+        let session = match updated {
+            Some(session) => db_session_to_session(session),
+            None => {
+                let current = self.get_upload(id).await?.ok_or(UploadError::NotFound)?;
+                if current.photo_id.is_some() {
+                    return Err(UploadError::Completed);
+                }
+                return Err(UploadError::OffsetMismatch { expected: current.upload_offset, actual: offset });
+            }
+        };
+        if session.is_complete() {
+            return self.finish_upload(session).await;
+        }
+        Ok(session)
+    }
+
+    pub async fn delete_upload(&self, id: Uuid) -> Result<bool, UploadError> {
+        let deleted = with_connection(&self.app_state.postgres_pool, move |conn| {
+            diesel::delete(upload_sessions::table.find(id))
+                .execute(conn)
+                .context("Failed to delete upload session")
+        }).await?;
+        Ok(deleted > 0)
+    }
+
+    pub async fn purge_expired_uploads(&self) -> anyhow::Result<usize> {
+        with_connection(&self.app_state.postgres_pool, |conn| {
+            diesel::delete(upload_sessions::table.filter(upload_sessions::expires_at.le(Utc::now())))
+                .execute(conn)
+                .context("Failed to purge expired upload sessions")
+        }).await
+    }
+
+    /// Joins the staged chunks and stores them as a photo. Safe to call again after a failure or a
+    /// concurrent finish: the chunks are only released together with recording the photo id.
+    async fn finish_upload(&self, mut session: UploadSession) -> Result<UploadSession, UploadError> {
+        let session_id = session.id;
+        let (finished_as, chunks) = with_connection(&self.app_state.postgres_pool, move |conn| {
+            let finished_as: Option<Uuid> = upload_sessions::table
+                .find(session_id)
+                .select(upload_sessions::photo_id)
+                .first(conn)
+                .context("Failed to read upload session")?;
+            let chunks: Vec<Vec<u8>> = upload_chunks::table
+                .filter(upload_chunks::session_id.eq(session_id))
+                .order(upload_chunks::chunk_offset.asc())
+                .select(upload_chunks::data)
+                .load(conn)
+                .context("Failed to read staged upload chunks")?;
+            Ok::<_, anyhow::Error>((finished_as, chunks))
+        }).await?;
+        if finished_as.is_some() {
+            session.photo_id = finished_as;
+            return Ok(session);
+        }
+        let photo_service = PhotoService::new(self.app_state.clone());
+        let photo = photo_service.upload_photo(
+            session.user_id,
+            session.photo_type.clone(),
+            chunks.concat(),
+            session.file_extension.clone(),
+        ).await.map_err(UploadError::from_photo_error)?;
+        let photo_id = photo.id;
+        let completed = with_connection(&self.app_state.postgres_pool, move |conn| conn.transaction(|conn| {
+            let completed = diesel::update(
+                upload_sessions::table
+                    .filter(upload_sessions::id.eq(session_id))
+                    .filter(upload_sessions::photo_id.is_null())
+            )
+            .set((
+                upload_sessions::photo_id.eq(photo_id),
+                upload_sessions::updated_at.eq(Utc::now()),
+            ))
+            .returning(DbUploadSession::as_returning())
+            .get_result(conn)
+            .optional()
+            .context("Failed to mark upload session complete")?;
+            if completed.is_some() {
+                diesel::delete(upload_chunks::table.filter(upload_chunks::session_id.eq(session_id)))
+                    .execute(conn)
+                    .context("Failed to release staged upload chunks")?;
+            }
+            Ok::<_, anyhow::Error>(completed)
+        })).await?;
+        match completed {
+            Some(completed) => {
+                info!("Resumable upload {} completed as photo {}", session.id, photo.id);
+                Ok(db_session_to_session(completed))
+            }
+            // A concurrent request finished first; its photo id is the one clients already saw.
+            None => self.get_upload(session_id).await?.ok_or(UploadError::NotFound),
+        }
+    }
+
+    fn expiry(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.app_state.config.current().storage.upload_expiry_seconds as i64)
+    }
+}
+
+/// Periodically deletes abandoned upload sessions until shutdown starts.
+pub fn spawn_upload_expiry_task(app_state: AppState) -> tokio::task::JoinHandle<()> {
+    let shutdown = app_state.shutdown.clone();
+    let upload_service = UploadService::new(app_state);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(EXPIRY_SWEEP_INTERVAL);
+        loop {
+            // A sweep already under way finishes before the task stops.
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.triggered() => return,
+            }
+            match upload_service.purge_expired_uploads().await {
+                Ok(0) => {}
+                Ok(purged) => info!("Purged {} expired upload sessions", purged),
+                Err(e) => error!("Failed to purge expired upload sessions: {}", e),
+            }
+        }
+    })
+}
+
+fn db_session_to_session(session: DbUploadSession) -> UploadSession {
+    UploadSession {
+        id: session.id,
+        user_id: session.user_id,
+        photo_type: session.photo_type,
+        file_extension: session.file_extension,
+        upload_length: session.upload_length,
+        upload_offset: session.upload_offset,
+        photo_id: session.photo_id,
+        expires_at: session.expires_at,
+    }
+}
@@ -2,13 +2,17 @@
 
 use anyhow::{Result, Context};
 use uuid::Uuid;
+use diesel::pg::Pg;
 use diesel::prelude::*;
 use chrono::Utc;
-use crate::models::user::{User, CreateUser, UpdateUser, UserPhoto};
+use crate::models::user::{User, CreateUser, UpdateUser, UserPhoto, UserListFilter, UserListPage, UserSortField};
+use crate::models::common::SortOrder;
 use crate::models::db_models::{DbUser, NewDbUser, UpdateDbUser, DbUserPhoto};
-use crate::database::postgres::get_connection;
+use crate::database::postgres::with_connection;
 use crate::schema::{users, user_photos};
-use crate::utils::encryption::hash_password;
+use crate::utils::pagination::{keyset_page, PageCursor, PagePosition};
+use crate::services::auth_service::hash_password;
+use crate::services::photo_service::PHOTO_STATUS_ACTIVE;
 use crate::AppState;
 
 #[derive(Clone)]
@@ -21,85 +25,114 @@ impl UserService {
         Self { app_state }
     }
 
-    pub async fn list_users(&self, limit: u32, offset: u32) -> Result<Vec<User>> {
-        let mut conn = get_connection(&self.app_state.postgres_pool)
-            .context("Failed to get database connection")?;
-        let db_users = users::table
-            .limit(limit as i64)
-            .offset(offset as i64)
-            .load::<DbUser>(&mut conn)
-            .context("Failed to load users from database")?;
-        let mut result_users = Vec::new();
-        for db_user in db_users {
-            let photos = self.get_user_photos(db_user.id).await?;
-            result_users.push(self.db_user_to_user(db_user, photos));
+    /// Returns one page of users matching `filter`, plus the total number of matches.
+    /// Cursor positions are only valid for listings sorted by creation time.
+    pub async fn list_users(
+        &self,
+        filter: &UserListFilter,
+        sort_by: UserSortField,
+        sort_order: SortOrder,
+        limit: u32,
+        position: &PagePosition,
+        include_photos: bool,
+    ) -> Result<UserListPage> {
+        if matches!(position, PagePosition::Cursor(_)) && sort_by != UserSortField::CreatedAt {
+            return Err(anyhow::anyhow!("Cursor pagination requires sorting by created_at"));
         }
-        Ok(result_users)
+        let filter = filter.clone();
+        let position = position.clone();
+        let (total, page, photos) = with_connection(&self.app_state.postgres_pool, move |conn| {
+            let total = filtered_users(&filter)
+                .count()
+                .get_result::<i64>(conn)
+                .context("Failed to count users")?;
+            let query = match &position {
+                PagePosition::Offset(offset) => sorted_users(filtered_users(&filter), sort_by, sort_order)
+                    .offset(*offset as i64),
+                PagePosition::Cursor(cursor) => {
+                    let descending = position.scans_descending(sort_order == SortOrder::Descending);
+                    users_after_cursor(filtered_users(&filter), cursor, descending)
+                }
+            };
+            let db_users = query
+                .limit(limit as i64 + 1)
+                .load::<DbUser>(conn)
+                .context("Failed to load users from database")?;
+            let page = keyset_page(db_users, limit as usize, &position, |user| (user.created_at, user.id));
+            let photos = if include_photos {
+                load_active_photos(conn, &page.items)?
+            } else {
+                vec![Vec::new(); page.items.len()]
+            };
+            Ok((total, page, photos))
+        }).await?;
+        // Cursors are keyed on creation time, so they mean nothing for other sort orders.
+        let keyed = sort_by == UserSortField::CreatedAt;
+        let result_users = page.items.into_iter()
+            .zip(photos)
+            .map(|(db_user, photos)| self.db_user_to_user(db_user, photos))
+            .collect();
+        Ok(UserListPage {
+            users: result_users,
+            total: total as u64,
+            next_cursor: page.next_cursor.filter(|_| keyed),
+            prev_cursor: page.prev_cursor.filter(|_| keyed),
+        })
     }
 
     pub async fn get_user(&self, id: Uuid) -> Result<Option<User>> {
-        let mut conn = get_connection(&self.app_state.postgres_pool)
-            .context("Failed to get database connection")?;
-        let db_user = users::table
-            .find(id)
-            .first::<DbUser>(&mut conn)
-            .optional()
-            .context("Failed to query user from database")?;
-        match db_user {
-            Some(user) => {
-                let photos = self.get_user_photos(user.id).await?;
-                Ok(Some(self.db_user_to_user(user, photos)))
-            }
-            None => Ok(None),
-        }
+        let db_user = with_connection(&self.app_state.postgres_pool, move |conn| {
+            let db_user = users::table
+                .find(id)
+                .first::<DbUser>(conn)
+                .optional()
+                .context("Failed to query user from database")?;
+            with_photos(conn, db_user)
+        }).await?;
+        Ok(db_user.map(|(user, photos)| self.db_user_to_user(user, photos)))
     }
 
     pub async fn get_user_by_email(&self, email: &str) -> Result<Option<User>> {
-        let mut conn = get_connection(&self.app_state.postgres_pool)
-            .context("Failed to get database connection")?;
-        let db_user = users::table
-            .filter(users::email.eq(email))
-            .first::<DbUser>(&mut conn)
-            .optional()
-            .context("Failed to query user by email from database")?;
-        match db_user {
-            Some(user) => {
-                let photos = self.get_user_photos(user.id).await?;
-                Ok(Some(self.db_user_to_user(user, photos)))
-            }
-            None => Ok(None),
-        }
+        let email = email.to_string();
+        let db_user = with_connection(&self.app_state.postgres_pool, move |conn| {
+            let db_user = users::table
+                .filter(users::email.eq(email))
+                .first::<DbUser>(conn)
+                .optional()
+                .context("Failed to query user by email from database")?;
+            with_photos(conn, db_user)
+        }).await?;
+        Ok(db_user.map(|(user, photos)| self.db_user_to_user(user, photos)))
     }
 
     pub async fn create_user(&self, create_data: CreateUser) -> Result<User> {
-        let mut conn = get_connection(&self.app_state.postgres_pool)
-            .context("Failed to get database connection")?;
-        let now = Utc::now();
-        let password_hash = hash_password(&create_data.password);
-        let new_user = NewDbUser {
-            email: create_data.email,
-            password_hash,
-            country_code: Some(create_data.country_code),
-            phone: create_data.phone,
-            first_name: create_data.first_name,
-            last_name: create_data.last_name,
-            role: create_data.role,
-            is_active: true,
-            email_verified: false,
-            phone_verified: false,
-            created_at: now,
-            updated_at: now,
-        };
-        let db_user = diesel::insert_into(users::table)
-            .values(&new_user)
-            .get_result::<DbUser>(&mut conn)
-            .context("Failed to insert user into database")?;
+        // Same scheme `AuthService` verifies logins against.
+        let password_hash = hash_password(&create_data.password).await?;
+        let db_user = with_connection(&self.app_state.postgres_pool, move |conn| {
+            let now = Utc::now();
+            let new_user = NewDbUser {
+                email: create_data.email,
+                password_hash,
+                country_code: Some(create_data.country_code),
+                phone: create_data.phone,
+                first_name: create_data.first_name,
+                last_name: create_data.last_name,
+                role: create_data.role,
+                is_active: true,
+                email_verified: false,
+                phone_verified: false,
+                created_at: now,
+                updated_at: now,
+            };
+            diesel::insert_into(users::table)
+                .values(&new_user)
+                .get_result::<DbUser>(conn)
+                .context("Failed to insert user into database")
+        }).await?;
         Ok(self.db_user_to_user(db_user, vec![]))
     }
 
     pub async fn update_user(&self, id: Uuid, update_data: UpdateUser) -> Result<Option<User>> {
-        let mut conn = get_connection(&self.app_state.postgres_pool)
-            .context("Failed to get database connection")?;
         let update_changeset = UpdateDbUser {
             email: update_data.email,
             country_code: update_data.country_code,
@@ -111,95 +144,59 @@ impl UserService {
             phone_verified: None,
             updated_at: Utc::now(),
         };
-
-        let updated_user = diesel::update(users::table.find(id))
-            .set(&update_changeset)
-            .get_result::<DbUser>(&mut conn)
-            .optional()
-            .context("Failed to update user in database")?;
-        match updated_user {
-            Some(user) => {
-                let photos = self.get_user_photos(user.id).await?;
-                Ok(Some(self.db_user_to_user(user, photos)))
-            }
-            None => Ok(None),
-        }
+        let updated_user = with_connection(&self.app_state.postgres_pool, move |conn| {
+            let updated_user = diesel::update(users::table.find(id))
+                .set(&update_changeset)
+                .get_result::<DbUser>(conn)
+                .optional()
+                .context("Failed to update user in database")?;
+            with_photos(conn, updated_user)
+        }).await?;
+        Ok(updated_user.map(|(user, photos)| self.db_user_to_user(user, photos)))
     }
 
     pub async fn delete_user(&self, id: Uuid) -> Result<bool> {
-        let mut conn = get_connection(&self.app_state.postgres_pool)
-            .context("Failed to get database connection")?;
-        let deleted_count = diesel::delete(users::table.find(id))
-            .execute(&mut conn)
-            .context("Failed to delete user from database")?;
+        let deleted_count = with_connection(&self.app_state.postgres_pool, move |conn| {
+            diesel::delete(users::table.find(id))
+                .execute(conn)
+                .context("Failed to delete user from database")
+        }).await?;
         Ok(deleted_count > 0)
     }
 
     pub async fn activate_user(&self, id: Uuid) -> Result<bool> {
-        let mut conn = get_connection(&self.app_state.postgres_pool)
-            .context("Failed to get database connection")?;
-        let update_changeset = UpdateDbUser {
-            email: None,
-            country_code: None,
-            phone: None,
-            first_name: None,
-            last_name: None,
-            is_active: Some(true),
-            email_verified: None,
-            phone_verified: None,
-            updated_at: Utc::now(),
-        };
-        let updated_count = diesel::update(users::table.find(id))
-            .set(&update_changeset)
-            .execute(&mut conn)
-            .context("Failed to activate user in database")?;
-
-        Ok(updated_count > 0)
+        self.set_active(id, true).await
     }
 
     pub async fn deactivate_user(&self, id: Uuid) -> Result<bool> {
-        let mut conn = get_connection(&self.app_state.postgres_pool)
-            .context("Failed to get database connection")?;
+        self.set_active(id, false).await
+    }
 
+    async fn set_active(&self, id: Uuid, is_active: bool) -> Result<bool> {
         let update_changeset = UpdateDbUser {
             email: None,
             country_code: None,
             phone: None,
             first_name: None,
             last_name: None,
-            is_active: Some(false),
+            is_active: Some(is_active),
             email_verified: None,
             phone_verified: None,
             updated_at: Utc::now(),
         };
-
-        let updated_count = diesel::update(users::table.find(id))
-            .set(&update_changeset)
-            .execute(&mut conn)
-            .context("Failed to deactivate user in database")?;
-
+        let updated_count = with_connection(&self.app_state.postgres_pool, move |conn| {
+            diesel::update(users::table.find(id))
+                .set(&update_changeset)
+                .execute(conn)
+                .context(if is_active {
+                    "Failed to activate user in database"
+                } else {
+                    "Failed to deactivate user in database"
+                })
+        }).await?;
         Ok(updated_count > 0)
     }
 
-    async fn get_user_photos(&self, user_id: Uuid) -> Result<Vec<UserPhoto>> {
-        let mut conn = get_connection(&self.app_state.postgres_pool)
-            .context("Failed to get database connection")?;
-        let db_photos = user_photos::table
-            .filter(user_photos::user_id.eq(user_id))
-            .load::<DbUserPhoto>(&mut conn)
-            .context("Failed to load user photos from database")?;
-        let photos = db_photos.into_iter().map(|db_photo| UserPhoto {
-            id: db_photo.id,
-            user_id: db_photo.user_id,
-            photo_type: db_photo.photo_type,
-            photo_url: db_photo.photo_url,
-            is_verified: db_photo.is_verified,
-            created_at: db_photo.created_at,
-            updated_at: db_photo.updated_at,
-        }).collect();
-        Ok(photos)
-    }
-
     fn db_user_to_user(&self, db_user: DbUser, photos: Vec<UserPhoto>) -> User {
         User {
             id: db_user.id,
@@ -216,3 +213,141 @@ impl UserService {
         }
     }
 }
+
+/// Pairs an optional user with their active photos.
+fn with_photos(conn: &mut PgConnection, db_user: Option<DbUser>) -> Result<Option<(DbUser, Vec<UserPhoto>)>> {
+    match db_user {
+        Some(user) => {
+            let photos = load_active_photos(conn, std::slice::from_ref(&user))?
+                .pop()
+                .unwrap_or_default();
+            Ok(Some((user, photos)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Active photos for each of `db_users`, in the same order, fetched with one query.
+/// Not paged: superseded and rejected photos are left out and `photo_policy.quotas` caps the rest.
+fn load_active_photos(conn: &mut PgConnection, db_users: &[DbUser]) -> Result<Vec<Vec<UserPhoto>>> {
+    let db_photos = DbUserPhoto::belonging_to(db_users)
+        .filter(user_photos::status.eq(PHOTO_STATUS_ACTIVE))
+        .order(user_photos::created_at.asc())
+        .load::<DbUserPhoto>(conn)
+        .context("Failed to load user photos from database")?;
+    Ok(db_photos
+        .grouped_by(db_users)
+        .into_iter()
+        .map(|photos| photos.into_iter().map(|db_photo| UserPhoto {
+            id: db_photo.id,
+            user_id: db_photo.user_id,
+            photo_type: db_photo.photo_type,
+            photo_url: db_photo.photo_url,
+            is_verified: db_photo.is_verified,
+            created_at: db_photo.created_at,
+            updated_at: db_photo.updated_at,
+            status: db_photo.status,
+            scan_verdict: db_photo.scan_verdict,
+        }).collect())
+        .collect())
+}
+
+fn filtered_users(filter: &UserListFilter) -> users::BoxedQuery<'static, Pg> {
+    let mut query = users::table.into_boxed();
+    if let Some(role) = &filter.role {
+        query = query.filter(users::role.eq(role.clone()));
+    }
+    if let Some(is_active) = filter.is_active {
+        query = query.filter(users::is_active.eq(is_active));
+    }
+    if let Some(email_verified) = filter.email_verified {
+        query = query.filter(users::email_verified.eq(email_verified));
+    }
+    if let Some(phone_verified) = filter.phone_verified {
+        query = query.filter(users::phone_verified.eq(phone_verified));
+    }
+    if let Some(created_after) = filter.created_after {
+        query = query.filter(users::created_at.ge(created_after));
+    }
+    if let Some(created_before) = filter.created_before {
+        query = query.filter(users::created_at.lt(created_before));
+    }
+    if let Some(search) = filter.search.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        let pattern = format!("%{}%", escape_like(search));
+        query = query.filter(
+            users::first_name.ilike(pattern.clone())
+                .or(users::last_name.ilike(pattern.clone()))
+                .or(users::email.ilike(pattern.clone()))
+                .or(users::phone.ilike(pattern)),
+        );
+    }
+    query
+}
+
+/// Applies the sort, with `id` as a tie-breaker so pages are stable.
+fn sorted_users(
+    query: users::BoxedQuery<'static, Pg>,
+    sort_by: UserSortField,
+    sort_order: SortOrder,
+) -> users::BoxedQuery<'static, Pg> {
+    let query = match (sort_by, sort_order) {
+        (UserSortField::CreatedAt, SortOrder::Ascending) => query.order(users::created_at.asc()),
+        (UserSortField::CreatedAt, SortOrder::Descending) => query.order(users::created_at.desc()),
+        (UserSortField::UpdatedAt, SortOrder::Ascending) => query.order(users::updated_at.asc()),
+        (UserSortField::UpdatedAt, SortOrder::Descending) => query.order(users::updated_at.desc()),
+        (UserSortField::Email, SortOrder::Ascending) => query.order(users::email.asc()),
+        (UserSortField::Email, SortOrder::Descending) => query.order(users::email.desc()),
+        (UserSortField::FirstName, SortOrder::Ascending) => query.order(users::first_name.asc()),
+        (UserSortField::FirstName, SortOrder::Descending) => query.order(users::first_name.desc()),
+        (UserSortField::LastName, SortOrder::Ascending) => query.order(users::last_name.asc()),
+        (UserSortField::LastName, SortOrder::Descending) => query.order(users::last_name.desc()),
+        (UserSortField::Role, SortOrder::Ascending) => query.order(users::role.asc()),
+        (UserSortField::Role, SortOrder::Descending) => query.order(users::role.desc()),
+    };
+    match sort_order {
+        SortOrder::Ascending => query.then_order_by(users::id.asc()),
+        SortOrder::Descending => query.then_order_by(users::id.desc()),
+    }
+}
+
+/// Rows strictly past the cursor in `(created_at, id)` order, scanning in the given direction.
+fn users_after_cursor(
+    query: users::BoxedQuery<'static, Pg>,
+    cursor: &PageCursor,
+    descending: bool,
+) -> users::BoxedQuery<'static, Pg> {
+    if descending {
+        query
+            .filter(users::created_at.lt(cursor.created_at)
+                .or(users::created_at.eq(cursor.created_at).and(users::id.lt(cursor.id))))
+            .order((users::created_at.desc(), users::id.desc()))
+    } else {
+        query
+            .filter(users::created_at.gt(cursor.created_at)
+                .or(users::created_at.eq(cursor.created_at).and(users::id.gt(cursor.id))))
+            .order((users::created_at.asc(), users::id.asc()))
+    }
+}
+
+/// Escapes `LIKE` wildcards so user input only ever matches literally.
+fn escape_like(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_like() {
+        assert_eq!(escape_like("john"), "john");
+        assert_eq!(escape_like("50%_off\\"), "50\\%\\_off\\\\");
+    }
+}
@@ -0,0 +1,166 @@
+//! Distributed tracing with OpenTelemetry
+//!
+//! Every tracing span also becomes an OpenTelemetry span. Incoming W3C `traceparent` headers on
+//! REST and gRPC requests become the parent of the request span, so a trace continues across
+//! services, and the trace ID doubles as the request ID when the caller sends none. Spans are
+//! exported over OTLP/HTTP only when `telemetry.enabled` is set, e.g. to a local collector on
+//! `http://localhost:4318`; otherwise trace IDs are still assigned but nothing leaves the process.
+//! Span and event attributes named in `logging::PII_FIELDS` are masked before export, the same
+//! way the log formatters mask them.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::trace::{TraceContextExt, TracerProvider as _};
+use futures::future::BoxFuture;
+use opentelemetry::{KeyValue, Value};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{self, EvictedQueue, Sampler, Tracer, TracerProvider};
+use opentelemetry_sdk::Resource;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::config::TelemetryConfig;
+use crate::logging::{is_pii_field, mask};
+
+/// Builds the tracer behind the `tracing_opentelemetry` layer and registers it globally.
+pub fn init_tracer(config: &TelemetryConfig) -> Result<Tracer> {
+    opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+    let trace_config = trace::config()
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(config.sample_ratio))))
+        .with_resource(Resource::new([KeyValue::new("service.name", config.service_name.clone())]));
+    if !config.enabled {
+        let provider = TracerProvider::builder().with_config(trace_config).build();
+        let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+        opentelemetry::global::set_tracer_provider(provider);
+        return Ok(tracer);
+    }
+    let exporter = opentelemetry_otlp::new_exporter()
+        .http()
+        .with_endpoint(config.otlp_endpoint.trim_end_matches('/'))
+        .with_timeout(Duration::from_secs(10))
+        .build_span_exporter()
+        .with_context(|| format!("Failed to set up the OTLP exporter for {}", config.otlp_endpoint))?;
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(RedactingExporter(exporter), opentelemetry_sdk::runtime::Tokio)
+        .with_config(trace_config)
+        .build();
+    let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+    opentelemetry::global::set_tracer_provider(provider);
+    Ok(tracer)
+}
+
+/// Masks PII attributes on spans and their events before handing them to the inner exporter.
+#[derive(Debug)]
+pub struct RedactingExporter<E>(pub E);
+
+impl<E: SpanExporter> SpanExporter for RedactingExporter<E> {
+    fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+        self.0.export(batch.into_iter().map(redact_span).collect())
+    }
+
+    fn shutdown(&mut self) {
+        self.0.shutdown();
+    }
+
+    fn force_flush(&mut self) -> BoxFuture<'static, ExportResult> {
+        self.0.force_flush()
+    }
+}
+
+fn redact_span(mut span: SpanData) -> SpanData {
+    redact_attributes(&mut span.attributes);
+    let mut events = EvictedQueue::new(span.events.len() as u32);
+    events.extend(span.events.into_iter().map(|mut event| {
+        redact_attributes(&mut event.attributes);
+        event
+    }));
+    span.events = events;
+    span
+}
+
+fn redact_attributes(attributes: &mut [KeyValue]) {
+    for attribute in attributes.iter_mut().filter(|attribute| is_pii_field(attribute.key.as_str())) {
+        attribute.value = Value::from(mask(attribute.key.as_str(), &attribute.value.as_str()));
+    }
+}
+
+/// Flushes buffered spans to the collector. Call once, before the process exits.
+pub fn shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+/// The remote parent described by W3C trace context headers; empty when absent or malformed.
+pub fn remote_context(traceparent: Option<&str>, tracestate: Option<&str>) -> opentelemetry::Context {
+    let mut carrier = HashMap::new();
+    if let Some(traceparent) = traceparent {
+        carrier.insert("traceparent".to_string(), traceparent.to_string());
+    }
+    if let Some(tracestate) = tracestate {
+        carrier.insert("tracestate".to_string(), tracestate.to_string());
+    }
+    TraceContextPropagator::new().extract(&carrier)
+}
+
+/// Makes `span` a child of the trace named in the request's `traceparent` header, if any.
+pub fn set_remote_parent(span: &tracing::Span, traceparent: Option<&str>, tracestate: Option<&str>) {
+    if traceparent.is_some() {
+        span.set_parent(remote_context(traceparent, tracestate));
+    }
+}
+
+/// Hex trace ID of the current span, used as the request ID in API responses.
+pub fn current_trace_id() -> Option<String> {
+    let context = tracing::Span::current().context();
+    let span = context.span();
+    let span_context = span.span_context();
+    span_context.is_valid().then(|| span_context.trace_id().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[derive(Debug, Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<SpanData>>>);
+
+    impl SpanExporter for Capture {
+        fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+            self.0.lock().unwrap().extend(batch);
+            Box::pin(std::future::ready(Ok(())))
+        }
+    }
+
+    #[test]
+    fn test_exported_attributes_are_masked() {
+        let capture = Capture::default();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(RedactingExporter(capture.clone()))
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("login", user_email = "jane.doe@example.com", attempts = 2);
+            let _entered = span.enter();
+            tracing::info!(email = "jane.doe@example.com", phone = "+971 50 123 4567", "User logged in successfully");
+        });
+        provider.force_flush();
+
+        let spans = capture.0.lock().unwrap();
+        let span = spans.iter().find(|span| span.name == "login").expect("login span exported");
+        let attribute = |attributes: &[KeyValue], key: &str| {
+            attributes.iter().find(|kv| kv.key.as_str() == key).map(|kv| kv.value.as_str().into_owned())
+        };
+        assert_eq!(attribute(&span.attributes, "user_email").as_deref(), Some("j***@example.com"));
+        assert_eq!(attribute(&span.attributes, "attempts").as_deref(), Some("2"));
+        let event = span.events.iter().next().expect("event exported");
+        assert_eq!(attribute(&event.attributes, "email").as_deref(), Some("j***@example.com"));
+        assert_eq!(attribute(&event.attributes, "phone").as_deref(), Some("***4567"));
+        assert!(!format!("{:?}", *spans).contains("jane.doe"));
+    }
+}
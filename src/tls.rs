@@ -0,0 +1,354 @@
+//! TLS for the REST and gRPC listeners
+//!
+//! Both servers use rustls with the certificate chain and key named by `tls.cert_file` and
+//! `tls.key_file`. The files are checked every `tls.reload_interval_seconds`; a renewed
+//! certificate applies to new connections without a restart, and if the new files do not load
+//! the previous certificate stays in use. gRPC can also verify client certificates against
+//! `tls.grpc_client_ca_file`, and the subject of a verified certificate names the calling
+//! service (see `service_identity`).
+
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use futures::Stream;
+use rustls::server::{AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient, NoClientAuth};
+use rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, error, info, warn};
+
+use crate::common::shutdown::Shutdown;
+use crate::config::TlsConfig;
+
+/// ALPN protocols offered by the REST listener.
+pub const REST_ALPN: &[&[u8]] = &[b"h2", b"http/1.1"];
+/// gRPC runs over HTTP/2 only.
+pub const GRPC_ALPN: &[&[u8]] = &[b"h2"];
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Whether a listener asks for client certificates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientAuth {
+    None,
+    /// Verified when presented; anonymous clients are still accepted.
+    Optional,
+    Required,
+}
+
+impl ClientAuth {
+    pub fn for_grpc(config: &TlsConfig) -> Self {
+        if config.grpc_client_ca_file.is_empty() {
+            Self::None
+        } else if config.grpc_require_client_cert {
+            Self::Required
+        } else {
+            Self::Optional
+        }
+    }
+}
+
+/// The calling service named by a verified client certificate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceIdentity(pub String);
+
+/// The identity in a DER client certificate: the subject's common name, or the whole subject
+/// when it has none.
+pub fn service_identity(der: &[u8]) -> Option<ServiceIdentity> {
+    let (_, certificate) = x509_parser::parse_x509_certificate(der).ok()?;
+    let subject = certificate.subject();
+    let name = subject.iter_common_name().next()
+        .and_then(|cn| cn.as_str().ok())
+        .map(str::to_string)
+        .unwrap_or_else(|| subject.to_string());
+    (!name.is_empty()).then_some(ServiceIdentity(name))
+}
+
+/// Builds a rustls server configuration from the files named in `config`.
+pub fn load_server_config(config: &TlsConfig, client_auth: ClientAuth, alpn: &[&[u8]]) -> Result<ServerConfig> {
+    let certs = load_certs(&config.cert_file)?;
+    let key = load_key(&config.key_file)?;
+    let verifier = match client_auth {
+        ClientAuth::None => NoClientAuth::boxed(),
+        ClientAuth::Optional => AllowAnyAnonymousOrAuthenticatedClient::new(load_roots(&config.grpc_client_ca_file)?).boxed(),
+        ClientAuth::Required => AllowAnyAuthenticatedClient::new(load_roots(&config.grpc_client_ca_file)?).boxed(),
+    };
+    let mut server_config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(certs, key)
+        .with_context(|| format!("{} does not match the certificate in {}", config.key_file, config.cert_file))?;
+    server_config.alpn_protocols = alpn.iter().map(|protocol| protocol.to_vec()).collect();
+    Ok(server_config)
+}
+
+fn open(path: &str) -> Result<BufReader<File>> {
+    Ok(BufReader::new(File::open(path).with_context(|| format!("Failed to open {}", path))?))
+}
+
+fn load_certs(path: &str) -> Result<Vec<Certificate>> {
+    let certs = rustls_pemfile::certs(&mut open(path)?)
+        .with_context(|| format!("Failed to read certificates from {}", path))?;
+    if certs.is_empty() {
+        anyhow::bail!("No PEM certificates found in {}", path);
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn load_key(path: &str) -> Result<PrivateKey> {
+    let mut reader = open(path)?;
+    loop {
+        match rustls_pemfile::read_one(&mut reader).with_context(|| format!("Failed to read private key from {}", path))? {
+            Some(rustls_pemfile::Item::PKCS8Key(key) | rustls_pemfile::Item::RSAKey(key) | rustls_pemfile::Item::ECKey(key)) => {
+                return Ok(PrivateKey(key));
+            }
+            Some(_) => continue,
+            None => anyhow::bail!("No PEM private key found in {}", path),
+        }
+    }
+}
+
+fn load_roots(path: &str) -> Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots.add(&cert).with_context(|| format!("Invalid CA certificate in {}", path))?;
+    }
+    Ok(roots)
+}
+
+/// A server configuration that follows its certificate files.
+#[derive(Clone)]
+pub struct ReloadingServerConfig {
+    settings: TlsConfig,
+    client_auth: ClientAuth,
+    alpn: &'static [&'static [u8]],
+    current: Arc<RwLock<Arc<ServerConfig>>>,
+}
+
+impl ReloadingServerConfig {
+    pub fn load(settings: &TlsConfig, client_auth: ClientAuth, alpn: &'static [&'static [u8]]) -> Result<Self> {
+        let server_config = load_server_config(settings, client_auth, alpn)?;
+        Ok(Self {
+            settings: settings.clone(),
+            client_auth,
+            alpn,
+            current: Arc::new(RwLock::new(Arc::new(server_config))),
+        })
+    }
+
+    pub fn current(&self) -> Arc<ServerConfig> {
+        self.current.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Re-reads the files. On failure the running configuration is kept.
+    pub fn reload(&self) -> Result<Arc<ServerConfig>> {
+        let server_config = Arc::new(load_server_config(&self.settings, self.client_auth, self.alpn)?);
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = server_config.clone();
+        Ok(server_config)
+    }
+
+    fn files(&self) -> Vec<&str> {
+        let mut files = vec![self.settings.cert_file.as_str(), self.settings.key_file.as_str()];
+        if self.client_auth != ClientAuth::None {
+            files.push(self.settings.grpc_client_ca_file.as_str());
+        }
+        files
+    }
+
+    /// Reloads whenever one of the files changes, then hands the new configuration to
+    /// `on_reload`. Does nothing when `tls.reload_interval_seconds` is 0.
+    pub fn spawn_reload_task(
+        &self,
+        name: &'static str,
+        shutdown: Shutdown,
+        on_reload: impl Fn(Arc<ServerConfig>) + Send + 'static,
+    ) -> Option<JoinHandle<()>> {
+        if self.settings.reload_interval_seconds == 0 {
+            return None;
+        }
+        let this = self.clone();
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(this.settings.reload_interval_seconds));
+            let mut seen = fingerprint(&this.files());
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = shutdown.triggered() => return,
+                }
+                let files = fingerprint(&this.files());
+                if files == seen {
+                    continue;
+                }
+                seen = files;
+                match this.reload() {
+                    Ok(server_config) => {
+                        info!("Reloaded the {} TLS certificate from {}", name, this.settings.cert_file);
+                        on_reload(server_config);
+                    }
+                    Err(e) => error!("Keeping the current {} TLS certificate: {:#}", name, e),
+                }
+            }
+        }))
+    }
+}
+
+/// Modification time and size of each file; a change in either triggers a reload.
+fn fingerprint(files: &[&str]) -> Vec<Option<(SystemTime, u64)>> {
+    files.iter()
+        .map(|file| Path::new(file).metadata().ok().and_then(|meta| Some((meta.modified().ok()?, meta.len()))))
+        .collect()
+}
+
+/// TLS connections accepted on `listener`, for tonic's `serve_with_incoming_shutdown`. Each
+/// handshake uses the configuration current at the time and runs on its own task, so a slow
+/// client cannot hold up others. Accepting stops once the stream is dropped.
+pub fn incoming(listener: TcpListener, config: ReloadingServerConfig) -> impl Stream<Item = io::Result<TlsStream<TcpStream>>> {
+    let (sender, receiver) = tokio::sync::mpsc::channel(64);
+    tokio::spawn(async move {
+        loop {
+            let (stream, peer) = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        // Usually out of file descriptors; back off instead of spinning.
+                        warn!("Failed to accept a connection: {}", e);
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                },
+                _ = sender.closed() => return,
+            };
+            let acceptor = TlsAcceptor::from(config.current());
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                    Ok(Ok(tls_stream)) => {
+                        let _ = sender.send(Ok(tls_stream)).await;
+                    }
+                    Ok(Err(e)) => debug!(%peer, "TLS handshake failed: {}", e),
+                    Err(_) => debug!(%peer, "TLS handshake timed out"),
+                }
+            });
+        }
+    });
+    futures::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|item| (item, receiver))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rcgen::{BasicConstraints, Certificate as GeneratedCert, CertificateParams, DnType, IsCa};
+    use rustls::{ClientConfig, ServerName};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::TlsConnector;
+
+    fn ca(name: &str) -> GeneratedCert {
+        let mut params = CertificateParams::new(Vec::new());
+        params.distinguished_name.push(DnType::CommonName, name);
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        GeneratedCert::from_params(params).unwrap()
+    }
+
+    fn leaf(common_name: &str, dns_names: &[&str]) -> GeneratedCert {
+        let mut params = CertificateParams::new(dns_names.iter().map(|name| name.to_string()).collect::<Vec<_>>());
+        params.distinguished_name.push(DnType::CommonName, common_name);
+        GeneratedCert::from_params(params).unwrap()
+    }
+
+    /// A server certificate for `localhost` and a CA for client certificates, written to a
+    /// fresh directory.
+    fn settings(dir: &Path, server_ca: &GeneratedCert, client_ca: &GeneratedCert) -> TlsConfig {
+        std::fs::create_dir_all(dir).unwrap();
+        let server = leaf("localhost", &["localhost"]);
+        let write = |name: &str, contents: String| {
+            let path = dir.join(name);
+            std::fs::write(&path, contents).unwrap();
+            path.to_string_lossy().into_owned()
+        };
+        TlsConfig {
+            enabled: true,
+            cert_file: write("server.crt", server.serialize_pem_with_signer(server_ca).unwrap()),
+            key_file: write("server.key", server.serialize_private_key_pem()),
+            reload_interval_seconds: 1,
+            grpc_client_ca_file: write("client-ca.crt", client_ca.serialize_pem().unwrap()),
+            grpc_require_client_cert: true,
+        }
+    }
+
+    fn client_config(server_ca: &GeneratedCert, client: Option<(&GeneratedCert, &GeneratedCert)>) -> Arc<ClientConfig> {
+        let mut roots = RootCertStore::empty();
+        roots.add(&Certificate(server_ca.serialize_der().unwrap())).unwrap();
+        let builder = ClientConfig::builder().with_safe_defaults().with_root_certificates(roots);
+        let mut config = match client {
+            Some((cert, signer)) => builder.with_client_auth_cert(
+                vec![Certificate(cert.serialize_der_with_signer(signer).unwrap())],
+                PrivateKey(cert.serialize_private_key_der()),
+            ).unwrap(),
+            None => builder.with_no_client_auth(),
+        };
+        config.alpn_protocols = vec![b"h2".to_vec()];
+        Arc::new(config)
+    }
+
+    /// Connects and echoes one byte; returns the client identity the server saw.
+    async fn round_trip(addr: std::net::SocketAddr, client: Arc<ClientConfig>, accepted: &mut (impl Stream<Item = io::Result<TlsStream<TcpStream>>> + Unpin)) -> io::Result<Option<ServiceIdentity>> {
+        use futures::StreamExt;
+        let stream = TcpStream::connect(addr).await?;
+        let mut tls = TlsConnector::from(client).connect(ServerName::try_from("localhost").unwrap(), stream).await?;
+        tls.write_all(b"x").await?;
+        let mut server_side = tokio::time::timeout(Duration::from_secs(2), accepted.next()).await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "no connection accepted"))?
+            .unwrap()?;
+        let mut byte = [0u8; 1];
+        server_side.read_exact(&mut byte).await?;
+        let (_, session) = server_side.get_ref();
+        assert_eq!(session.alpn_protocol(), Some(&b"h2"[..]));
+        Ok(session.peer_certificates().and_then(|certs| service_identity(&certs[0].0)))
+    }
+
+    #[tokio::test]
+    async fn test_mutual_tls_identity_and_reload() {
+        let dir = std::env::temp_dir().join(format!("tls-test-{}", uuid::Uuid::new_v4()));
+        let server_ca = ca("Test Server CA");
+        let client_ca = ca("Test Client CA");
+        let settings = settings(&dir, &server_ca, &client_ca);
+        let config = ReloadingServerConfig::load(&settings, ClientAuth::for_grpc(&settings), GRPC_ALPN).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut accepted = Box::pin(incoming(listener, config.clone()));
+
+        let billing = leaf("billing-service", &[]);
+        let identity = round_trip(addr, client_config(&server_ca, Some((&billing, &client_ca))), &mut accepted).await.unwrap();
+        assert_eq!(identity, Some(ServiceIdentity("billing-service".to_string())));
+
+        // Without a client certificate the handshake is refused.
+        assert!(round_trip(addr, client_config(&server_ca, None), &mut accepted).await.is_err());
+
+        // A certificate issued by a new CA is picked up without restarting.
+        let rotated_ca = ca("Rotated Server CA");
+        settings_with_server_ca(&dir, &rotated_ca);
+        config.reload().unwrap();
+        let identity = round_trip(addr, client_config(&rotated_ca, Some((&billing, &client_ca))), &mut accepted).await.unwrap();
+        assert_eq!(identity, Some(ServiceIdentity("billing-service".to_string())));
+
+        // A broken key keeps the running certificate.
+        std::fs::write(dir.join("server.key"), "not a key").unwrap();
+        assert!(config.reload().is_err());
+        assert!(round_trip(addr, client_config(&rotated_ca, Some((&billing, &client_ca))), &mut accepted).await.is_ok());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    fn settings_with_server_ca(dir: &Path, server_ca: &GeneratedCert) {
+        let server = leaf("localhost", &["localhost"]);
+        std::fs::write(dir.join("server.crt"), server.serialize_pem_with_signer(server_ca).unwrap()).unwrap();
+        std::fs::write(dir.join("server.key"), server.serialize_private_key_pem()).unwrap();
+    }
+}
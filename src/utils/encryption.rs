@@ -2,8 +2,11 @@
 
 use anyhow::Result;
 use sha2::{Sha256, Digest};
+use hmac::{Hmac, Mac};
 use base64::{Engine as _, engine::general_purpose};
 
+type HmacSha256 = Hmac<Sha256>;
+
 pub fn hash_password(password: &str) -> String {
     let mut hasher = Sha256::new();
     hasher.update(password.as_bytes());
@@ -55,6 +58,19 @@ pub fn generate_checksum(data: &[u8]) -> String {
 pub fn verify_checksum(data: &[u8], expected_checksum: &str) -> bool {
     generate_checksum(data) == expected_checksum
 }
+pub fn sign_hmac_sha256(key: &[u8], message: &[u8]) -> String {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    general_purpose::URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+}
+pub fn verify_hmac_sha256(key: &[u8], message: &[u8], signature: &str) -> bool {
+    let Ok(signature) = general_purpose::URL_SAFE_NO_PAD.decode(signature) else {
+        return false;
+    };
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.verify_slice(&signature).is_ok()
+}
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -96,4 +112,12 @@ mod tests {
         let modified_data = b"Modified data";
         assert!(!verify_checksum(modified_data, &checksum));
     }
+    #[test]
+    fn test_hmac_signature() {
+        let signature = sign_hmac_sha256(b"key", b"payload");
+        assert!(verify_hmac_sha256(b"key", b"payload", &signature));
+        assert!(!verify_hmac_sha256(b"other-key", b"payload", &signature));
+        assert!(!verify_hmac_sha256(b"key", b"tampered", &signature));
+        assert!(!verify_hmac_sha256(b"key", b"payload", "not base64!"));
+    }
 }
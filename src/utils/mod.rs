@@ -4,9 +4,11 @@ pub mod validation;
 pub mod encryption;
 pub mod date_time;
 pub mod error;
+pub mod pagination;
 
 
 pub use validation::*;
 pub use encryption::*;
 pub use date_time::*;
 pub use error::*;
+pub use pagination::*;
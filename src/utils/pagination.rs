@@ -0,0 +1,152 @@
+//! Keyset (cursor) pagination helpers
+
+use anyhow::Result;
+use base64::{Engine as _, engine::general_purpose};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorDirection {
+    /// Rows after the cursor in the listing's order.
+    Next,
+    /// Rows before the cursor in the listing's order.
+    Prev,
+}
+
+/// Position in a listing keyed on `(created_at, id)`. Clients only ever see the encoded form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageCursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+    pub direction: CursorDirection,
+}
+
+impl PageCursor {
+    pub fn encode(&self) -> String {
+        let direction = match self.direction {
+            CursorDirection::Next => 'n',
+            CursorDirection::Prev => 'p',
+        };
+        let raw = format!("{}:{}:{}", direction, self.created_at.timestamp_micros(), self.id);
+        general_purpose::URL_SAFE_NO_PAD.encode(raw)
+    }
+
+    pub fn decode(value: &str) -> Result<Self> {
+        let raw = general_purpose::URL_SAFE_NO_PAD.decode(value)
+            .map_err(|_| anyhow::anyhow!("Invalid cursor"))?;
+        let raw = String::from_utf8(raw).map_err(|_| anyhow::anyhow!("Invalid cursor"))?;
+        let mut parts = raw.splitn(3, ':');
+        let direction = match parts.next() {
+            Some("n") => CursorDirection::Next,
+            Some("p") => CursorDirection::Prev,
+            _ => return Err(anyhow::anyhow!("Invalid cursor")),
+        };
+        let created_at = parts.next()
+            .and_then(|micros| micros.parse().ok())
+            .and_then(DateTime::from_timestamp_micros)
+            .ok_or_else(|| anyhow::anyhow!("Invalid cursor"))?;
+        let id = parts.next()
+            .and_then(|id| Uuid::parse_str(id).ok())
+            .ok_or_else(|| anyhow::anyhow!("Invalid cursor"))?;
+        Ok(Self { created_at, id, direction })
+    }
+}
+
+/// Where a page starts: classic offset, or a cursor from a previous page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PagePosition {
+    Offset(u32),
+    Cursor(PageCursor),
+}
+
+impl PagePosition {
+    /// Whether the rows have to be scanned in descending `(created_at, id)` order.
+    /// `Prev` pages are read backwards from the cursor and flipped afterwards.
+    pub fn scans_descending(&self, descending: bool) -> bool {
+        match self {
+            Self::Offset(_) => descending,
+            Self::Cursor(cursor) => descending == (cursor.direction == CursorDirection::Next),
+        }
+    }
+}
+
+/// Offset of the 1-based `page`, or `None` when it does not fit in a `u32`.
+pub fn offset_for_page(page: u32, limit: u32) -> Option<u32> {
+    page.max(1).checked_sub(1)?.checked_mul(limit)
+}
+
+#[derive(Debug, Clone)]
+pub struct KeysetPage<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+}
+
+/// Builds a page from rows fetched with `limit + 1` in scan order. The extra row only
+/// signals that another page exists and is dropped.
+pub fn keyset_page<T>(
+    mut rows: Vec<T>,
+    limit: usize,
+    position: &PagePosition,
+    key: impl Fn(&T) -> (DateTime<Utc>, Uuid),
+) -> KeysetPage<T> {
+    let has_more = rows.len() > limit;
+    rows.truncate(limit);
+    let cursor = |row: &T, direction| {
+        let (created_at, id) = key(row);
+        PageCursor { created_at, id, direction }.encode()
+    };
+    let (has_next, has_prev) = match position {
+        PagePosition::Offset(offset) => (has_more, *offset > 0),
+        PagePosition::Cursor(PageCursor { direction: CursorDirection::Next, .. }) => (has_more, true),
+        PagePosition::Cursor(PageCursor { direction: CursorDirection::Prev, .. }) => {
+            rows.reverse();
+            (true, has_more)
+        }
+    };
+    KeysetPage {
+        next_cursor: rows.last().filter(|_| has_next).map(|row| cursor(row, CursorDirection::Next)),
+        prev_cursor: rows.first().filter(|_| has_prev).map(|row| cursor(row, CursorDirection::Prev)),
+        items: rows,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_for_page() {
+        assert_eq!(offset_for_page(0, 50), Some(0));
+        assert_eq!(offset_for_page(3, 50), Some(100));
+        assert_eq!(offset_for_page(u32::MAX, 100), None);
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = PageCursor {
+            created_at: DateTime::from_timestamp_micros(1_700_000_000_123_456).unwrap(),
+            id: Uuid::new_v4(),
+            direction: CursorDirection::Prev,
+        };
+        assert_eq!(PageCursor::decode(&cursor.encode()).unwrap(), cursor);
+        assert!(PageCursor::decode("not-a-cursor").is_err());
+    }
+
+    #[test]
+    fn test_keyset_page() {
+        let at = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let rows: Vec<(DateTime<Utc>, Uuid)> = (0..3).map(|_| (at, Uuid::new_v4())).collect();
+
+        let first = keyset_page(rows.clone(), 2, &PagePosition::Offset(0), |row| *row);
+        assert_eq!(first.items.len(), 2);
+        assert!(first.next_cursor.is_some());
+        assert!(first.prev_cursor.is_none());
+
+        let cursor = PageCursor { created_at: at, id: Uuid::new_v4(), direction: CursorDirection::Prev };
+        let back = keyset_page(rows.clone(), 2, &PagePosition::Cursor(cursor), |row| *row);
+        assert_eq!(back.items, vec![rows[1], rows[0]]);
+        assert!(back.next_cursor.is_some());
+        assert!(back.prev_cursor.is_some());
+    }
+}
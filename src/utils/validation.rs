@@ -21,9 +21,12 @@ pub fn validate_username(username: &str) -> bool {
 }
 pub fn validate_password(password: &str) -> bool {
     let regex = PASSWORD_REGEX.get_or_init(|| {
-        Regex::new(r"^(?=.*[a-z])(?=.*[A-Z])(?=.*\d)[a-zA-Z\d@$!%*?&]{8,}$").unwrap()
+        Regex::new(r"^[a-zA-Z\d@$!%*?&]{8,}$").unwrap()
     });
     regex.is_match(password)
+        && password.chars().any(|c| c.is_ascii_lowercase())
+        && password.chars().any(|c| c.is_ascii_uppercase())
+        && password.chars().any(|c| c.is_ascii_digit())
 }
 pub fn validate_uuid(uuid_str: &str) -> bool {
     uuid::Uuid::parse_str(uuid_str).is_ok()